@@ -17,11 +17,42 @@
 use proc_macro2::{Ident, Literal, Span, TokenStream, TokenTree};
 use quote::{quote, quote_spanned};
 use syn::spanned::Spanned;
-use syn::{Error, ExprPath, Meta, NestedMeta};
+use syn::{Error, Expr, ExprPath, Meta, NestedMeta}; ////
 
 const BASE_ATTR_PATH: &str = "druid";
 const IGNORE_ATTR_PATH: &str = "ignore";
 const SAME_FN_ATTR_PATH: &str = "same_fn";
+const WIDGETS_ATTR_PATH: &str = "widgets"; ////
+const INIT_ATTR_PATH: &str = "init"; ////
+const APP_STATE_ATTR_PATH: &str = "app_state"; ////
+const MAX_WIDGETS_ATTR_PATH: &str = "max_widgets"; ////
+const MAX_WINDOWS_ATTR_PATH: &str = "max_windows"; ////
+const DELEGATE_ATTR_PATH: &str = "delegate"; ////
+
+/// The #[druid(attributes)] on a struct or enum
+pub struct ContainerAttrs {
+    /// `true` if this is the Application State type, which owns the static Widgets and Windows
+    pub app_state: bool,
+    /// The `widget_set!` enum that stores the Widgets, e.g. `MyWidgets`
+    pub widgets: Option<ExprPath>,
+    /// The initial value of the type, if `ConstDefault` should be derived
+    pub init: Option<Init>,
+    /// Number of Widget slots in the arena, e.g. `64`. Doesn't change the `Widget` types in
+    /// the arena: `Flex` or `Label` with other capacities need a custom `widgets` set.
+    pub max_widgets: Option<syn::LitInt>,
+    /// Number of Window slots in the arena, including the unused window ID 0
+    pub max_windows: Option<syn::LitInt>,
+    /// The `AppDelegate` type stored in the arena, e.g. `MyDelegate`
+    pub delegate: Option<ExprPath>,
+}
+
+/// How `ConstDefault` is derived
+pub enum Init {
+    /// `#[druid(init)]`: from the init attribute or `ConstDefault` of each field
+    Fields,
+    /// `#[druid(init = "State::new()")]`: from the expression
+    Expr(Box<Expr>),
+}
 
 /// The fields for a struct or an enum variant.
 ////#[derive(Debug)]
@@ -50,6 +81,8 @@ pub struct Field {
     /// `true` if this field should be ignored.
     pub ignore: bool,
     pub same_fn: Option<ExprPath>,
+    /// The initial value of this field, e.g. `Mode::Off`
+    pub init: Option<Expr>, ////
     //TODO: more attrs here
 }
 
@@ -87,6 +120,7 @@ impl Field {
 
         let mut ignore = false;
         let mut same_fn = None;
+        let mut init = None; ////
 
         for attr in field
             .attrs
@@ -115,6 +149,14 @@ impl Field {
                                 let path = parse_lit_into_expr_path(&meta.lit)?;
                                 same_fn = Some(path);
                             }
+                            NestedMeta::Meta(Meta::NameValue(meta))
+                                if meta.path.is_ident(INIT_ATTR_PATH) =>
+                            {
+                                if init.is_some() {
+                                    return Err(Error::new(meta.span(), "Duplicate attribute"));
+                                }
+                                init = Some(parse_lit_into_expr(&meta.lit)?);
+                            }
                             other => return Err(Error::new(other.span(), "Unknown attribute")),
                         }
                     }
@@ -131,6 +173,7 @@ impl Field {
             ident,
             ignore,
             same_fn,
+            init, ////
         })
     }
 
@@ -160,6 +203,133 @@ impl Field {
     }
 }
 
+impl ContainerAttrs { ////
+    pub fn parse_ast(attrs: &[syn::Attribute]) -> Result<Self, Error> {
+        let mut app_state = false;
+        let mut widgets = None;
+        let mut init = None;
+        let mut max_widgets = None;
+        let mut max_windows = None;
+        let mut delegate = None;
+
+        for attr in attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident(BASE_ATTR_PATH))
+        {
+            match attr.parse_meta()? {
+                Meta::List(meta) => {
+                    for nested in meta.nested.iter() {
+                        match nested {
+                            NestedMeta::Meta(Meta::NameValue(meta))
+                                if meta.path.is_ident(WIDGETS_ATTR_PATH) =>
+                            {
+                                if widgets.is_some() {
+                                    return Err(Error::new(meta.span(), "Duplicate attribute"));
+                                }
+                                widgets = Some(parse_lit_into_expr_path(&meta.lit)?);
+                            }
+                            NestedMeta::Meta(Meta::NameValue(meta))
+                                if meta.path.is_ident(INIT_ATTR_PATH) =>
+                            {
+                                if init.is_some() {
+                                    return Err(Error::new(meta.span(), "Duplicate attribute"));
+                                }
+                                init = Some(Init::Expr(Box::new(parse_lit_into_expr(&meta.lit)?)));
+                            }
+                            NestedMeta::Meta(Meta::NameValue(meta))
+                                if meta.path.is_ident(MAX_WIDGETS_ATTR_PATH) =>
+                            {
+                                if max_widgets.is_some() {
+                                    return Err(Error::new(meta.span(), "Duplicate attribute"));
+                                }
+                                max_widgets = Some(parse_lit_into_capacity(&meta.lit)?);
+                            }
+                            NestedMeta::Meta(Meta::NameValue(meta))
+                                if meta.path.is_ident(MAX_WINDOWS_ATTR_PATH) =>
+                            {
+                                if max_windows.is_some() {
+                                    return Err(Error::new(meta.span(), "Duplicate attribute"));
+                                }
+                                max_windows = Some(parse_lit_into_capacity(&meta.lit)?);
+                            }
+                            NestedMeta::Meta(Meta::NameValue(meta))
+                                if meta.path.is_ident(DELEGATE_ATTR_PATH) =>
+                            {
+                                if delegate.is_some() {
+                                    return Err(Error::new(meta.span(), "Duplicate attribute"));
+                                }
+                                delegate = Some(parse_lit_into_expr_path(&meta.lit)?);
+                            }
+                            NestedMeta::Meta(Meta::Path(path)) if path.is_ident(INIT_ATTR_PATH) => {
+                                if init.is_some() {
+                                    return Err(Error::new(nested.span(), "Duplicate attribute"));
+                                }
+                                init = Some(Init::Fields);
+                            }
+                            NestedMeta::Meta(Meta::Path(path)) if path.is_ident(APP_STATE_ATTR_PATH) => {
+                                if app_state {
+                                    return Err(Error::new(nested.span(), "Duplicate attribute"));
+                                }
+                                app_state = true;
+                            }
+                            other => return Err(Error::new(other.span(), "Unknown attribute")),
+                        }
+                    }
+                }
+                other => {
+                    return Err(Error::new(
+                        other.span(),
+                        "Expected attribute list (the form #[druid(one, two)])",
+                    ))
+                }
+            }
+        }
+        if !app_state {
+            let span = widgets.as_ref().map(|w| w.span())
+                .or_else(|| max_widgets.as_ref().map(|m| m.span()))
+                .or_else(|| max_windows.as_ref().map(|m| m.span()))
+                .or_else(|| delegate.as_ref().map(|d| d.span()));
+            if let Some(span) = span {
+                return Err(Error::new(span, "only allowed with `#[druid(app_state)]`"));
+            }
+        }
+        Ok(ContainerAttrs { app_state, widgets, init, max_widgets, max_windows, delegate })
+    }
+
+    /// The tokens for the `AppData::WidgetSlots` type, e.g. `[druid::WidgetType<State>; 64]`
+    pub fn widget_slots_tokens(&self, state_type: &syn::Ident) -> TokenStream {
+        let widgets = self.widgets_tokens(state_type);
+        match self.max_widgets {
+            Some(ref max) => quote!([#widgets; #max]),
+            None => quote!([#widgets; druid::MAX_WIDGETS]),
+        }
+    }
+
+    /// The tokens for the `AppData::WindowSlots` type, e.g. `[druid::WindowSlot<State>; 4]`
+    pub fn window_slots_tokens(&self, state_type: &syn::Ident) -> TokenStream {
+        match self.max_windows {
+            Some(ref max) => quote!([druid::WindowSlot<#state_type>; #max]),
+            None => quote!([druid::WindowSlot<#state_type>; druid::MAX_WINDOWS]),
+        }
+    }
+
+    /// The tokens for the `AppData::Widgets` type
+    pub fn widgets_tokens(&self, state_type: &syn::Ident) -> TokenStream {
+        match self.widgets {
+            Some(ref widgets) => quote!(#widgets<#state_type>),
+            None => quote!(druid::WidgetType<#state_type>),
+        }
+    }
+
+    /// The tokens for the `AppData::Delegate` type, e.g. `MyDelegate`
+    pub fn delegate_tokens(&self) -> TokenStream {
+        match self.delegate {
+            Some(ref delegate) => quote!(#delegate),
+            None => quote!(druid::NoDelegate),
+        }
+    }
+}
+
 fn parse_lit_into_expr_path(lit: &syn::Lit) -> Result<ExprPath, Error> {
     let string = if let syn::Lit::Str(lit) = lit {
         lit
@@ -173,3 +343,19 @@ fn parse_lit_into_expr_path(lit: &syn::Lit) -> Result<ExprPath, Error> {
     let tokens = syn::parse_str(&string.value())?;
     syn::parse2(tokens)
 }
+
+/// Parse `"Mode::Off"` into an expression, keeping the span of the literal for errors
+fn parse_lit_into_expr(lit: &syn::Lit) -> Result<Expr, Error> { ////
+    match lit {
+        syn::Lit::Str(lit) => lit.parse(),
+        _ => Err(Error::new(lit.span(), "expected str, found... something else")),
+    }
+}
+
+/// Parse a capacity like `64`. Widget IDs are 8-bit, so at most 255 Widgets can be created.
+fn parse_lit_into_capacity(lit: &syn::Lit) -> Result<syn::LitInt, Error> { ////
+    match lit {
+        syn::Lit::Int(int) if int.base10_parse::<u8>().map(|n| n > 0).unwrap_or(false) => Ok(int.clone()),
+        _ => Err(Error::new(lit.span(), "expected a number from 1 to 255")),
+    }
+}
@@ -22,14 +22,17 @@ use syn::{spanned::Spanned, Data, DataEnum, DataStruct};
 pub(crate) fn derive_data_impl(
     input: syn::DeriveInput,
 ) -> Result<proc_macro2::TokenStream, syn::Error> {
-    match &input.data {
+    let mut res = match &input.data { ////
         Data::Struct(s) => derive_struct(&input, s),
         Data::Enum(e) => derive_enum(&input, e),
         Data::Union(u) => Err(syn::Error::new(
             u.union_token.span(),
             "Data implementations cannot be derived from unions",
         )),
-    }
+    }?; ////
+    res.extend(crate::init::derive_const_default(&input)?); //// Initial value for `#[druid(init)]`
+    res.extend(crate::widget::derive_widget(&input)?); //// Static Widgets and Windows for `#[druid(app_state)]`
+    Ok(res) ////
 }
 
 fn derive_struct(
@@ -53,15 +56,13 @@ fn derive_struct(
         quote!(true)
     };
 
-    let mut res = quote! {  //// Allow extend
+    let res = quote! {
         impl<#generics_bounds> druid::Data for #ty #generics {
             fn same(&self, other: &Self) -> bool {
                 #diff
             }
         }
     };
-    let widget = crate::widget::derive_widget(ty.clone(), s.clone()) ?; //// Derive static Widgets and Windows
-    res.extend(widget);  //// Append static Widgets and Windows
 
     Ok(res)
 }
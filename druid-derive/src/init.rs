@@ -0,0 +1,120 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The implementation of `ConstDefault` for #[derive(Data)], which initialises the static arena
+
+use crate::attr::{ContainerAttrs, FieldKind, Fields, Init};
+
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+
+/// Derive `const DEFAULT` from `#[druid(init = "expr")]` on the type, or else from the
+/// `init` attribute or `ConstDefault` of each field. Only derived for `#[druid(init)]`,
+/// `#[druid(app_state)]` or a field with `init`, so plain `Data` types need nothing more.
+pub(crate) fn derive_const_default(input: &syn::DeriveInput) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let ty = &input.ident;
+    let attrs = ContainerAttrs::parse_ast(&input.attrs)?;
+    let value = match (attrs.init, &input.data) {
+        (Some(Init::Expr(init)), _) => quote!(#init),
+        (init, syn::Data::Struct(s)) => {
+            let field_init = Fields::parse_ast(&s.fields)?.iter().any(|f| f.init.is_some());
+            if init.is_none() && !attrs.app_state && !field_init {
+                return Ok(quote!());
+            }
+            struct_value(ty, &s.fields)?
+        }
+        //  Enums have no obvious default, so they don't get `ConstDefault` unless asked for
+        (None, _) if !attrs.app_state => return Ok(quote!()),
+        (_, _) => {
+            return Err(syn::Error::new(
+                ty.span(),
+                "enums need an initial value, e.g. `#[druid(init = \"Mode::Off\")]`",
+            ))
+        }
+    };
+
+    let bounds = input.generics.params.iter().map(|gp| {
+        use syn::GenericParam::*;
+        match gp {
+            Type(ty) => quote_spanned!(ty.span()=> #ty : ::druid::ConstDefault),
+            Lifetime(lf) => quote!(#lf),
+            Const(cst) => quote!(#cst),
+        }
+    });
+    let generics = &input.generics;
+    Ok(quote! {
+        impl<#( #bounds, )*> ::druid::ConstDefault for #ty #generics {
+            const DEFAULT: Self = #value;
+        }
+    })
+}
+
+/// The value of a struct, e.g. `State { count: <u32 as ::druid::ConstDefault>::DEFAULT }`
+fn struct_value(ty: &syn::Ident, fields: &syn::Fields) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let parsed = Fields::parse_ast(fields)?;
+    let values = parsed.iter().zip(fields.iter()).map(|(field, syn_field)| {
+        let field_ty = &syn_field.ty;
+        match field.init {
+            Some(ref init) => quote!(#init),
+            None => quote_spanned!(field_ty.span()=> <#field_ty as ::druid::ConstDefault>::DEFAULT),
+        }
+    });
+    Ok(match (fields, &parsed.kind) {
+        (syn::Fields::Unit, _) => quote!(#ty),
+        (_, FieldKind::Named) => {
+            let idents = parsed.iter().map(|field| field.ident_tokens());
+            quote!(#ty { #( #idents: #values ),* })
+        }
+        (_, FieldKind::Unnamed) => quote!(#ty( #( #values ),* )),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn init_attributes_and_defaults() {
+        let input: syn::DeriveInput = syn::parse_quote! {
+            struct Reading(#[druid(init = "-1")] i32, bool);
+        };
+        assert!(derive_const_default(&input).unwrap().to_string().contains("Reading (- 1 ,"));
+        let input: syn::DeriveInput = syn::parse_quote! {
+            #[druid(init)]
+            struct Reading(i32, bool);
+        };
+        let tokens = derive_const_default(&input).unwrap().to_string();
+        assert!(tokens.contains("const DEFAULT : Self = Reading (< i32 as :: druid :: ConstDefault > :: DEFAULT , < bool"));
+        //  Plain `Data` types don't need `ConstDefault` fields
+        let input: syn::DeriveInput = syn::parse_quote! {
+            struct Entry { path: PathBuf }
+        };
+        assert!(derive_const_default(&input).unwrap().is_empty());
+
+        let input: syn::DeriveInput = syn::parse_quote! {
+            enum Mode { Off, On }
+        };
+        assert!(derive_const_default(&input).unwrap().is_empty());
+        let input: syn::DeriveInput = syn::parse_quote! {
+            #[druid(app_state)]
+            enum Mode { Off, On }
+        };
+        assert!(derive_const_default(&input).is_err());
+        let input: syn::DeriveInput = syn::parse_quote! {
+            #[druid(init = "Mode::Off")]
+            enum Mode { Off, On }
+        };
+        assert!(derive_const_default(&input).unwrap().to_string().contains("const DEFAULT : Self = Mode :: Off"));
+    }
+}
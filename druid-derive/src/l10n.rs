@@ -0,0 +1,341 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The implementation for include_l10n!, which compiles `.ftl` files into message tables,
+//! and l10n_keys!, which generates the keys of the messages
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{LitStr, Token};
+
+/// Locale that must have every message, unless `default = "..."` is given
+const DEFAULT_LOCALE: &str = "en-US";
+
+/// The arguments: `"resources/i18n"` or `"resources/i18n", default = "en-GB"`
+pub(crate) struct L10nInput {
+    path: LitStr,
+    default: Option<LitStr>,
+}
+
+impl Parse for L10nInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path = input.parse()?;
+        let mut default = None;
+        if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let name: syn::Ident = input.parse()?;
+            if name != "default" {
+                return Err(syn::Error::new(name.span(), "expected `default = \"<locale>\"`"));
+            }
+            input.parse::<Token![=]>()?;
+            default = Some(input.parse()?);
+            input.parse::<Option<Token![,]>>()?;
+        }
+        Ok(L10nInput { path, default })
+    }
+}
+
+/// The messages of one locale, read from `<base_dir>/<locale>/*.ftl`
+struct Locale {
+    name: String,
+    files: Vec<PathBuf>,
+    messages: Vec<(String, String)>,
+}
+
+pub(crate) fn include_l10n_impl(input: L10nInput) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let locales = load_locales(&input)?;
+    let files = rebuild_files(&locales);
+    let bundles = locales.iter().map(|locale| {
+        let name = &locale.name;
+        let keys = locale.messages.iter().map(|(k, _)| k);
+        let patterns = locale.messages.iter().map(|(_, v)| v);
+        quote! {
+            druid::Bundle {
+                locale: #name,
+                messages: &[ #( (#keys, #patterns) ),* ],
+            }
+        }
+    });
+    let count = locales.len();
+    Ok(quote! {
+        {
+            #files
+            static BUNDLES: [druid::Bundle; #count] = [ #( #bundles ),* ];
+            &BUNDLES[..]
+        }
+    })
+}
+
+/// Generate an `L10nKey` constant for every message of the default locale, e.g.
+/// `HELLO_COUNTER` for `hello-counter`. Terms are private to the resources, so they get none.
+pub(crate) fn l10n_keys_impl(input: L10nInput) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let locales = load_locales(&input)?;
+    let files = rebuild_files(&locales);
+    let mut names: Vec<String> = Vec::new();
+    let mut keys = Vec::new();
+    for (key, pattern) in locales[0].messages.iter().filter(|(k, _)| !k.starts_with('-')) {
+        let name = const_name(key);
+        if names.contains(&name) {
+            let msg = format!("message `{}` has the same constant name `{}` as another message", key, name);
+            return Err(syn::Error::new(input.path.span(), msg));
+        }
+        let ident = syn::Ident::new(&name, input.path.span());
+        let doc = format!("`{}`: {}", key, pattern.lines().next().unwrap_or_default());
+        keys.push(quote! {
+            #[doc = #doc]
+            pub const #ident: druid::L10nKey = druid::L10nKey::unchecked(#key);
+        });
+        names.push(name);
+    }
+    Ok(quote! {
+        #files
+        #( #keys )*
+    })
+}
+
+/// Read the resources and check the messages, with the default locale first
+fn load_locales(input: &L10nInput) -> Result<Vec<Locale>, syn::Error> {
+    let span = input.path.span();
+    let error = |msg: String| syn::Error::new(span, msg);
+    //  Paths are relative to the crate that calls the macro, like `include_str!`
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").map_err(|e| error(e.to_string()))?;
+    let base_dir = Path::new(&manifest_dir).join(input.path.value());
+    let default = input
+        .default
+        .as_ref()
+        .map(|lit| lit.value())
+        .unwrap_or_else(|| DEFAULT_LOCALE.to_string());
+
+    let mut locales = read_locales(&base_dir).map_err(|e| error(format!("{}: {}", base_dir.display(), e)))?;
+    //  The default locale goes first, the rest are sorted by name
+    let default_index = locales
+        .iter()
+        .position(|l| l.name == default)
+        .ok_or_else(|| error(format!("no resources for default locale {} in {}", default, base_dir.display())))?;
+    let default_locale = locales.remove(default_index);
+    locales.insert(0, default_locale);
+
+    check_messages(&locales).map_err(error)?;
+    Ok(locales)
+}
+
+/// Rebuild when the resources change
+fn rebuild_files(locales: &[Locale]) -> proc_macro2::TokenStream {
+    let files = locales
+        .iter()
+        .flat_map(|l| l.files.iter())
+        .map(|f| f.to_string_lossy().into_owned());
+    quote! {
+        #( const _: &str = include_str!(#files); )*
+    }
+}
+
+/// The constant name of a message key, e.g. `STEPS_TITLE` for `steps.title`
+fn const_name(key: &str) -> String {
+    key.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect()
+}
+
+/// Read every `<locale>/*.ftl` file in the directory
+fn read_locales(base_dir: &Path) -> std::io::Result<Vec<Locale>> {
+    let mut locales = Vec::new();
+    for entry in fs::read_dir(base_dir)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let mut files: Vec<PathBuf> = fs::read_dir(&path)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().map(|ext| ext == "ftl").unwrap_or(false))
+            .collect();
+        files.sort();
+        let mut messages = Vec::new();
+        for file in &files {
+            let source = fs::read_to_string(file)?;
+            let parsed = parse_ftl(&source).map_err(|e| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", file.display(), e))
+            })?;
+            messages.extend(parsed);
+        }
+        locales.push(Locale { name, files, messages });
+    }
+    locales.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(locales)
+}
+
+/// Every message must be defined in the default locale, and only once per locale
+fn check_messages(locales: &[Locale]) -> Result<(), String> {
+    let default = &locales[0];
+    for locale in locales {
+        for (i, (key, _)) in locale.messages.iter().enumerate() {
+            if locale.messages[..i].iter().any(|(k, _)| k == key) {
+                return Err(format!("message `{}` is defined twice in {}", key, locale.name));
+            }
+            if !default.messages.iter().any(|(k, _)| k == key) {
+                return Err(format!(
+                    "message `{}` in {} is missing from the default locale {}",
+                    key, locale.name, default.name
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parse the messages and terms of a Fluent resource into keys and patterns.
+/// Attributes are keyed as `message.attribute`. Multiline patterns are dedented.
+fn parse_ftl(source: &str) -> Result<Vec<(String, String)>, String> {
+    let mut messages: Vec<(String, String)> = Vec::new();
+    let lines: Vec<&str> = source.lines().collect();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        i += 1;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with(char::is_whitespace) {
+            return Err(format!("line {}: unexpected indented line `{}`", i, line.trim()));
+        }
+        let eq = line.find('=').ok_or_else(|| format!("line {}: expected `key = value`", i))?;
+        let key = line[..eq].trim();
+        if !is_identifier(key.strip_prefix('-').unwrap_or(key)) {
+            return Err(format!("line {}: invalid message key `{}`", i, key));
+        }
+        //  Continuation lines are indented, blank lines may separate them
+        let start = i;
+        while i < lines.len() && (lines[i].starts_with(char::is_whitespace) || lines[i].trim().is_empty()) {
+            i += 1;
+        }
+        let mut block = &lines[start..i];
+        while let Some((last, rest)) = block.split_last() {
+            if !last.trim().is_empty() {
+                break;
+            }
+            block = rest;
+        }
+        //  Attributes start with `.` and end the value of the message
+        let value_len = block.iter().position(|l| l.trim_start().starts_with('.')).unwrap_or(block.len());
+        let value = join_pattern(line[eq + 1..].trim(), &block[..value_len]);
+        check_pattern(&value).map_err(|e| format!("line {}: {} in `{}`", start, e, key))?;
+        if !value.is_empty() {
+            messages.push((key.to_string(), value));
+        }
+        for attr in &block[value_len..] {
+            let attr = attr.trim();
+            if !attr.starts_with('.') {
+                return Err(format!("line {}: multiline attributes are not supported in `{}`", start, key));
+            }
+            let eq = attr.find('=').ok_or_else(|| format!("line {}: expected `.attribute = value`", start))?;
+            let value = attr[eq + 1..].trim().to_string();
+            check_pattern(&value).map_err(|e| format!("line {}: {} in `{}`", start, e, key))?;
+            messages.push((format!("{}.{}", key, attr[1..eq].trim()), value));
+        }
+    }
+    Ok(messages)
+}
+
+/// Join the first line of a pattern with its dedented continuation lines
+fn join_pattern(first: &str, continuation: &[&str]) -> String {
+    let indent = continuation
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let mut lines: Vec<&str> = Vec::new();
+    if !first.is_empty() {
+        lines.push(first);
+    }
+    for line in continuation {
+        lines.push(line.get(indent..).unwrap_or("").trim_end());
+    }
+    lines.join("\n")
+}
+
+/// Placeables must be balanced, so that the formatter can't fail at runtime
+fn check_pattern(pattern: &str) -> Result<(), &'static str> {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in pattern.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' if depth > 0 => in_string = !in_string,
+            _ if in_string => {}
+            '{' => depth += 1,
+            '}' if depth == 0 => return Err("unbalanced `}`"),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    if depth == 0 {
+        Ok(())
+    } else {
+        Err("unclosed `{`")
+    }
+}
+
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars.next().map(|c| c.is_ascii_alphabetic()).unwrap_or(false)
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_messages_terms_and_selects() {
+        let source = "\
+# Comment
+-app-name = Druid
+
+hello-counter = Current value is { $count }
+steps =
+    { $count ->
+        [one] One step
+       *[other] { $count } steps
+    }
+    .title = Steps
+";
+        let messages = parse_ftl(source).unwrap();
+        assert_eq!(
+            messages,
+            vec![
+                ("-app-name".to_string(), "Druid".to_string()),
+                ("hello-counter".to_string(), "Current value is { $count }".to_string()),
+                (
+                    "steps".to_string(),
+                    "{ $count ->\n    [one] One step\n   *[other] { $count } steps\n}".to_string()
+                ),
+                ("steps.title".to_string(), "Steps".to_string()),
+            ]
+        );
+        assert!(parse_ftl("broken = { $count").is_err());
+    }
+
+    #[test]
+    fn key_constant_names() {
+        assert_eq!(const_name("hello-counter"), "HELLO_COUNTER");
+        assert_eq!(const_name("steps.title"), "STEPS_TITLE");
+        assert_eq!(const_name("steps_title"), "STEPS_TITLE");
+    }
+}
@@ -63,6 +63,7 @@ fn derive_struct(input: &syn::DeriveInput) -> Result<proc_macro2::TokenStream, s
 
         quote! {
             #[allow(non_camel_case_types)]
+            #[derive(Clone, Copy, Debug, Default)] //// Lenses are stored in `LensWrap` widgets
             pub struct #field_name;
         }
     });
@@ -18,6 +18,8 @@ extern crate proc_macro;
 
 mod attr;
 mod data;
+mod init; //// To initialise the static Application Data
+mod l10n; //// To compile Fluent resources into Flash ROM
 mod lens;
 mod widget; //// To support static Widgets and Windows
 
@@ -39,3 +41,27 @@ pub fn derive_lens(input: TokenStream) -> TokenStream {
         .unwrap_or_else(|err| err.to_compile_error())
         .into()
 }
+
+/// Compile the Fluent `.ftl` files in `<dir>/<locale>/` into static message tables,
+/// e.g. `druid::include_l10n!("resources/i18n")`. The path is relative to the crate root.
+/// Returns a `&'static [druid::Bundle]` with the default locale (`en-US` unless
+/// `default = "..."` is given) first. Every message must be defined in the default locale.
+#[proc_macro]
+pub fn include_l10n(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as l10n::L10nInput);
+    l10n::include_l10n_impl(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Generate a `druid::L10nKey` constant for every message in the default locale of the
+/// Fluent `.ftl` files in `<dir>/<locale>/`, e.g. `HELLO_COUNTER` for `hello-counter`.
+/// Takes the same arguments as `include_l10n!`. A key that is missing from the default
+/// locale fails to compile, e.g. `mod keys { druid::l10n_keys!("resources/i18n"); }`.
+#[proc_macro]
+pub fn l10n_keys(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as l10n::L10nInput);
+    l10n::l10n_keys_impl(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
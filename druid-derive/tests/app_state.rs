@@ -0,0 +1,60 @@
+//! Test #[derive(Data)] with #[druid(app_state)]
+
+use druid::{AppData, ArenaCell, ConstDefault, Data};
+
+/// Nested in `Watch`, so it's plain `Data` with a `ConstDefault` and owns no arena
+#[derive(Clone, Data, Default)]
+#[druid(init)]
+struct Settings {
+    dark: bool,
+}
+
+//  Two app_state types in one module don't clash: each has its own arena and `handle_touch`
+#[derive(Clone, Data, Default)]
+#[druid(app_state)]
+struct Watch {
+    steps: u32,
+    settings: Settings,
+}
+
+#[derive(Clone, Data, Default)]
+#[druid(app_state)]
+struct Phone {
+    battery: u8,
+}
+
+/// Tells apart the types that own an arena. The inherent method is picked if `T: AppData`,
+/// otherwise the method of `NoArena` is picked.
+struct Probe<T>(core::marker::PhantomData<T>);
+
+impl<T: AppData> Probe<T> {
+    fn has_arena(&self) -> bool {
+        true
+    }
+}
+
+trait NoArena {
+    fn has_arena(&self) -> bool {
+        false
+    }
+}
+
+impl<T> NoArena for Probe<T> {}
+
+#[test]
+fn app_states_in_one_module() {
+    let watch: &ArenaCell<Watch> = Watch::storage();
+    let phone: &ArenaCell<Phone> = Phone::storage();
+    assert_ne!(watch as *const _ as usize, phone as *const _ as usize);
+
+    //  Namespaced by the type, so both compile side by side
+    let _: fn(u16, u16) = Watch::handle_touch;
+    let _: fn(u16, u16) = Phone::handle_touch;
+}
+
+#[test]
+fn nested_data_has_no_arena() {
+    assert!(Probe::<Watch>(Default::default()).has_arena());
+    assert!(!Probe::<Settings>(Default::default()).has_arena());
+    assert!(Settings::DEFAULT.same(&Settings { dark: false }));
+}
@@ -22,7 +22,12 @@
 #![deny(intra_doc_link_resolution_failure)]
 #![allow(clippy::new_without_default)]
 
-pub use piet_common as piet;
+////pub use piet_common as piet;
+/// The piet API, with the render context of the platform, e.g. the framebuffer on the headless platform
+pub mod piet { ////
+    pub use piet_common::*;
+    pub use crate::platform::Piet;
+} ////
 pub use piet_common::kurbo;
 
 #[cfg(target_os = "windows")]
@@ -33,8 +38,8 @@ extern crate winapi;
 #[macro_use]
 extern crate objc;
 
-#[cfg(not(any(feature = "use_gtk", target_os = "linux")))]
-#[macro_use]
+////#[cfg(not(any(feature = "use_gtk", target_os = "linux")))]
+////#[macro_use]
 ////extern crate lazy_static;
 
 mod application;
@@ -51,6 +56,7 @@ mod mouse;
 //a bunch of compiler warnings, so let's revisit that later.
 pub mod platform;
 mod runloop;
+mod timer; ////
 mod window;
 
 pub use application::Application;
@@ -62,6 +68,7 @@ pub use error::Error;
 ////pub use keycodes::KeyCode;
 ////pub use menu::Menu;
 pub use mouse::{Cursor, MouseButton, MouseEvent};
-////pub use runloop::RunLoop;
+pub use runloop::{focused_window, next_deadline, post_event, post_touch, QueuedEvent, RunLoop, ANIM_FRAME_INTERVAL, MAX_QUEUED_EVENTS}; ////
+pub use timer::{set_clock, Clock, FakeClock, Instant, MAX_TIMERS}; ////
 pub use window::{Text, TimerToken, WinCtx, WinHandler, WindowIdType, WindowBuilder, WindowHandle};
 pub use platform::{DruidContext, start_display, draw_to_display, set_display_pixels, show_touch}; ////
\ No newline at end of file
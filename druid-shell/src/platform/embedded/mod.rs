@@ -24,4 +24,5 @@ pub mod runloop;
 pub mod util;
 pub mod window;
 pub use piet_common::{ start_display, draw_to_display, set_display_pixels, show_touch }; ////
+pub use piet_common::Piet; ////
 pub use window::DruidContext; ////
\ No newline at end of file
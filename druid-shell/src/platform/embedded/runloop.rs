@@ -14,6 +14,31 @@
 
 //! embedded-graphics implementation of runloop.
 
+use crate::timer::{Clock, Instant}; ////
+
+extern "C" { ////
+    /// Microseconds since Mynewt started
+    fn os_get_uptime_usec() -> i64; ////
+} ////
+
+/// The monotonic clock, backed by the Mynewt uptime
+pub struct PlatformClock; ////
+
+impl Clock for PlatformClock { ////
+    fn now(&self) -> Instant {
+        let usec = unsafe { os_get_uptime_usec() };
+        Instant::from_millis(usec as u64 / 1000)
+    }
+}
+
+/// Sleep until an interrupt arrives, which may post more input for the `RunLoop`.
+/// The OS tick interrupt also wakes the `RunLoop`, so that expired timers are fired.
+/// Returns true because the `RunLoop` never stops on embedded platforms.
+pub fn wait_for_event() -> bool { ////
+    cortex_m::asm::wfi();
+    true
+}
+
 /* ////
 use std::cell::RefCell;
 
@@ -44,27 +44,33 @@ use crate::piet::{Piet, RenderContext};
 ////use crate::keyboard;
 use crate::mouse::{Cursor}; ////
 ////use crate::mouse::{Cursor, MouseButton, MouseEvent};
-use crate::window::{Text, WinCtx, WinHandler, WindowIdType}; ////
+use crate::timer::Instant; ////
+use crate::window::{Text, TimerToken, WinCtx, WinHandler, WindowIdType}; ////
 ////use crate::window::{Text, TimerToken, WinCtx, WinHandler};
 use crate::Error;
 
 static mut TEXT: crate::piet::PietText = crate::piet::PietText{};
-static mut DRUID_CONTEXT: DruidContext = DruidContext {};
 static mut PIET_CONTEXT: Piet = Piet {};
 
-pub struct DruidContext {}
+pub struct DruidContext { ////
+    /// Timers requested through this context fire for this window
+    window_id: WindowIdType, ////
+} ////
 impl WinCtx for DruidContext {
     fn invalidate(&mut self) {
-        cortex_m::asm::bkpt(); ////
+        crate::runloop::invalidate_all(); ////
     }
     fn text_factory(&mut self) -> &mut Text { 
         unsafe { &mut TEXT }
     }
     fn set_cursor(&mut self, _cursor: &Cursor) { panic!("no set cursor") }
+    fn request_timer(&mut self, deadline: Instant) -> TimerToken { ////
+        crate::timer::request_timer(self.window_id, deadline)
+    }
 }
 impl DruidContext {
-    pub fn new() -> Self {
-        DruidContext {}
+    pub fn new(window_id: WindowIdType) -> Self { ////
+        DruidContext { window_id } ////
     }
 }
 
@@ -366,14 +372,24 @@ impl<THandler: WinHandler<THandler> + Clone + Default> WindowBuilder<THandler> {
 impl<THandler: WinHandler<THandler> + Clone + Default> WindowHandle<THandler> {  ////  THandler is DruidHandler<T: Data + 'static>
 ////impl WindowHandle {
     pub fn show(&self) {
-        unsafe { self.state.handler.clone().paint(&mut PIET_CONTEXT, &mut DRUID_CONTEXT); }
+        let mut ctx = DruidContext::new(self.window_id); ////
+        let request_anim = unsafe { self.state.handler.clone().paint(&mut PIET_CONTEXT, &mut ctx) }; ////
         if let Err(_) = unsafe { PIET_CONTEXT.finish() } {
             panic!("piet error on render");
         }
+        if request_anim { ////
+            crate::runloop::request_anim_frame(self.window_id);
+        } ////
+    }
+
+    /// Add the window to the `RunLoop`, for windows built after the `RunLoop` started
+    pub fn open(&self) {
+        crate::runloop::open_window(self.window_id); ////
     }
 
-    /// Close the window.
+    /// Close the window. The window is destroyed and removed by the `RunLoop`.
     pub fn close(&self) {
+        crate::runloop::close_window(self.window_id); ////
         /* ////
             if let Some(state) = self.state.upgrade() {
                 with_application(|app| {
@@ -385,13 +401,14 @@ impl<THandler: WinHandler<THandler> + Clone + Default> WindowHandle<THandler> {
 
     /// Bring this window to the front of the window stack and give it focus.
     pub fn bring_to_front_and_focus(&self) {
-        //FIXME: implementation goes here
+        ////FIXME: implementation goes here
+        crate::runloop::focus_window(self.window_id); //// Shown on the display by the `RunLoop`
         ////log::warn!("bring_to_front_and_focus not yet implemented for gtk");
     }
 
     // Request invalidation of the entire window contents.
     pub fn invalidate(&self) {
-        self.show();
+        crate::runloop::invalidate_window(self.window_id); //// Repainted by the `RunLoop`
         /* ////
             if let Some(state) = self.state.upgrade() {
                 state.window.queue_draw();
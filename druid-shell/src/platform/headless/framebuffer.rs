@@ -0,0 +1,181 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! In-memory RGB565 framebuffer that stands in for the display on the host.
+
+use core::cell::RefCell;
+use critical_section::Mutex;
+use super::error::Error;
+
+/// Width of the framebuffer in pixels, same as the PineTime display
+pub const WIDTH: usize = 240;
+/// Height of the framebuffer in pixels, same as the PineTime display
+pub const HEIGHT: usize = 240;
+
+/// The headless display: RGB565 pixels plus the state that the display and
+/// touch drivers would keep on the device.
+pub struct Framebuffer {
+    pixels: [u16; WIDTH * HEIGHT],
+    /// Number of frames painted since the display was started
+    frames: u32,
+    /// Coordinates of the last touch passed to `show_touch`
+    last_touch: Option<(u16, u16)>,
+    /// True if a window has requested a repaint that hasn't been taken yet
+    invalidated: bool,
+}
+
+/// The framebuffer for the headless display
+static FRAMEBUFFER: Mutex<RefCell<Framebuffer>> = Mutex::new(RefCell::new(Framebuffer::new()));
+
+/// Run `f` with the headless framebuffer. Used by the headless render context to
+/// paint, and by tests and tools to inspect what was drawn. Panics if called from
+/// inside `f`, e.g. when `f` runs the `RunLoop`.
+pub fn with_framebuffer<R>(f: impl FnOnce(&mut Framebuffer) -> R) -> R {
+    critical_section::with(|cs| f(&mut FRAMEBUFFER.borrow(cs).borrow_mut()))
+}
+
+impl Framebuffer {
+    /// Create a framebuffer cleared to black
+    pub const fn new() -> Self {
+        Framebuffer {
+            pixels: [0; WIDTH * HEIGHT],
+            frames: 0,
+            last_touch: None,
+            invalidated: false,
+        }
+    }
+
+    /// All pixels in row-major order, as RGB565
+    pub fn pixels(&self) -> &[u16] {
+        &self.pixels
+    }
+
+    /// Return the RGB565 pixel at (x,y), or `None` if outside the framebuffer
+    pub fn pixel(&self, x: usize, y: usize) -> Option<u16> {
+        if x >= WIDTH || y >= HEIGHT { return None; }
+        Some(self.pixels[y * WIDTH + x])
+    }
+
+    /// Set the RGB565 pixel at (x,y). Pixels outside the framebuffer are clipped.
+    pub fn set_pixel(&mut self, x: i32, y: i32, color: u16) {
+        if x < 0 || y < 0 || x as usize >= WIDTH || y as usize >= HEIGHT { return; }
+        self.pixels[y as usize * WIDTH + x as usize] = color;
+    }
+
+    /// Fill the entire framebuffer with the RGB565 colour
+    pub fn fill(&mut self, color: u16) {
+        for pixel in self.pixels.iter_mut() { *pixel = color; }
+    }
+
+    /// Number of frames painted since the display was started
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Coordinates of the last touch shown on the display
+    pub fn last_touch(&self) -> Option<(u16, u16)> {
+        self.last_touch
+    }
+
+    /// Return true if a repaint was requested since the last call, and clear the request
+    pub fn take_invalidated(&mut self) -> bool {
+        let invalidated = self.invalidated;
+        self.invalidated = false;
+        invalidated
+    }
+
+    /// Copy the framebuffer into `out` as RGBA8888, 4 bytes per pixel in row-major order
+    pub fn write_rgba(&self, out: &mut [u8]) {
+        assert!(out.len() >= WIDTH * HEIGHT * 4, "rgba buffer too small");
+        for (pixel, rgba) in self.pixels.iter().zip(out.chunks_mut(4)) {
+            rgba.copy_from_slice(&rgb565_to_rgba(*pixel));
+        }
+    }
+
+    /// Record that a frame has been painted
+    pub(crate) fn frame_painted(&mut self) {
+        self.frames += 1;
+    }
+
+    /// Record that a window has requested a repaint
+    pub(crate) fn invalidate(&mut self) {
+        self.invalidated = true;
+    }
+}
+
+/// Convert an RGB565 colour to RGBA8888 bytes
+pub fn rgb565_to_rgba(color: u16) -> [u8; 4] {
+    let r = ((color >> 11) & 0x1f) as u8;
+    let g = ((color >> 5) & 0x3f) as u8;
+    let b = (color & 0x1f) as u8;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+        0xff,
+    ]
+}
+
+/// Convert a colour in RGBA8888 format (e.g. from `Color::as_rgba_u32`) to RGB565. Alpha is ignored.
+pub fn rgba_to_rgb565(rgba: u32) -> u16 {
+    let r = ((rgba >> 24) & 0xff) as u16;
+    let g = ((rgba >> 16) & 0xff) as u16;
+    let b = ((rgba >> 8) & 0xff) as u16;
+    ((r >> 3) << 11) | ((g >> 2) << 5) | (b >> 3)
+}
+
+/// Start the headless display. Clears the framebuffer to black.
+pub fn start_display() -> Result<(), Error> {
+    with_framebuffer(|fb| *fb = Framebuffer::new());
+    Ok(())
+}
+
+/// Draw the pixels to the framebuffer. Each pixel is `(x, y, rgb565)`,
+/// pixels outside the framebuffer are clipped.
+pub fn draw_to_display<T>(item: T)
+where
+    T: IntoIterator<Item = (i32, i32, u16)>,
+{
+    with_framebuffer(|fb| {
+        for (x, y, color) in item {
+            fb.set_pixel(x, y, color);
+        }
+    });
+}
+
+/// Set the pixels in the window from (left,top) to (right,bottom) inclusive,
+/// in row-major order. Same as the batched display driver on the device.
+pub fn set_display_pixels<T>(left: u16, top: u16, right: u16, bottom: u16, colors: T) -> Result<(), Error>
+where
+    T: IntoIterator<Item = u16>,
+{
+    if left > right || top > bottom { return Err(Error); }
+    with_framebuffer(|fb| {
+        let width = (right - left + 1) as usize;
+        for (i, color) in colors.into_iter().enumerate() {
+            let x = left as usize + i % width;
+            let y = top as usize + i / width;
+            if y > bottom as usize { break; }
+            fb.set_pixel(x as i32, y as i32, color);
+        }
+    });
+    Ok(())
+}
+
+/// Record a touch at (x,y). The device draws a marker on the display, the
+/// headless display only remembers the coordinates.
+pub fn show_touch(x: u16, y: u16) -> Result<(), Error> {
+    with_framebuffer(|fb| fb.last_touch = Some((x, y)));
+    Ok(())
+}
@@ -0,0 +1,30 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Headless platform support, for running druid on a host without a display.
+//!
+//! Windows are painted into an in-memory RGB565 framebuffer the same size as
+//! the PineTime screen, which can be inspected (or converted to RGBA) by
+//! tests and tools running on the host. `Piet` is the render context that
+//! paints into the framebuffer.
+
+pub mod application;
+pub mod error;
+pub mod framebuffer;
+pub mod piet;
+pub mod runloop;
+pub mod window;
+pub use framebuffer::{ start_display, draw_to_display, set_display_pixels, show_touch }; ////
+pub use piet::Piet; ////
+pub use window::DruidContext; ////
@@ -0,0 +1,167 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Render context that paints into the in-memory framebuffer, in place of the
+//! `embedded-graphics` render context that paints the display on the device.
+//!
+//! Shapes are filled and stroked at pixel centres without anti-aliasing. The headless
+//! display has no fonts, so text is drawn as its filled bounding box.
+
+use heapless::Vec;
+use piet_common::{Color, Error, PietText, PietTextLayout, RenderContext, TextLayout};
+use crate::kurbo::{Affine, Point, Rect, Shape, Vec2};
+
+use super::framebuffer::{rgba_to_rgb565, with_framebuffer, HEIGHT, WIDTH};
+
+/// Max number of `save` calls without a matching `restore`
+const MAX_SAVED_STATES: usize = 16;
+
+/// Height of the text bounding box above the baseline, same as the 12x16 font on the device
+const TEXT_HEIGHT: f64 = 16.0;
+
+/// Transform and clip set by the `Widget`s, saved by `save` and restored by `restore`
+#[derive(Clone, Copy)]
+struct State {
+    transform: Affine,
+    /// Clip rectangle in framebuffer coordinates
+    clip: Rect,
+}
+
+/// Render context for the headless display. Created for each paint of a window.
+pub struct Piet {
+    state: State,
+    saved: Vec<State, MAX_SAVED_STATES>,
+    text: PietText,
+}
+
+impl Piet {
+    /// Create a render context that paints the whole framebuffer
+    pub fn new() -> Self {
+        Piet {
+            state: State {
+                transform: Affine::translate(Vec2::ZERO),
+                clip: Rect::new(0.0, 0.0, WIDTH as f64, HEIGHT as f64),
+            },
+            saved: Vec::new(),
+            text: PietText {},
+        }
+    }
+
+    /// The rectangle in framebuffer coordinates covered by the rectangle in `Widget` coordinates
+    fn to_framebuffer(&self, rect: Rect) -> Rect {
+        let corners = [
+            self.state.transform * Point::new(rect.x0, rect.y0),
+            self.state.transform * Point::new(rect.x1, rect.y0),
+            self.state.transform * Point::new(rect.x0, rect.y1),
+            self.state.transform * Point::new(rect.x1, rect.y1),
+        ];
+        corners[1..].iter().fold(Rect::from_points(corners[0], corners[0]), |bounds, corner| {
+            bounds.union_pt(*corner)
+        })
+    }
+
+    /// Set the pixels inside the rectangle in `Widget` coordinates whose centres pass
+    /// the `inside` test, which is given the centre in `Widget` coordinates
+    fn paint_pixels(&self, rect: Rect, brush: &Color, inside: impl Fn(Point) -> bool) {
+        let bounds = self.to_framebuffer(rect).intersect(self.state.clip);
+        if bounds.width() <= 0.0 || bounds.height() <= 0.0 {
+            return;
+        }
+        //  Bounds are inside the clip, so they are never negative and `as` rounds down
+        let ceil = |v: f64| if (v as i32) as f64 == v { v as i32 } else { v as i32 + 1 };
+        let inverse = self.state.transform.inverse();
+        let color = rgba_to_rgb565(brush.as_rgba_u32());
+        with_framebuffer(|fb| {
+            for y in bounds.y0 as i32..ceil(bounds.y1) {
+                for x in bounds.x0 as i32..ceil(bounds.x1) {
+                    let centre = inverse * Point::new(x as f64 + 0.5, y as f64 + 0.5);
+                    if inside(centre) {
+                        fb.set_pixel(x, y, color);
+                    }
+                }
+            }
+        });
+    }
+}
+
+impl RenderContext for Piet {
+    type Text = PietText;
+    type TextLayout = PietTextLayout;
+
+    /// Fill the framebuffer, ignoring the transform and clip
+    fn clear(&mut self, color: Color) {
+        let color = rgba_to_rgb565(color.as_rgba_u32());
+        with_framebuffer(|fb| fb.fill(color));
+    }
+
+    fn stroke(&mut self, shape: impl Shape, brush: &Color, width: f64) {
+        let half = width / 2.0;
+        let rect = shape.bounding_box().inflate(half, half);
+        if let Some(line) = shape.as_line() {
+            let length2 = (line.p1 - line.p0).hypot2();
+            self.paint_pixels(rect, brush, |p| {
+                //  Distance from the nearest point on the line
+                let t = if length2 == 0.0 { 0.0 } else { (p - line.p0).dot(line.p1 - line.p0) / length2 };
+                let t = t.clamp(0.0, 1.0);
+                (p - line.p0.lerp(line.p1, t)).hypot2() <= half * half
+            });
+        } else {
+            //  The outline is within `half` of the pixel if the winding number changes nearby
+            let offsets = [Vec2::new(half, 0.0), Vec2::new(-half, 0.0), Vec2::new(0.0, half), Vec2::new(0.0, -half)];
+            self.paint_pixels(rect, brush, |p| {
+                let inside = shape.winding(p) != 0;
+                offsets.iter().any(|offset| (shape.winding(p + *offset) != 0) != inside)
+            });
+        }
+    }
+
+    fn fill(&mut self, shape: impl Shape, brush: &Color) {
+        self.paint_pixels(shape.bounding_box(), brush, |p| shape.winding(p) != 0);
+    }
+
+    fn clip(&mut self, shape: impl Shape) {
+        self.state.clip = self.state.clip.intersect(self.to_framebuffer(shape.bounding_box()));
+    }
+
+    fn text(&mut self) -> &mut PietText {
+        &mut self.text
+    }
+
+    /// Fill the bounding box of the text, from the baseline at `pos` up to the text height
+    fn draw_text(&mut self, layout: &PietTextLayout, pos: impl Into<Point>, brush: &Color) {
+        let pos = pos.into();
+        let bounds = Rect::new(pos.x, pos.y - TEXT_HEIGHT, pos.x + layout.width(), pos.y);
+        self.fill(bounds, brush);
+    }
+
+    /// Fails if `MAX_SAVED_STATES` states are already saved
+    fn save(&mut self) -> Result<(), Error> {
+        self.saved.push(self.state).map_err(|_| Error)
+    }
+
+    fn restore(&mut self) -> Result<(), Error> {
+        if let Some(state) = self.saved.pop() {
+            self.state = state;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn transform(&mut self, transform: Affine) {
+        self.state.transform *= transform;
+    }
+}
@@ -0,0 +1,47 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Headless implementation of runloop.
+
+use crate::timer::{Clock, Instant};
+
+#[cfg(not(target_os = "none"))]
+extern crate std;
+
+/// On the host the clock follows `std::time::Instant`, counting from the first reading.
+/// On embedded targets the headless platform has no time source, so the clock stays at time 0.
+/// Tests that need exact control of time install a `FakeClock` with `set_clock`.
+pub struct PlatformClock;
+
+#[cfg(not(target_os = "none"))]
+impl Clock for PlatformClock {
+    fn now(&self) -> Instant {
+        static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+        let elapsed = START.get_or_init(std::time::Instant::now).elapsed();
+        Instant::from_millis(elapsed.as_millis() as u64)
+    }
+}
+
+#[cfg(target_os = "none")]
+impl Clock for PlatformClock {
+    fn now(&self) -> Instant {
+        Instant::from_millis(0)
+    }
+}
+
+/// Nothing can post input while the headless `RunLoop` is waiting, so the `RunLoop`
+/// stops when there is no more work. Returns false.
+pub fn wait_for_event() -> bool {
+    false
+}
@@ -0,0 +1,197 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Headless window creation and management.
+
+use core::marker::PhantomData;
+use crate::kurbo::{Size};
+use crate::piet::{Piet, RenderContext};
+
+use crate::mouse::{Cursor};
+use crate::timer::Instant;
+use crate::window::{Text, TimerToken, WinCtx, WinHandler, WindowIdType};
+use crate::Error;
+
+use super::framebuffer::{self, with_framebuffer};
+
+pub struct DruidContext {
+    /// Timers requested through this context fire for this window
+    window_id: WindowIdType,
+    text: crate::piet::PietText,
+}
+impl WinCtx for DruidContext {
+    fn invalidate(&mut self) {
+        with_framebuffer(|fb| fb.invalidate());
+        crate::runloop::invalidate_all();
+    }
+    fn text_factory(&mut self) -> &mut Text {
+        &mut self.text
+    }
+    fn set_cursor(&mut self, _cursor: &Cursor) {}
+    fn request_timer(&mut self, deadline: Instant) -> TimerToken {
+        crate::timer::request_timer(self.window_id, deadline)
+    }
+}
+impl DruidContext {
+    pub fn new(window_id: WindowIdType) -> Self {
+        DruidContext { window_id, text: crate::piet::PietText {} }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct WindowHandle<THandler: WinHandler<THandler>> {  //  THandler is DruidHandler<T: Data + 'static>
+    pub window_id: WindowIdType,
+    pub state: WindowState<THandler>,
+}
+
+/// Builder abstraction for creating new windows
+pub struct WindowBuilder<THandler: WinHandler<THandler>> {  //  THandler is DruidHandler<T: Data + 'static>
+    window_id: WindowIdType,
+    handler: THandler,
+    size: Size,
+    phantom: PhantomData<THandler>,  //  Needed to do compile-time checking for `THandler`
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct WindowState<THandler: WinHandler<THandler>> {  //  THandler is DruidHandler<T: Data + 'static>
+    pub window_id: WindowIdType,
+    pub handler: THandler,
+}
+
+impl<THandler: WinHandler<THandler> + Clone + Default> WindowBuilder<THandler> {  //  THandler is DruidHandler<T: Data + 'static>
+    pub fn new() -> Self {
+        WindowBuilder  {
+            window_id: 0,
+            size: Size::new(
+                framebuffer::WIDTH as f64,
+                framebuffer::HEIGHT as f64,
+            ),
+            phantom: PhantomData,
+            handler: Default::default(),
+        }
+    }
+
+    pub fn set_handler(&mut self, handler: THandler) {
+        let window_id = handler.get_window_id();
+        self.window_id = window_id;
+        handler.add_handler(window_id, handler.clone());
+        self.handler = handler;
+    }
+
+    pub fn set_size(&mut self, size: Size) {
+        self.size = size;
+    }
+
+    pub fn build(self) -> Result<WindowHandle<THandler>, Error> {
+        let window_id = self.window_id;
+        let handler = self.handler;
+        let state = WindowState {
+            window_id,
+            handler,
+        };
+        let handle = WindowHandle {
+            window_id,
+            state,
+        };
+        Ok(handle)
+    }
+}
+
+impl<THandler: WinHandler<THandler> + Clone + Default> WindowHandle<THandler> {  //  THandler is DruidHandler<T: Data + 'static>
+    /// Paint the window into the framebuffer.
+    pub fn show(&self) {
+        let mut ctx = DruidContext::new(self.window_id);
+        let mut piet = Piet::new();
+        let request_anim = self.state.handler.clone().paint(&mut piet, &mut ctx);
+        if piet.finish().is_err() {
+            panic!("piet error on render");
+        }
+        if request_anim {
+            crate::runloop::request_anim_frame(self.window_id);
+        }
+        with_framebuffer(|fb| fb.frame_painted());
+    }
+
+    /// Add the window to the `RunLoop`, for windows built after the `RunLoop` started
+    pub fn open(&self) {
+        crate::runloop::open_window(self.window_id);
+    }
+
+    /// Close the window. The window is destroyed and removed by the `RunLoop`.
+    pub fn close(&self) {
+        crate::runloop::close_window(self.window_id);
+    }
+
+    /// Show this window in the framebuffer and give it focus.
+    pub fn bring_to_front_and_focus(&self) {
+        crate::runloop::focus_window(self.window_id);
+    }
+
+    // Request invalidation of the entire window contents. The window is repainted by the `RunLoop`.
+    pub fn invalidate(&self) {
+        with_framebuffer(|fb| fb.invalidate());
+        crate::runloop::invalidate_window(self.window_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kurbo::{Affine, Rect, Vec2};
+    use crate::piet::{Color, FontBuilder, Text, TextLayoutBuilder};
+
+    /// Paints like a `Widget` laid out at (40,40): a red panel with a white border
+    #[derive(Clone, Copy, Default)]
+    struct Panel;
+
+    impl WinHandler<Panel> for Panel {
+        fn paint(&mut self, piet: &mut Piet, _ctx: &mut dyn WinCtx) -> bool {
+            piet.clear(Color::BLACK);
+            piet.save().unwrap();
+            piet.transform(Affine::translate(Vec2::new(40.0, 40.0)));
+            let panel = Rect::new(0.0, 0.0, 100.0, 50.0);
+            piet.fill(panel, &Color::rgb8(0xff, 0, 0));
+            piet.stroke(panel, &Color::WHITE, 2.0);
+            piet.restore().unwrap();
+            let font = piet.text().new_font_by_name("Default", 16.0).build().unwrap();
+            let layout = piet.text().new_text_layout(&font, "Hi").build().unwrap();
+            piet.draw_text(&layout, (10.0, 200.0), &Color::WHITE);
+            for _ in 0..16 {
+                piet.save().unwrap();
+            }
+            assert!(piet.save().is_err(), "saved states should be full");
+            false
+        }
+        fn get_window_id(&self) -> WindowIdType { 1 }
+        fn add_handler(&self, _window_id: WindowIdType, _handler: Panel) {}
+        fn get_handler(&self, _window_id: WindowIdType) -> Panel { Panel }
+    }
+
+    #[test]
+    fn show_paints_pixels() {
+        let mut builder = WindowBuilder::new();
+        builder.set_handler(Panel);
+        builder.build().unwrap().show();
+        with_framebuffer(|fb| {
+            assert_eq!(fb.pixel(20, 20), Some(0x0000), "background");
+            assert_eq!(fb.pixel(90, 65), Some(0xf800), "panel");
+            assert_eq!(fb.pixel(40, 40), Some(0xffff), "top left border");
+            assert_eq!(fb.pixel(139, 60), Some(0xffff), "right border");
+            assert_eq!(fb.pixel(141, 60), Some(0x0000), "outside the border");
+            assert_eq!(fb.pixel(12, 190), Some(0xffff), "text box");
+            assert_eq!(fb.pixel(12, 201), Some(0x0000), "below the baseline");
+            assert!(fb.frames() >= 1);
+        });
+    }
+}
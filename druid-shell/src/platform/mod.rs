@@ -15,6 +15,14 @@
 //! Platform specific implementations.
 
 cfg_if::cfg_if! {
+    if #[cfg(all(target_arch = "arm", target_os = "none", not(feature = "headless")))] { ////
+        mod embedded; ////
+        pub use self::embedded::*; ////
+    } else { ////
+        mod headless; ////
+        pub use self::headless::*; ////
+    } ////
+    /* ////
     if #[cfg(all(target_os = "windows", not(feature = "use_gtk")))] {
         mod windows;
         pub use windows::*;
@@ -28,4 +36,5 @@ cfg_if::cfg_if! {
         mod embedded;
         pub use self::embedded::*;
     }
-}
\ No newline at end of file
+    */ ////
+}
@@ -14,6 +14,394 @@
 
 //! The main application loop.
 
+use core::cell::{Cell, RefCell}; ////
+use core::marker::PhantomData; ////
+use core::time::Duration; ////
+use critical_section::Mutex; ////
+use heapless::Deque; ////
+
+use crate::kurbo::Point; ////
+use crate::mouse::{MouseButton, MouseEvent}; ////
+use crate::platform::runloop as platform; ////
+use crate::platform::window as platform_window; ////
+use crate::platform::DruidContext; ////
+use crate::timer::{self, Instant, MAX_TIMERS}; ////
+use crate::window::{WinHandler, WindowBuilder, WindowHandle, WindowIdType}; ////
+
+/// Max number of input events that may be queued between iterations of the `RunLoop`
+pub const MAX_QUEUED_EVENTS: usize = 16;
+
+/// Time between animation frames, for windows that are animating
+pub const ANIM_FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+/// An input event queued by an interrupt handler or driver, to be handled by the `RunLoop`
+#[derive(Debug, Clone)]
+pub enum QueuedEvent {
+    MouseDown(MouseEvent),
+    MouseUp(MouseEvent),
+    MouseMove(MouseEvent),
+    /// The back gesture or button
+    Back,
+    /// Repaint the window
+    Invalidate,
+}
+
+/// A set of window IDs, with one bit for every possible `WindowIdType`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct WindowSet([u32; 8]);
+
+impl WindowSet {
+    /// The set with no windows
+    const EMPTY: WindowSet = WindowSet([0; 8]);
+
+    /// The set with every window ID
+    const ALL: WindowSet = WindowSet([!0; 8]);
+
+    /// Add the window ID to the set
+    fn insert(&mut self, window_id: WindowIdType) {
+        self.0[window_id as usize / 32] |= 1 << (window_id % 32);
+    }
+
+    /// True if the window ID is in the set
+    fn contains(&self, window_id: WindowIdType) -> bool {
+        self.0[window_id as usize / 32] & (1 << (window_id % 32)) != 0
+    }
+
+    /// Remove the window ID from the set
+    fn remove(&mut self, window_id: WindowIdType) {
+        self.0[window_id as usize / 32] &= !(1 << (window_id % 32));
+    }
+
+    /// True if the set has no windows
+    fn is_empty(&self) -> bool {
+        self.0.iter().all(|bits| *bits == 0)
+    }
+
+    /// The window IDs that are in either set
+    fn union(mut self, other: WindowSet) -> WindowSet {
+        for (bits, other) in self.0.iter_mut().zip(other.0.iter()) {
+            *bits |= other;
+        }
+        self
+    }
+
+    /// The window IDs in the set, lowest first
+    fn iter(self) -> impl Iterator<Item = WindowIdType> {
+        (0..=WindowIdType::MAX).filter(move |window_id| self.contains(*window_id))
+    }
+}
+
+/// Input events for each window ID, in the order they were posted
+static EVENT_QUEUE: Mutex<RefCell<Deque<(WindowIdType, QueuedEvent), MAX_QUEUED_EVENTS>>> =
+    Mutex::new(RefCell::new(Deque::new()));
+
+/// The windows that need to be repainted
+static INVALID_WINDOWS: Mutex<Cell<WindowSet>> = Mutex::new(Cell::new(WindowSet::EMPTY));
+
+/// The windows that have requested an animation frame, to be painted at the deadline
+static ANIM_FRAMES: Mutex<Cell<(WindowSet, Instant)>> = Mutex::new(Cell::new((WindowSet::EMPTY, Instant::from_millis(0))));
+
+/// ID of the window that is shown on the display and receives touch input, 0 if no window is open
+static FOCUSED_WINDOW: Mutex<Cell<WindowIdType>> = Mutex::new(Cell::new(0));
+
+/// The windows that have been opened after the `RunLoop` started, to be added by the `RunLoop`
+static OPENED_WINDOWS: Mutex<Cell<WindowSet>> = Mutex::new(Cell::new(WindowSet::EMPTY));
+
+/// The windows that have been closed, to be removed by the `RunLoop`
+static CLOSED_WINDOWS: Mutex<Cell<WindowSet>> = Mutex::new(Cell::new(WindowSet::EMPTY));
+
+/// Queue an input event for the window. Safe to call from interrupt handlers.
+/// Returns the event if the queue is full.
+pub fn post_event(window_id: WindowIdType, event: QueuedEvent) -> Result<(), QueuedEvent> {
+    critical_section::with(|cs| {
+        EVENT_QUEUE.borrow(cs).borrow_mut()
+            .push_back((window_id, event))
+            .map_err(|(_, event)| event)
+    })
+}
+
+/// Queue a tap (mouse down and mouse up) at the (x,y) coordinates for the window.
+/// Safe to call from interrupt handlers. The tap is dropped if the queue is full.
+pub fn post_touch(window_id: WindowIdType, x: u16, y: u16) -> Result<(), QueuedEvent> {
+    let pos = Point::new(x as f64, y as f64);
+    let down = QueuedEvent::MouseDown(MouseEvent { pos, count: 1, button: MouseButton::Left });
+    let up = QueuedEvent::MouseUp(MouseEvent { pos, count: 0, button: MouseButton::Left });
+    critical_section::with(|cs| {
+        let mut queue = EVENT_QUEUE.borrow(cs).borrow_mut();
+        //  Queue both events or neither, so that a mouse down is never left without a mouse up
+        if queue.capacity() - queue.len() < 2 {
+            return Err(down);
+        }
+        queue.push_back((window_id, down)).ok();
+        queue.push_back((window_id, up)).ok();
+        Ok(())
+    })
+}
+
+/// Request a repaint of the window on the next iteration of the `RunLoop`
+pub(crate) fn invalidate_window(window_id: WindowIdType) {
+    critical_section::with(|cs| {
+        let invalid = INVALID_WINDOWS.borrow(cs);
+        let mut windows = invalid.get();
+        windows.insert(window_id);
+        invalid.set(windows);
+    });
+}
+
+/// Request a repaint of all windows on the next iteration of the `RunLoop`
+pub(crate) fn invalidate_all() {
+    critical_section::with(|cs| INVALID_WINDOWS.borrow(cs).set(WindowSet::ALL));
+}
+
+/// The window that is shown on the display and receives touch input, or `None` if no window
+/// is open. Safe to call from interrupt handlers.
+pub fn focused_window() -> Option<WindowIdType> {
+    match critical_section::with(|cs| FOCUSED_WINDOW.borrow(cs).get()) {
+        0 => None,
+        window_id => Some(window_id),
+    }
+}
+
+/// Show the window on the display and send touch input to it. The window
+/// is repainted and gets the focus on the next iteration of the `RunLoop`.
+pub(crate) fn focus_window(window_id: WindowIdType) {
+    critical_section::with(|cs| FOCUSED_WINDOW.borrow(cs).set(window_id));
+    invalidate_window(window_id);
+}
+
+/// Add the window to the `RunLoop` on its next iteration
+pub(crate) fn open_window(window_id: WindowIdType) {
+    critical_section::with(|cs| {
+        let opened = OPENED_WINDOWS.borrow(cs);
+        let mut windows = opened.get();
+        windows.insert(window_id);
+        opened.set(windows);
+    });
+}
+
+/// Remove the window from the `RunLoop` on its next iteration
+pub(crate) fn close_window(window_id: WindowIdType) {
+    critical_section::with(|cs| {
+        let closed = CLOSED_WINDOWS.borrow(cs);
+        let mut windows = closed.get();
+        windows.insert(window_id);
+        closed.set(windows);
+    });
+}
+
+/// Repaint the window at the next animation frame, `ANIM_FRAME_INTERVAL` from now.
+/// Windows that request a frame while one is pending are painted at the same frame.
+pub(crate) fn request_anim_frame(window_id: WindowIdType) {
+    let now = timer::now();
+    critical_section::with(|cs| {
+        let frames = ANIM_FRAMES.borrow(cs);
+        let (mut windows, deadline) = frames.get();
+        let deadline = if windows.is_empty() { now + ANIM_FRAME_INTERVAL } else { deadline };
+        windows.insert(window_id);
+        frames.set((windows, deadline));
+    });
+}
+
+/// The earliest time that `RunLoop::run_once` has work to do, because a timer
+/// expires or an animation frame is due. A cooperative scheduler should call
+/// `RunLoop::run_once` again at this time.
+pub fn next_deadline() -> Option<Instant> {
+    let (windows, deadline) = critical_section::with(|cs| ANIM_FRAMES.borrow(cs).get());
+    let anim = if !windows.is_empty() { Some(deadline) } else { None };
+    match (anim, timer::next_timer_deadline()) {
+        (Some(anim), Some(timer)) => Some(anim.min(timer)),
+        (anim, timer) => anim.or(timer),
+    }
+}
+
+/// Pop the next queued input event
+fn next_event() -> Option<(WindowIdType, QueuedEvent)> {
+    critical_section::with(|cs| EVENT_QUEUE.borrow(cs).borrow_mut().pop_front())
+}
+
+/// Return the windows to be repainted, including the animation frames that are due at `now`,
+/// and clear the requests
+fn take_invalid_windows(now: Instant) -> WindowSet {
+    critical_section::with(|cs| {
+        let frames = ANIM_FRAMES.borrow(cs);
+        let (windows, deadline) = frames.get();
+        let due = if !windows.is_empty() && deadline <= now {
+            frames.set((WindowSet::EMPTY, deadline));
+            windows
+        } else {
+            WindowSet::EMPTY
+        };
+        INVALID_WINDOWS.borrow(cs).replace(WindowSet::EMPTY).union(due)
+    })
+}
+
+/// The main application loop. Drains the queued input events, dispatches them
+/// to the `WinHandler` of each window, fires the expired timers, then repaints
+/// the focused window once if it was invalidated or is due for an animation frame.
+///
+/// Only the focused window is shown on the display. The first window added gets
+/// the focus; `WindowHandle::bring_to_front_and_focus` moves it to another window.
+/// Windows opened with `WindowHandle::open` are added, and closed windows are destroyed
+/// and removed, at the start and end of each iteration.
+///
+/// The `RunLoop` keeps only the IDs of its windows and gets each window's handler with
+/// `WinHandler::get_handler`, so it serves as many windows as the handlers can hold.
+pub struct RunLoop<THandler: WinHandler<THandler> + Clone + Default> { ////
+    windows: WindowSet,
+    /// The window that was last sent `got_focus`, 0 if none
+    focused: WindowIdType,
+    phantom: PhantomData<THandler>,  //  Needed to do compile-time checking for `THandler`
+}
+
+impl<THandler: WinHandler<THandler> + Clone + Default> RunLoop<THandler> { ////
+    /// Create a new `RunLoop`.
+    ///
+    /// The runloop does not start until [`RunLoop::run`] or [`RunLoop::run_once`] is called.
+    ///
+    /// [`RunLoop::run`]: struct.RunLoop.html#method.run
+    /// [`RunLoop::run_once`]: struct.RunLoop.html#method.run_once
+    pub fn new() -> Self {
+        RunLoop {
+            windows: WindowSet::EMPTY,
+            focused: 0,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Serve the window from this `RunLoop`. The window gets the focus if no other window has it.
+    pub fn add_window(&mut self, handle: WindowHandle<THandler>) {
+        let window_id = handle.0.window_id;
+        self.windows.insert(window_id);
+        if focused_window().is_none() {
+            focus_window(window_id);
+        }
+    }
+
+    /// The handle of the window, if the window is served by this `RunLoop`
+    fn window(&self, window_id: WindowIdType) -> Option<WindowHandle<THandler>> {
+        if window_id == 0 || !self.windows.contains(window_id) {
+            return None;
+        }
+        let handler = THandler::default().get_handler(window_id);
+        let state = platform_window::WindowState { window_id, handler };
+        Some(WindowHandle(platform_window::WindowHandle { window_id, state }))
+    }
+
+    /// Add the opened windows, destroy and remove the closed windows, then send `got_focus`
+    /// to the focused window if the focus has moved. Returns true if any window was added,
+    /// removed or focused.
+    fn sync_windows(&mut self) -> bool {
+        let mut busy = false;
+        let opened = critical_section::with(|cs| OPENED_WINDOWS.borrow(cs).replace(WindowSet::EMPTY));
+        for window_id in opened.iter() {
+            if self.windows.contains(window_id) {
+                continue;
+            }
+            busy = true;
+            let mut builder = WindowBuilder::new();
+            builder.set_handler(THandler::default().get_handler(window_id));
+            if let Ok(handle) = builder.build() {
+                self.add_window(handle);
+            }
+        }
+        let closed = critical_section::with(|cs| CLOSED_WINDOWS.borrow(cs).replace(WindowSet::EMPTY));
+        if !closed.is_empty() {
+            for window_id in closed.iter() {
+                let handle = match self.window(window_id) {
+                    Some(handle) => handle,
+                    None => continue,
+                };
+                busy = true;
+                timer::cancel_timers(window_id);
+                self.windows.remove(window_id);
+                let mut ctx = DruidContext::new(window_id);
+                handle.0.state.handler.clone().destroy(&mut ctx);
+            }
+            //  Show the first remaining window if the focused window was closed
+            if let Some(focused) = focused_window() {
+                if closed.contains(focused) {
+                    match self.windows.iter().next() {
+                        Some(window_id) => focus_window(window_id),
+                        None => critical_section::with(|cs| FOCUSED_WINDOW.borrow(cs).set(0)),
+                    }
+                }
+            }
+        }
+        let focused = focused_window().unwrap_or(0);
+        if focused != self.focused {
+            self.focused = focused;
+            if let Some(handle) = self.window(focused) {
+                busy = true;
+                let mut ctx = DruidContext::new(focused);
+                handle.0.state.handler.clone().got_focus(&mut ctx);
+            }
+        }
+        busy
+    }
+
+    /// Handle all queued input events and expired timers, then repaint the invalidated windows.
+    /// For cooperative schedulers: call this from a task whenever input has been posted,
+    /// and at the time returned by `next_deadline` when timers or animation frames are pending.
+    /// Returns true if any event or timer was handled or any window was repainted.
+    pub fn run_once(&mut self) -> bool {
+        let mut busy = self.sync_windows();
+        while let Some((window_id, event)) = next_event() {
+            busy = true;
+            let handle = match self.window(window_id) {
+                Some(handle) => handle,
+                None => continue,  //  Window has been closed
+            };
+            let mut handler = handle.0.state.handler.clone();
+            let mut ctx = DruidContext::new(window_id);
+            match event {
+                QueuedEvent::MouseDown(event) => handler.mouse_down(&event, &mut ctx),
+                QueuedEvent::MouseUp(event)   => handler.mouse_up(&event, &mut ctx),
+                QueuedEvent::MouseMove(event) => handler.mouse_move(&event, &mut ctx),
+                QueuedEvent::Back             => handler.back(&mut ctx),
+                QueuedEvent::Invalidate       => invalidate_window(window_id),
+            }
+        }
+        //  Fire the expired timers. Bounded, in case a timer handler keeps requesting timers that have already expired.
+        let now = timer::now();
+        for _ in 0..MAX_TIMERS {
+            let (window_id, token) = match timer::next_expired(now) {
+                Some(expired) => expired,
+                None => break,
+            };
+            busy = true;
+            if let Some(handle) = self.window(window_id) {
+                let mut ctx = DruidContext::new(window_id);
+                handle.0.state.handler.clone().timer(token, &mut ctx);
+            }
+        }
+        //  Events may have closed or focused a window
+        busy |= self.sync_windows();
+        //  Repaint the focused window once, however many events invalidated it. Hidden windows
+        //  are not painted, they are repainted when they get the focus.
+        let invalid = take_invalid_windows(now);
+        if let Some(handle) = self.window(self.focused) {
+            if invalid.contains(self.focused) {
+                busy = true;
+                handle.show();
+            }
+        }
+        busy
+    }
+
+    /// Start the runloop.
+    ///
+    /// On embedded platforms this never returns: the loop sleeps until an interrupt
+    /// posts more input, or the OS tick expires a timer or animation frame. On the headless
+    /// platform it returns once there is no more work, even if timers or frames are pending.
+    pub fn run(&mut self) {
+        loop {
+            if !self.run_once() && !platform::wait_for_event() {
+                return;
+            }
+        }
+    }
+}
+
 /* ////
 use crate::platform::runloop as platform;
 
@@ -37,4 +425,26 @@ impl RunLoop {
         self.0.run()
     }
 }
-*/ ////
\ No newline at end of file
+*/ ////
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_set_holds_every_id() {
+        let mut windows = WindowSet::EMPTY;
+        assert!(windows.is_empty());
+        windows.insert(31);
+        windows.insert(32);
+        windows.insert(WindowIdType::MAX);
+        assert!(windows.contains(31) && windows.contains(32) && windows.contains(WindowIdType::MAX));
+        assert!(!windows.contains(0) && !windows.contains(33));
+        let mut other = WindowSet::EMPTY;
+        other.insert(200);
+        assert!(windows.union(other).contains(200));
+        assert!(WindowSet::ALL.contains(WindowIdType::MAX));
+        windows.remove(32);
+        assert!(windows.contains(31) && !windows.contains(32));
+        assert_eq!(windows.iter().next(), Some(31));
+    }
+}
@@ -0,0 +1,237 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Monotonic clock and timers, replacing `std::time::Instant` on platforms without `std`.
+
+use core::cell::{Cell, RefCell};
+use core::ops::{Add, Sub};
+use core::time::Duration;
+use critical_section::Mutex;
+
+use crate::platform::runloop as platform;
+use crate::window::{TimerToken, WindowIdType};
+
+/// Max number of timers that may be pending at the same time
+pub const MAX_TIMERS: usize = 8;
+
+/// A point in time, measured in milliseconds by the monotonic `Clock`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(u64);
+
+impl Instant {
+    /// The instant `ms` milliseconds after the clock started
+    pub const fn from_millis(ms: u64) -> Instant {
+        Instant(ms)
+    }
+
+    /// Milliseconds since the clock started
+    pub const fn as_millis(self) -> u64 {
+        self.0
+    }
+
+    /// The current time according to the `Clock`
+    pub fn now() -> Instant {
+        now()
+    }
+
+    /// Time elapsed from `earlier` to this instant, or zero if `earlier` is later
+    pub fn duration_since(self, earlier: Instant) -> Duration {
+        Duration::from_millis(self.0.saturating_sub(earlier.0))
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+    fn add(self, rhs: Duration) -> Instant {
+        //  Saturates, so that a far deadline (e.g. `Duration::MAX`) means "never" instead of overflowing
+        Instant(self.0.saturating_add(rhs.as_millis().min(u64::MAX as u128) as u64))
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+    fn sub(self, rhs: Instant) -> Duration {
+        self.duration_since(rhs)
+    }
+}
+
+/// A monotonic clock. The platform provides one, tests may replace it with a `FakeClock`.
+pub trait Clock: Sync {
+    /// The current time. Must never go backwards.
+    fn now(&self) -> Instant;
+}
+
+/// A `Clock` that only moves when it is told to. Used to test timers on the host.
+/// Keeps microseconds, so that advancing by less than a millisecond at a time is not lost.
+pub struct FakeClock(Mutex<Cell<u64>>);
+
+impl FakeClock {
+    /// Create a clock that stays at time 0 until advanced
+    pub const fn new() -> FakeClock {
+        FakeClock(Mutex::new(Cell::new(0)))
+    }
+
+    /// Move the clock forward
+    pub fn advance(&self, duration: Duration) {
+        critical_section::with(|cs| {
+            let us = self.0.borrow(cs);
+            let by = duration.as_micros().min(u64::MAX as u128) as u64;
+            us.set(us.get().saturating_add(by));
+        });
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Instant {
+        Instant(critical_section::with(|cs| self.0.borrow(cs).get()) / 1000)
+    }
+}
+
+/// The clock installed by `set_clock`, if any
+static CLOCK: Mutex<Cell<Option<&'static dyn Clock>>> = Mutex::new(Cell::new(None));
+
+/// Replace the platform clock, e.g. with a `FakeClock` when testing on the host
+pub fn set_clock(clock: &'static dyn Clock) {
+    critical_section::with(|cs| CLOCK.borrow(cs).set(Some(clock)));
+}
+
+/// The current time according to the installed clock, or the platform clock
+pub fn now() -> Instant {
+    let clock = critical_section::with(|cs| CLOCK.borrow(cs).get());
+    match clock {
+        Some(clock) => clock.now(),
+        None => platform::PlatformClock.now(),
+    }
+}
+
+/// A pending timer
+#[derive(Clone, Copy)]
+struct Timer {
+    deadline: Instant,
+    window_id: WindowIdType,
+    token: TimerToken,
+}
+
+/// Fixed-capacity set of pending timers. Timers fire in order of deadline.
+pub(crate) struct TimerWheel<const N: usize> {
+    slots: [Option<Timer>; N],
+    /// Token for the next timer. Token 0 is `TimerToken::INVALID`.
+    next_token: usize,
+}
+
+impl<const N: usize> TimerWheel<N> {
+    pub(crate) const fn new() -> Self {
+        TimerWheel {
+            slots: [None; N],
+            next_token: 1,
+        }
+    }
+
+    /// Schedule a timer for the window. Returns `None` if all slots are in use.
+    pub(crate) fn schedule(&mut self, window_id: WindowIdType, deadline: Instant) -> Option<TimerToken> {
+        let slot = self.slots.iter_mut().find(|slot| slot.is_none())?;
+        let token = TimerToken::new(self.next_token);
+        self.next_token = self.next_token.checked_add(1).unwrap_or(1);
+        *slot = Some(Timer { deadline, window_id, token });
+        Some(token)
+    }
+
+    /// Remove the timer with the earliest deadline that has expired at `now`
+    pub(crate) fn pop_expired(&mut self, now: Instant) -> Option<(WindowIdType, TimerToken)> {
+        let slot = self.slots.iter_mut()
+            .filter(|slot| matches!(slot, Some(timer) if timer.deadline <= now))
+            .min_by_key(|slot| slot.map(|timer| timer.deadline))?;
+        slot.take().map(|timer| (timer.window_id, timer.token))
+    }
+
+    /// The earliest deadline of all pending timers
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        self.slots.iter().flatten().map(|timer| timer.deadline).min()
+    }
+
+    /// Remove all timers of the window
+    pub(crate) fn cancel_window(&mut self, window_id: WindowIdType) {
+        for slot in self.slots.iter_mut() {
+            if matches!(slot, Some(timer) if timer.window_id == window_id) {
+                *slot = None;
+            }
+        }
+    }
+}
+
+/// Pending timers for all windows
+static TIMERS: Mutex<RefCell<TimerWheel<MAX_TIMERS>>> = Mutex::new(RefCell::new(TimerWheel::new()));
+
+/// Schedule a timer for the window. Returns `TimerToken::INVALID` if too many timers are pending.
+pub(crate) fn request_timer(window_id: WindowIdType, deadline: Instant) -> TimerToken {
+    critical_section::with(|cs| TIMERS.borrow(cs).borrow_mut().schedule(window_id, deadline))
+        .unwrap_or(TimerToken::INVALID)
+}
+
+/// Remove the earliest timer that has expired at `now`
+pub(crate) fn next_expired(now: Instant) -> Option<(WindowIdType, TimerToken)> {
+    critical_section::with(|cs| TIMERS.borrow(cs).borrow_mut().pop_expired(now))
+}
+
+/// Remove all timers of the window, when the window is closed
+pub(crate) fn cancel_timers(window_id: WindowIdType) {
+    critical_section::with(|cs| TIMERS.borrow(cs).borrow_mut().cancel_window(window_id));
+}
+
+/// The earliest deadline of all pending timers
+pub(crate) fn next_timer_deadline() -> Option<Instant> {
+    critical_section::with(|cs| TIMERS.borrow(cs).borrow().next_deadline())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timers_fire_in_deadline_order() {
+        static CLOCK: FakeClock = FakeClock::new();
+        let mut wheel: TimerWheel<2> = TimerWheel::new();
+        let start = CLOCK.now();
+        let late = wheel.schedule(1, start + Duration::from_millis(500)).unwrap();
+        let early = wheel.schedule(2, start + Duration::from_millis(100)).unwrap();
+        assert_ne!(late, early);
+        assert!(wheel.schedule(1, start).is_none(), "wheel should be full");
+        assert_eq!(wheel.next_deadline(), Some(Instant::from_millis(100)));
+
+        assert_eq!(wheel.pop_expired(CLOCK.now()), None);
+        CLOCK.advance(Duration::from_millis(600));
+        assert_eq!(wheel.pop_expired(CLOCK.now()), Some((2, early)));
+        assert_eq!(wheel.pop_expired(CLOCK.now()), Some((1, late)));
+        assert_eq!(wheel.pop_expired(CLOCK.now()), None);
+        assert_eq!(wheel.next_deadline(), None);
+    }
+
+    #[test]
+    fn fake_clock_keeps_sub_millisecond_steps() {
+        static CLOCK: FakeClock = FakeClock::new();
+        for _ in 0..3 {
+            CLOCK.advance(Duration::from_micros(500));
+        }
+        assert_eq!(CLOCK.now(), Instant::from_millis(1));
+        CLOCK.advance(Duration::MAX);
+        assert_eq!(CLOCK.now(), Instant::from_millis(u64::MAX / 1000));
+    }
+
+    #[test]
+    fn far_deadlines_saturate() {
+        let far = Instant::from_millis(1) + Duration::MAX;
+        assert_eq!(far, Instant::from_millis(u64::MAX));
+        assert!(far > Instant::from_millis(1) + Duration::from_secs(3600));
+    }
+}
@@ -27,6 +27,7 @@ use crate::kurbo::{Size}; ////
 ////use crate::menu::Menu;
 use crate::mouse::{Cursor, MouseEvent};
 use crate::platform::window as platform;
+use crate::timer::Instant; ////
 
 /// Windows are identified by an 8-bit ID
 pub type WindowIdType = u8;
@@ -34,7 +35,7 @@ pub type WindowIdType = u8;
 // It's possible we'll want to make this type alias at a lower level,
 // see https://github.com/linebender/piet/pull/37 for more discussion.
 /// The platform text factory, reexported from piet.
-pub type Text = <piet_common::Piet as piet_common::RenderContext>::Text;
+pub type Text = <crate::piet::Piet as piet_common::RenderContext>::Text; ////
 
 /// A token that uniquely identifies a running timer.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Hash)]
@@ -44,7 +45,6 @@ impl TimerToken {
     /// A token that does not correspond to any timer.
     pub const INVALID: TimerToken = TimerToken(0);
 
-    #[allow(dead_code)] ////
     pub(crate) const fn new(id: usize) -> TimerToken {
         TimerToken(id)
     }
@@ -93,12 +93,20 @@ impl<THandler: WinHandler<THandler> + Clone + Default> WindowHandle<THandler> {
         self.0.show()
     }
 
-    /// Close the window.
+    /// Add a window that was built after the `RunLoop` started to the `RunLoop`,
+    /// on its next iteration. Windows built before are added with `RunLoop::add_window`.
+    pub fn open(&self) {
+        self.0.open()
+    }
+
+    /// Close the window. On the next iteration of the `RunLoop`, the window's
+    /// `WinHandler::destroy` is called and the window is removed.
     pub fn close(&self) {
         self.0.close()
     }
 
     /// Bring this window to the front of the window stack and give it focus.
+    /// On embedded platforms only the focused window is shown on the display.
     pub fn bring_to_front_and_focus(&self) {
         self.0.bring_to_front_and_focus()
     }
@@ -206,21 +214,23 @@ pub trait WinCtx {
     /// Set the cursor icon.
     fn set_cursor(&mut self, cursor: &Cursor);
 
-    /* ////
-        /// Schedule a timer.
-        ///
-        /// This causes a [`WinHandler::timer()`] call at the deadline. The
-        /// return value is a token that can be used to associate the request
-        /// with the handler call.
-        ///
-        /// Note that this is not a precise timer. On Windows, the typical
-        /// resolution is around 10ms. Therefore, it's best used for things
-        /// like blinking a cursor or triggering tooltips, not for anything
-        /// requiring precision.
-        ///
-        /// [`WinHandler::timer()`]: trait.WinHandler.html#tymethod.timer
-        fn request_timer(&mut self, deadline: std::time::Instant) -> TimerToken;
+    /// Schedule a timer.
+    ///
+    /// This causes a [`WinHandler::timer()`] call at the deadline. The
+    /// return value is a token that can be used to associate the request
+    /// with the handler call.
+    ///
+    /// Note that this is not a precise timer. Timers are fired by the
+    /// `RunLoop`, so the resolution depends on how often it wakes up.
+    /// Therefore, it's best used for things like blinking a cursor or
+    /// triggering tooltips, not for anything requiring precision.
+    /// Returns `TimerToken::INVALID` if too many timers are pending.
+    ///
+    /// [`WinHandler::timer()`]: trait.WinHandler.html#tymethod.timer
+    fn request_timer(&mut self, deadline: Instant) -> TimerToken; ////
+    ////fn request_timer(&mut self, deadline: std::time::Instant) -> TimerToken;
 
+    /* ////
         /// Prompt the user to chose a file to open.
         ///
         /// Blocks while the user picks the file.
@@ -248,7 +258,7 @@ pub trait WinHandler<THandler> {  ////  THandler is DruidHandler<T: Data + 'stat
     /// Request the handler to paint the window contents. Return value
     /// indicates whether window is animating, i.e. whether another paint
     /// should be scheduled for the next animation frame.
-    fn paint(&mut self, piet: &mut piet_common::Piet, ctx: &mut dyn WinCtx) -> bool;
+    fn paint(&mut self, piet: &mut crate::piet::Piet, ctx: &mut dyn WinCtx) -> bool; ////
 
     /* ////
         /// Called when the resources need to be rebuilt.
@@ -300,6 +310,10 @@ pub trait WinHandler<THandler> {  ////  THandler is DruidHandler<T: Data + 'stat
     #[allow(unused_variables)]
     fn mouse_up(&mut self, event: &MouseEvent, ctx: &mut dyn WinCtx) {}
 
+    /// Called when the user makes the back gesture, e.g. a swipe or a press of the side button.
+    #[allow(unused_variables)] ////
+    fn back(&mut self, ctx: &mut dyn WinCtx) {} ////
+
     /// Called on timer event.
     ///
     /// This is called at (approximately) the requested deadline by a
@@ -326,6 +340,9 @@ pub trait WinHandler<THandler> {  ////  THandler is DruidHandler<T: Data + 'stat
     /// Add a Window Handler for the Data type
     fn add_handler(&self, window_id: WindowIdType, handler: THandler); ////
 
+    /// Return the Window Handler for the window ID of the Data type
+    fn get_handler(&self, window_id: WindowIdType) -> THandler; ////
+
     /* ////
         /// Get a reference to the handler state. Used mostly by idle handlers.
         fn as_any(&mut self) -> &mut dyn Any;
@@ -12,58 +12,86 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-#![no_main] ////
-#![no_std]  //  This program will run on embedded platforms
+#![cfg_attr(target_os = "none", no_main)] ////
+#![cfg_attr(target_os = "none", no_std)]  //  This program will run on embedded platforms, and on the host with the headless platform
+#[cfg(target_os = "none")] ////
 use core::panic::PanicInfo; ////  Import `PanicInfo` type which is used by `panic()` below
 use druid::widget::{Align, Button, Column, Flex, Label, Padding};
-use druid::{AppLauncher, LocalizedString, Widget, WindowDesc};
+use druid::{AppLauncher, Data, LocalizedString, Widget, WindowDesc};
+#[cfg(target_os = "none")] ////
 use cortex_m_rt::entry; ////
 
+#[cfg(target_os = "none")] ////
 #[entry]
 fn main() -> ! {
+    launch();
+    loop {} ////
+}
+
+/// On the host, the window is painted into the headless framebuffer
+#[cfg(not(target_os = "none"))] ////
+fn main() {
+    launch();
+}
+
+/// Keys of the messages in `resources/i18n/en-US/*.ftl`, e.g. `keys::HELLO_COUNTER`
+mod keys { ////
+    druid::l10n_keys!("resources/i18n"); ////
+} ////
+
+/// Application state, which owns the static Widgets and Windows via `#[druid(app_state)]`
+#[derive(Clone, Data, Default)]
+#[druid(app_state)] ////
+struct State {
+    count: u32,
+}
+
+/// Launch the app
+fn launch() {
     //  Build a new window
-    let main_window = WindowDesc::<u32,Flex<u32>>::new(ui_builder);
+    let main_window = WindowDesc::<State,Flex<State>>::new(ui_builder);
     //  Application state is initially 0
-    let data = 0_u32;
+    let data = State::default();
     //  Launch the window with the initial application state
-    AppLauncher::<u32,Flex<u32>>::with_window(main_window)
+    AppLauncher::<State,Flex<State>>::with_window(main_window)
         .use_simple_logger()
+        //  Messages from `resources/i18n/<locale>/*.ftl`, compiled into Flash ROM
+        .localization(druid::include_l10n!("resources/i18n"))
         .launch(data)
         .expect("launch failed");
-    loop {} ////
 }
 
 /// Build the UI for the window. The application state consists of 1 value: `count` of type `u32`.
-fn ui_builder() -> Flex<u32> {  //  `u32` is the window state
+fn ui_builder() -> Flex<State> {  //  `State` is the window state
     //  Create a line of text based on a counter value
     let text =
-        LocalizedString::<u32>::new("hello-counter")
+        LocalizedString::<State>::new(keys::HELLO_COUNTER) ////  Fails to compile if the message is missing
         .with_arg(
             "count", 
             //  Closure that will fetch the counter value...
-            |data: &u32, _env| (*data).into()
+            |data: &State, _env| data.count.into()
         );
     //  Create a label widget to display the text
-    let label = Label::<u32>::new(text);
+    let label = Label::<State>::new(text);
     //  Create a button widget to increment the counter
-    let button = Button::<u32>::new(
+    let button = Button::<State>::new(
         "increment", 
         //  Closure that will be called when button is tapped...
-        |_ctx, data, _env| *data += 1
+        |_ctx, data: &mut State, _env| data.count += 1
     );
 
     //  Create a column for the UI
-    let mut col = Column::new::<u32>();
+    let mut col = Column::new::<State>();
     //  Add the label widget to the column, centered with padding
-    col.add_child::<Align::<u32>>(
-        Align::<u32>::centered(
-            Padding::<u32>::new(5.0, label)
+    col.add_child::<Align::<State>>(
+        Align::<State>::centered(
+            Padding::<State>::new(5.0, label)
         ),
         1.0
     );
     //  Add the button widget to the column, with padding
-    col.add_child::<Padding::<u32>>(
-        Padding::<u32>::new(5.0, button), 
+    col.add_child::<Padding::<State>>(
+        Padding::<State>::new(5.0, button), 
         1.0
     );
     //  Return the column containing the label and button widgets
@@ -71,6 +99,7 @@ fn ui_builder() -> Flex<u32> {  //  `u32` is the window state
 }
 
 ///  This function is called on panic, like an assertion failure. We display the filename and line number and pause in the debugger. From https://os.phil-opp.com/freestanding-rust-binary/
+#[cfg(target_os = "none")] ////
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     //  Display the filename and line number to the Semihosting Console.
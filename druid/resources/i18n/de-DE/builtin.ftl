@@ -0,0 +1,3 @@
+-app-name = Druid
+
+hello-counter = Der aktuelle Wert ist { $count }
@@ -0,0 +1,3 @@
+-app-name = Druid
+
+hello-counter = Current value is { $count }
@@ -0,0 +1,3 @@
+-app-name = Druide
+
+hello-counter = La valeur actuelle est { $count }
@@ -0,0 +1,158 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Easing curves and tweened values for animating widgets.
+//!
+//! A widget calls `EventCtx::request_anim_frame` to start animating, then steps its
+//! `Tween` with the interval of each `Event::AnimFrame` and requests another frame
+//! until the `Tween` is done.
+
+use core::time::Duration;
+use crate::kurbo::Point;
+
+/// Easing curve that maps the elapsed fraction of an animation to the fraction of the change
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    /// Constant speed
+    Linear,
+    /// Start slow, then speed up
+    EaseIn,
+    /// Start fast, then slow down
+    EaseOut,
+    /// Start slow, speed up, then slow down
+    EaseInOut,
+}
+
+impl Easing {
+    /// Map `t` from 0.0 to 1.0 onto the curve. Cubic curves, so no `libm` is needed.
+    pub fn apply(self, t: f64) -> f64 {
+        let t = if t < 0.0 { 0.0 } else if t > 1.0 { 1.0 } else { t };
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => {
+                let u = 1.0 - t;
+                1.0 - u * u * u
+            }
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    let u = 2.0 - 2.0 * t;
+                    1.0 - u * u * u / 2.0
+                }
+            }
+        }
+    }
+}
+
+/// A value that can be interpolated by a `Tween`
+pub trait Lerp: Copy {
+    /// The value at fraction `t` of the way from `self` to `to`
+    fn lerp(self, to: Self, t: f64) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, to: Self, t: f64) -> Self {
+        self + (to - self) * t as f32
+    }
+}
+
+impl Lerp for f64 {
+    fn lerp(self, to: Self, t: f64) -> Self {
+        self + (to - self) * t
+    }
+}
+
+impl Lerp for Point {
+    fn lerp(self, to: Self, t: f64) -> Self {
+        Point::new(self.x.lerp(to.x, t), self.y.lerp(to.y, t))
+    }
+}
+
+/// A value that moves from one value to another over a duration, following an `Easing` curve
+#[derive(Debug, Clone, Copy)]
+pub struct Tween<V: Lerp> {
+    from: V,
+    to: V,
+    easing: Easing,
+    /// Duration of the animation in nanoseconds, same unit as `Event::AnimFrame`
+    duration: u64,
+    /// Nanoseconds elapsed since the animation started
+    elapsed: u64,
+}
+
+impl<V: Lerp> Tween<V> {
+    /// Create a tween from `from` to `to` that runs for `duration`
+    pub fn new(from: V, to: V, duration: Duration, easing: Easing) -> Self {
+        Tween {
+            from,
+            to,
+            easing,
+            duration: duration.as_nanos() as u64,
+            elapsed: 0,
+        }
+    }
+
+    /// A tween that has finished at `value`
+    pub fn done(value: V) -> Self {
+        Tween::new(value, value, Duration::from_nanos(0), Easing::Linear)
+    }
+
+    /// Advance by the interval of an `Event::AnimFrame`, in nanoseconds. Returns the new value.
+    pub fn step(&mut self, interval: u64) -> V {
+        self.elapsed = self.elapsed.saturating_add(interval).min(self.duration);
+        self.value()
+    }
+
+    /// The current value
+    pub fn value(&self) -> V {
+        if self.is_done() {
+            return self.to;
+        }
+        let t = self.elapsed as f64 / self.duration as f64;
+        self.from.lerp(self.to, self.easing.apply(t))
+    }
+
+    /// The value at the end of the animation
+    pub fn target(&self) -> V {
+        self.to
+    }
+
+    /// True when the animation has finished. Request no more animation frames after this.
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// Restart the animation from the current value towards a new target
+    pub fn retarget(&mut self, to: V, duration: Duration) {
+        *self = Tween::new(self.value(), to, duration, self.easing);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tween_steps_to_target() {
+        let mut tween = Tween::new(Point::new(0.0, 0.0), Point::new(100.0, 50.0), Duration::from_millis(100), Easing::Linear);
+        assert_eq!(tween.step(0), Point::new(0.0, 0.0));
+        assert_eq!(tween.step(50_000_000), Point::new(50.0, 25.0));
+        assert!(!tween.is_done());
+        assert_eq!(tween.step(80_000_000), Point::new(100.0, 50.0));
+        assert!(tween.is_done());
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+    }
+}
@@ -22,27 +22,34 @@ use std::sync::Arc;
 
 use core::marker::PhantomData; ////
 use crate::kurbo::Size;
-use crate::shell::{Application, Error as PlatformError, /* RunLoop, */ WindowBuilder, WindowHandle};
-use crate::win_handler::{AppState, GlobalWindows};
-use crate::window::{WindowId}; ////
-use crate::{/* theme, AppDelegate, */ Data, DruidHandler, /* Env, LocalizedString, MenuDesc, */ Widget}; ////
+use crate::shell::{Application, Error as PlatformError}; ////
+////use crate::shell::{Application, Error as PlatformError, WindowBuilder, WindowHandle};
+use crate::win_handler::AppState; ////
+use crate::window::{Window, WindowId}; ////
+use crate::windowbox::WindowBox; ////
+use crate::{theme, AppData, Bundle, CapacityError, DruidHandler, Env, /* LocalizedString, MenuDesc, */ Widget}; ////
 
-/////// A function that modifies the initial environment.
+/// A function that modifies the initial environment.
+type EnvSetupFn = fn(&mut Env); ////
 ////type EnvSetupFn = dyn FnOnce(&mut Env);
 
-type MaxWindows = heapless::consts::U2; //// Max number of windows
-type Vec<T> = heapless::Vec::<T, MaxWindows>; ////
+/// The runloop for the application windows. Drains the input events posted by
+/// interrupt handlers and drivers, and repaints the invalidated windows.
+pub type RunLoop<T> = crate::shell::RunLoop<DruidHandler<T>>; ////
 
 /// Handles initial setup of an application, and starts the runloop.
-pub struct AppLauncher<T: Data + 'static + Default, W: Widget<T> + 'static> { ////
+///
+/// Windows are stored in the `UiArena` as they are added, so an application may have
+/// as many windows as its `max_windows` allows, e.g. `#[druid(app_state, max_windows = 4)]`.
+pub struct AppLauncher<T: AppData, W: Widget<T> + 'static> { ////
 ////pub struct AppLauncher<T> {
-    windows: Vec<WindowDesc<T, W>>, ////
     ////windows: Vec<WindowDesc<T>>,
-    phantom_data: PhantomData<T>,  //  Needed to do compile-time checking for `Data`
-    /* ////
-    env_setup: Option<Box<EnvSetupFn>>,
-    delegate: Option<Box<dyn AppDelegate<T>>>,
-    */ ////
+    phantom_data: PhantomData<(T, W)>,  //  Needed to do compile-time checking for `Data` and the root `Widget` ////
+    env_setup: Option<EnvSetupFn>, ////
+    bundles: &'static [Bundle], ////
+    ////env_setup: Option<Box<EnvSetupFn>>,
+    delegate: Option<T::Delegate>, ////
+    ////delegate: Option<Box<dyn AppDelegate<T>>>,
 }
 
 /// A function that can create a widget.
@@ -53,7 +60,7 @@ pub struct AppLauncher<T: Data + 'static + Default, W: Widget<T> + 'static> { //
 ///
 /// This includes a function that can build the root widget, as well as other
 /// window properties such as the title.
-pub struct WindowDesc<T: Data + 'static + Default, W: Widget<T> + 'static> { ////
+pub struct WindowDesc<T: AppData, W: Widget<T> + 'static> { ////
     pub(crate) root_builder: fn() -> W, ////
     ////pub(crate) root_builder: Arc<WidgetBuilderFn<T>>,
     ////pub(crate) title: Option<LocalizedString<T>>,
@@ -69,42 +76,60 @@ pub struct WindowDesc<T: Data + 'static + Default, W: Widget<T> + 'static> { ///
     phantom_data: PhantomData<T>,  //  Needed to do compile-time checking for `Data`
 }
 
-impl<T: Data + 'static + Default, W: Widget<T> + 'static> AppLauncher<T, W> { ////
+impl<T: AppData, W: Widget<T> + Clone + 'static> AppLauncher<T, W> ////
+where T::Widgets: From<W> {
     /// Create a new `AppLauncher` with the provided window.
     pub fn with_window(window: WindowDesc<T, W>) -> Self { ////
     ////pub fn with_window(window: WindowDesc<T>) -> Self {
-        let mut windows = Vec::new(); ////
-        windows.push(window)
-            .expect("with window failed"); ////
         AppLauncher {
-            windows, ////
             ////windows: vec![window],
             phantom_data: PhantomData, ////
-            /*
             env_setup: None,
+            bundles: &[], ////
             delegate: None,
-            */
-        }
+        }.add_window(window) ////
     }
 
-    /*
-    /// Provide an optional closure that will be given mutable access to
+    /// Add another window, e.g. a settings screen. Only one window is shown on the display:
+    /// the first window gets the focus, and `sys::SHOW_WINDOW` moves it to another window.
+    /// Panics if the application has more windows than its `max_windows`.
+    pub fn add_window(self, window: WindowDesc<T, W>) -> Self { ////
+        if let Err(err) = window.add_to_arena(&mut AppState::<T>::new()) { ////
+            panic!("{}", err);
+        } ////
+        self
+    }
+
+    /// Provide an optional function that will be given mutable access to
     /// the environment before launch.
     ///
-    /// This can be used to set or override theme values.
-    pub fn configure_env(mut self, f: impl Fn(&mut Env) + 'static) -> Self {
-        self.env_setup = Some(Box::new(f));
+    /// This can be used to pick a theme like `theme::LIGHT`, or to set or override theme values.
+    pub fn configure_env(mut self, f: EnvSetupFn) -> Self { ////
+    ////pub fn configure_env(mut self, f: impl Fn(&mut Env) + 'static) -> Self {
+        self.env_setup = Some(f); ////
+        ////self.env_setup = Some(Box::new(f));
+        self
+    }
+
+    /// Set the message tables for [`LocalizedString`]s, in order of preference.
+    /// Messages that are missing from all bundles fall back to the builtin strings.
+    ///
+    /// [`LocalizedString`]: struct.LocalizedString.html
+    pub fn localization(mut self, bundles: &'static [Bundle]) -> Self { ////
+        self.bundles = bundles;
         self
     }
 
-    /// Set the [`AppDelegate`].
+    /// Set the [`AppDelegate`]. The delegate is moved into the `UiArena` of the Application
+    /// Data, so its type is named with `#[druid(app_state, delegate = "MyDelegate")]`.
     ///
     /// [`AppDelegate`]: struct.AppDelegate.html
-    pub fn delegate(mut self, delegate: impl AppDelegate<T> + 'static) -> Self {
-        self.delegate = Some(Box::new(delegate));
+    pub fn delegate(mut self, delegate: T::Delegate) -> Self { ////
+    ////pub fn delegate(mut self, delegate: impl AppDelegate<T> + 'static) -> Self {
+        self.delegate = Some(delegate); ////
+        ////self.delegate = Some(Box::new(delegate));
         self
     }
-    */
 
     /// Initialize a minimal logger for printing logs out to stderr.
     ///
@@ -117,33 +142,57 @@ impl<T: Data + 'static + Default, W: Widget<T> + 'static> AppLauncher<T, W> { //
     /// Build the windows and start the runloop.
     ///
     /// Returns an error if a window cannot be instantiated. This is usually
-    /// a fatal error.
-    #[allow(unused_mut)] ////
-    pub fn launch(mut self, data: T) -> Result<(), PlatformError> {
+    /// a fatal error. On embedded platforms the runloop never returns.
+    pub fn launch(self, data: T) -> Result<(), PlatformError> {
+        let mut main_loop = self.build(data)?; ////
+        main_loop.run();
+        Ok(())
+    }
+
+    /// Build the windows and paint them, without starting the runloop.
+    ///
+    /// For cooperative schedulers: call [`RunLoop::run_once`] on the returned runloop
+    /// from a task, after input has been posted with `post_touch` or `post_event`.
+    ///
+    /// [`RunLoop::run_once`]: struct.RunLoop.html#method.run_once
+    pub fn build(mut self, data: T) -> Result<RunLoop<T>, PlatformError> { ////
         Application::init();
-        /* ////
-        let mut main_loop = RunLoop::new();
+        let mut main_loop = RunLoop::new(); ////
         let mut env = theme::init();
+        env.set_localization(self.bundles); ////
         if let Some(f) = self.env_setup.take() {
             f(&mut env);
         }
-        */ ////
 
         let mut state = AppState::<T>::new(); ////
         state.set_data(data); ////
+        state.set_env(env); ////
+        if let Some(delegate) = self.delegate.take() { ////
+            state.set_delegate(delegate);
+        } ////
         ////let state = AppState::new(data, env, self.delegate.take());
 
-        for desc in self.windows {
-            let window = desc.build_native(&mut state)?;
-            window.show();
+        //  Windows were added to the arena by `with_window` and `add_window`, in order of window ID
+        let mut id = WindowId(0); ////
+        while let Some(next_id) = T::with_arena(|arena| arena.next_window(id)) { ////
+            id = next_id;
+            let window = state.build_native(id)?; ////
+            state.window_added(id); ////
+            if crate::shell::focused_window().is_none() { //// Only the focused window is shown on the display
+                window.show();
+            } ////
+            main_loop.add_window(window); ////
         }
 
+        Ok(main_loop) ////
         ////main_loop.run();
-        Ok(())
+        ////Ok(())
     }
 }
 
-impl<T: Data + 'static + Default, W: Widget<T> + 'static> WindowDesc<T, W> { ////
+/// The root `Widget` type must be in the `WidgetSet` of the Data type, otherwise the `WindowDesc` will not compile.
+impl<T: AppData, W: Widget<T> + Clone + 'static> WindowDesc<T, W> ////
+where T::Widgets: From<W> {
     /// Create a new `WindowDesc`, taking a funciton that will generate the root
     /// [`Widget`] for this window.
     ///
@@ -194,12 +243,14 @@ impl<T: Data + 'static + Default, W: Widget<T> + 'static> WindowDesc<T, W> { ///
         self
     }
 
-    /// Attempt to create a platform window from this `WindowDesc`.
-    pub(crate) fn build_native(
-        &self,
+    /// Store the root `Widget` and the Window Handler of this window in the `UiArena`,
+    /// to be shown by `AppState::build_native`. The window keeps the ID of the `WindowDesc`.
+    /// If the arena has no slot for the window, the `WindowDesc` is dropped and its ID released.
+    pub(crate) fn add_to_arena(
+        self, ////
         state: &mut AppState<T> ////
         ////state: &Rc<RefCell<AppState<T>>>,
-    ) -> Result<WindowHandle<DruidHandler<T>>, PlatformError> { ////
+    ) -> Result<WindowId, CapacityError> { ////
     ////) -> Result<WindowHandle, PlatformError> {
         /* ////
         let mut title = self
@@ -213,15 +264,13 @@ impl<T: Data + 'static + Default, W: Widget<T> + 'static> WindowDesc<T, W> { ///
             .map(|m| m.build_window_menu(&state.borrow().data, &state.borrow().env));
         */ ////
 
+        if !T::with_arena(|arena| arena.has_window_slot(self.id)) { ////
+            return Err(CapacityError::Windows);
+        } ////
         let handler: DruidHandler<T> = DruidHandler::new_shared(self.id); ////
         ////let handler = DruidHandler::new_shared(state.clone(), self.id);
-
-        let mut builder: WindowBuilder<DruidHandler<T>> = WindowBuilder::new();
-        builder.set_handler(handler); ////
-        ////builder.set_handler(Box::new(handler));
-        if let Some(size) = self.size {
-            builder.set_size(size);
-        }
+        state.add_handler(self.id, handler); ////
+        state.set_window_size(self.id, self.size); ////
         /* ////
         builder.set_title(title.localized_str());
         if let Some(menu) = platform_menu {
@@ -230,16 +279,15 @@ impl<T: Data + 'static + Default, W: Widget<T> + 'static> WindowDesc<T, W> { ///
         */ ////
 
         let root_widget = (self.root_builder)(); ////
-        let root_box = root_widget.new_window(); ////
-        //let root_win = Window::new(root_widget); ////
-        //let root_box = WindowBox::new(&mut root_win); ////
+        let root_box = WindowBox::new(Window::new(root_widget)); ////
         ////let root = (self.root_builder)();
         state
             ////.borrow_mut()
             .add_window(self.id, root_box); ////
             ////.add_window(self.id, Window::new(root, title, menu));
-
-        builder.build()
+        let id = self.id; ////
+        core::mem::forget(self); ////  The window owns the ID now, don't release it
+        Ok(id) ////
     }
 
     /* ////
@@ -251,8 +299,15 @@ impl<T: Data + 'static + Default, W: Widget<T> + 'static> WindowDesc<T, W> { ///
     */ ////
 }
 
+/// Release the ID of a `WindowDesc` that was never added to the arena, so that it may be reused
+impl<T: AppData, W: Widget<T> + 'static> Drop for WindowDesc<T, W> { ////
+    fn drop(&mut self) {
+        self.id.release();
+    }
+} ////
+
 /// Implement formatted output for WindowDesc
-impl<T: Data + Default, W: Widget<T>> core::fmt::Debug for WindowDesc<T, W> { ////
+impl<T: AppData, W: Widget<T>> core::fmt::Debug for WindowDesc<T, W> { ////
     fn fmt(&self, _fmt: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
         //  TODO
         Ok(())
@@ -0,0 +1,527 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! `UiArena` owns the Widgets, Windows, Window Handlers and Application Data of an application.
+//! Replaces the `static mut` storage that used to be generated by `#[derive(Data)]` for embedded platforms.
+
+use core::cell::RefCell;
+use core::marker::PhantomData;
+use core::mem;
+use critical_section::Mutex;
+use crate::{
+    AppDelegate, Command, ConstDefault, Data, DruidHandler, Env, Instant, Transition, WindowBox, WindowId, ////
+    kurbo::Size,
+    command::CommandQueue,
+    navigator::{NavError, Navigation, MAX_SCREENS},
+    transition::ScreenTransition,
+    widget::{CapacityError, WidgetId, WidgetSet},
+};
+
+/// Application Data that owns a static `UiArena`. Implemented by `#[derive(Data)]` with `#[druid(app_state)]`.
+pub trait AppData: Data + Default + 'static {
+    /// The `Widget` types that may be boxed by `WidgetBox`, usually `WidgetType<Self>`
+    type Widgets: WidgetSet<Self>;
+
+    /// Static storage for the `UiArena`, usually `ArenaCell<Self>`
+    type Storage: ArenaStorage<Self> + 'static;
+
+    /// Storage for the Widgets, usually `[Self::Widgets; MAX_WIDGETS]`.
+    /// Set the size with `#[druid(app_state, max_widgets = 64)]`. The size doesn't change
+    /// which `Widget` types are stored, that's set by `Self::Widgets`.
+    type WidgetSlots: Slots<Self::Widgets>;
+
+    /// Storage for the Windows, usually `[WindowSlot<Self>; MAX_WINDOWS]`.
+    /// Window ID 0 is not used. Set the size with `#[druid(app_state, max_windows = 4)]`.
+    type WindowSlots: Slots<WindowSlot<Self>>;
+
+    /// The `AppDelegate` stored in the `UiArena`, usually `NoDelegate`.
+    /// Set the type with `#[druid(app_state, delegate = "MyDelegate")]`.
+    type Delegate: AppDelegate<Self>;
+
+    /// Return the static storage for the Data type
+    fn storage() -> &'static Self::Storage;
+
+    /// Run `f` with exclusive access to the `UiArena` for the Data type
+    fn with_arena<R, F: FnOnce(&mut UiArena<Self>) -> R>(f: F) -> R {
+        Self::storage().with(f)
+    }
+}
+
+/// Storage that hands out exclusive access to a `UiArena`
+pub trait ArenaStorage<D: AppData> {
+    /// Run `f` with exclusive access to the arena
+    fn with<R, F: FnOnce(&mut UiArena<D>) -> R>(&self, f: F) -> R;
+}
+
+/// Fixed-size storage for the Widgets or Windows of an application, e.g. `[D::Widgets; 64]`
+pub trait Slots<T>: AsRef<[T]> + AsMut<[T]> + 'static {
+    /// Storage with every slot empty
+    const EMPTY: Self;
+}
+
+impl<T: ConstDefault + 'static, const N: usize> Slots<T> for [T; N] {
+    const EMPTY: Self = [T::DEFAULT; N];
+}
+
+/// Max number of changes to the `Env` while a Window or the `AppDelegate` handles a call
+pub const MAX_ENV_CHANGES: usize = 4;
+
+/// A change to the `Env`, deferred while the `Env` is moved out of the arena
+#[derive(Clone, Copy)]
+pub(crate) enum EnvChange {
+    /// Run the function on the `Env`, e.g. to switch themes
+    Configure(fn(&mut Env)),
+    /// Switch the locale
+    SetLocale(&'static str),
+}
+
+impl EnvChange {
+    fn apply(self, env: &mut Env) {
+        match self {
+            EnvChange::Configure(f) => f(env),
+            EnvChange::SetLocale(locale) => env.set_locale(locale),
+        }
+    }
+}
+
+/// The Window, Window Handler and animation state for one window ID
+pub struct WindowSlot<D: AppData> {
+    window: WindowBox<D>,
+    handler: DruidHandler<D>,
+    /// The size set by `WindowDesc::window_size`, passed to the platform window
+    size: Option<Size>,
+    /// The time of the last animation frame painted, or `None` if the window is not animating
+    prev_paint_time: Option<Instant>,
+    /// The running change of screen, or `None` if the window is not changing screens
+    transition: Option<ScreenTransition<D>>,
+}
+
+impl<D: AppData> ConstDefault for WindowSlot<D> {
+    const DEFAULT: Self = WindowSlot {
+        window: WindowBox::empty(),
+        handler: DruidHandler { window_id: WindowId(0), phantom: PhantomData },
+        size: None,
+        prev_paint_time: None,
+        transition: None,
+    };
+}
+
+/// Owns the Widgets, Windows, Window Handlers and Application Data for the Data type.
+///
+/// Widgets, Windows and the Application Data are moved out of the arena while they
+/// handle a call and moved back afterwards, so the arena is never borrowed while
+/// Widget code is running.
+pub struct UiArena<D: AppData> {
+    /// widgets[i] is the Widget with Widget ID i
+    widgets: D::WidgetSlots,
+    /// windows[i] is the Window and Window Handler with window ID i. i=0 is not used.
+    windows: D::WindowSlots,
+    /// Commands submitted by Widgets, to be run after the current event
+    commands: CommandQueue,
+    /// Screens hidden by a `Navigator`, with the ID of their window and the `Transition`
+    /// that covered them, bottom first
+    screens: heapless::Vec<(WindowId, WindowBox<D>, Transition), MAX_SCREENS>,
+    /// Screen changes requested through a `Navigator`, to be applied after the current event
+    navigation: heapless::Deque<(WindowId, Navigation<D>), MAX_SCREENS>,
+    /// The `AppDelegate` set by `AppLauncher::delegate`
+    delegate: Option<D::Delegate>,
+    /// The Application Data
+    data: D,
+    /// True while the Application Data and the Env have been moved out to handle a call
+    data_taken: bool,
+    env: Env,
+    /// Changes to the Env while it's moved out, applied when it's returned
+    env_changes: heapless::Vec<EnvChange, MAX_ENV_CHANGES>,
+}
+
+impl<D: AppData> UiArena<D> {
+    /// Create an arena with no Widgets and Windows, and the initial Application Data
+    pub const fn new(data: D) -> Self {
+        UiArena {
+            widgets: D::WidgetSlots::EMPTY,
+            windows: D::WindowSlots::EMPTY,
+            commands: CommandQueue::new(),
+            screens: heapless::Vec::new(),
+            navigation: heapless::Deque::new(),
+            delegate: None,
+            data,
+            data_taken: false,
+            env: Env::empty(), ////
+            env_changes: heapless::Vec::new(),
+        }
+    }
+
+    /// Store the Widget with the Widget ID. Returns the Widget if the arena is too small.
+    pub(crate) fn add_widget(&mut self, id: WidgetId, widget: D::Widgets) -> Result<(), (D::Widgets, CapacityError)> {
+        match self.widgets.as_mut().get_mut(id.index()) {
+            Some(slot) => {
+                *slot = widget;
+                Ok(())
+            }
+            None => Err((widget, CapacityError::Widgets)),
+        }
+    }
+
+    /// Move the Widget out of the arena. Must be returned with `put_widget`.
+    pub(crate) fn take_widget(&mut self, id: WidgetId) -> D::Widgets {
+        mem::replace(&mut self.widgets.as_mut()[id.index()], D::Widgets::NONE)
+    }
+
+    /// Return a Widget that was moved out by `take_widget`
+    pub(crate) fn put_widget(&mut self, id: WidgetId, widget: D::Widgets) {
+        self.widgets.as_mut()[id.index()] = widget;
+    }
+
+    /// Store the Window with the window ID
+    pub(crate) fn add_window(&mut self, window_id: WindowId, window: WindowBox<D>) {
+        self.window_slot(window_id).window = window;
+    }
+
+    /// True if there is a slot for the window ID, i.e. the ID is less than `max_windows`
+    pub(crate) fn has_window_slot(&self, window_id: WindowId) -> bool {
+        (window_id.0 as usize) < self.windows.as_ref().len()
+    }
+
+    /// The Window, Window Handler and animation state for the window ID
+    fn window_slot(&mut self, window_id: WindowId) -> &mut WindowSlot<D> {
+        let slots = self.windows.as_mut();
+        assert!((window_id.0 as usize) < slots.len(), "too many windows, increase `max_windows`");
+        &mut slots[window_id.0 as usize]
+    }
+
+    /// Store the Window Handler with the window ID
+    pub(crate) fn add_handler(&mut self, window_id: WindowId, handler: DruidHandler<D>) {
+        self.window_slot(window_id).handler = handler;
+    }
+
+    /// Remove the Window and Window Handler with the window ID, so that the slot may be reused.
+    /// Returns the Window to be released, or `None` if the window is not open.
+    pub(crate) fn remove_window(&mut self, window_id: WindowId) -> Option<WindowBox<D>> {
+        if !self.is_window_open(window_id) {
+            return None;
+        }
+        Some(mem::replace(self.window_slot(window_id), WindowSlot::DEFAULT).window)
+    }
+
+    /// The open window with the lowest ID greater than `after`. Visits every open
+    /// window without holding the arena, e.g. to send `update` to each window.
+    pub(crate) fn next_window(&self, after: WindowId) -> Option<WindowId> {
+        self.windows.as_ref().iter()
+            .enumerate()
+            .skip(after.0 as usize + 1)
+            .map(|(i, slot)| (i, slot.handler.window_id))
+            .find(|(i, window_id)| window_id.0 as usize == *i)
+            .map(|(_, window_id)| window_id)
+    }
+
+    /// Return a copy of the Window Handler for the window ID
+    pub(crate) fn handler(&self, window_id: WindowId) -> DruidHandler<D> {
+        self.windows.as_ref()[window_id.0 as usize].handler.clone()
+    }
+
+    /// Set the size of the platform window for the window ID
+    pub(crate) fn set_window_size(&mut self, window_id: WindowId, size: Option<Size>) {
+        self.window_slot(window_id).size = size;
+    }
+
+    /// The size of the platform window for the window ID, if set by `WindowDesc::window_size`
+    pub(crate) fn window_size(&self, window_id: WindowId) -> Option<Size> {
+        self.windows.as_ref()[window_id.0 as usize].size
+    }
+
+    /// Return the time of the last animation frame painted for the window ID
+    pub(crate) fn prev_paint_time(&self, window_id: WindowId) -> Option<Instant> {
+        self.windows.as_ref()[window_id.0 as usize].prev_paint_time
+    }
+
+    /// Record the time of the last animation frame painted for the window ID
+    pub(crate) fn set_prev_paint_time(&mut self, window_id: WindowId, time: Option<Instant>) {
+        self.window_slot(window_id).prev_paint_time = time;
+    }
+
+    /// Queue a Command for the window ID. Returns the Command if the queue is full.
+    pub(crate) fn push_command(&mut self, window_id: WindowId, command: Command) -> Result<(), Command> {
+        self.commands.push_back((window_id, command))
+            .map_err(|(_, command)| command)
+    }
+
+    /// Pop the next queued Command
+    pub(crate) fn pop_command(&mut self) -> Option<(WindowId, Command)> {
+        self.commands.pop_front()
+    }
+
+    /// True if a screen may be pushed: counts the hidden screens, the screens to be
+    /// hidden by running transitions and the queued pushes
+    pub(crate) fn can_push_screen(&self) -> bool {
+        let pushes = self.navigation.iter()
+            .filter(|(_, navigation)| matches!(navigation, Navigation::Push(..)))
+            .count();
+        let hiding = self.windows.as_ref().iter()
+            .filter(|slot| matches!(&slot.transition, Some(transition) if transition.hides_old()))
+            .count();
+        self.can_navigate() && self.screens.len() + hiding + pushes < MAX_SCREENS
+    }
+
+    /// True if another screen change may be queued
+    pub(crate) fn can_navigate(&self) -> bool {
+        !self.navigation.is_full()
+    }
+
+    /// Number of screens hidden under the current screen of the window, after the queued changes
+    pub(crate) fn screen_depth(&self, window_id: WindowId) -> usize {
+        let hiding = matches!(self.windows.as_ref().get(window_id.0 as usize),
+            Some(WindowSlot { transition: Some(transition), .. }) if transition.hides_old());
+        let hidden = self.screens.iter().filter(|(id, ..)| *id == window_id).count() + hiding as usize;
+        self.navigation.iter()
+            .filter(|(id, _)| *id == window_id)
+            .fold(hidden, |depth, (_, navigation)| match navigation {
+                Navigation::Push(..) => depth + 1,
+                Navigation::Pop(_) => depth.saturating_sub(1),
+                Navigation::Replace(..) => depth,
+            })
+    }
+
+    /// Queue a screen change. Returns `NavError::Full` and the change, to be released outside
+    /// the arena, if too many screens would be hidden or too many changes are queued.
+    pub(crate) fn push_navigation(&mut self, window_id: WindowId, navigation: Navigation<D>)
+        -> Result<(), (NavError, Navigation<D>)> {
+        let full = match navigation {
+            Navigation::Push(..) => !self.can_push_screen(),
+            _ => !self.can_navigate(),
+        };
+        if full {
+            return Err((NavError::Full, navigation));
+        }
+        self.navigation.push_back((window_id, navigation))
+            .map_err(|(_, navigation)| (NavError::Full, navigation))
+    }
+
+    /// Pop the next queued screen change
+    pub(crate) fn pop_navigation(&mut self) -> Option<(WindowId, Navigation<D>)> {
+        self.navigation.pop_front()
+    }
+
+    /// Hide the old screen of the window under the current screen, which was pushed with the `Transition`.
+    /// Returns `NavError::Full` and the screen, to be released outside the arena, if `MAX_SCREENS`
+    /// screens are hidden.
+    pub(crate) fn hide_screen(&mut self, window_id: WindowId, screen: WindowBox<D>, transition: Transition)
+        -> Result<(), (NavError, WindowBox<D>)> {
+        self.screens.push((window_id, screen, transition))
+            .map_err(|(_, screen, _)| (NavError::Full, screen))
+    }
+
+    /// Show the new screen in the window. Returns the old screen to be released.
+    pub(crate) fn replace_screen(&mut self, window_id: WindowId, screen: WindowBox<D>) -> WindowBox<D> {
+        mem::replace(&mut self.window_slot(window_id).window, screen)
+    }
+
+    /// Show the screen that was hidden last in the window. Returns the old screen to be
+    /// released and the `Transition` that pushed it, or `None` if no screen is hidden.
+    pub(crate) fn restore_screen(&mut self, window_id: WindowId) -> Option<(WindowBox<D>, Transition)> {
+        let index = self.screens.iter().rposition(|(id, ..)| *id == window_id)?;
+        let (_, screen, transition) = self.screens.remove(index);
+        Some((self.replace_screen(window_id, screen), transition))
+    }
+
+    /// Remove one hidden screen of the window, when the window is closed.
+    /// Returns `None` when no screens are hidden.
+    pub(crate) fn remove_hidden_screen(&mut self, window_id: WindowId) -> Option<WindowBox<D>> {
+        let index = self.screens.iter().position(|(id, ..)| *id == window_id)?;
+        Some(self.screens.remove(index).1)
+    }
+
+    /// Remove the queued screen changes of the window, when the window is closed.
+    /// Returns the queued screens to be released.
+    pub(crate) fn cancel_navigation(&mut self, window_id: WindowId) -> heapless::Vec<WindowBox<D>, MAX_SCREENS> {
+        let mut screens = heapless::Vec::new();
+        let mut kept = heapless::Deque::new();
+        while let Some((id, navigation)) = self.navigation.pop_front() {
+            match navigation {
+                _ if id != window_id => { kept.push_back((id, navigation)).ok(); }
+                Navigation::Push(screen, _) | Navigation::Replace(screen, _) => { screens.push(screen).ok(); }
+                Navigation::Pop(_) => {}
+            }
+        }
+        self.navigation = kept;
+        screens
+    }
+
+    /// Start a change of screen in the window. The running transition must have been finished.
+    pub(crate) fn start_transition(&mut self, window_id: WindowId, transition: ScreenTransition<D>) {
+        self.window_slot(window_id).transition = Some(transition);
+    }
+
+    /// Advance the running transition of the window by the interval in nanoseconds.
+    /// Returns true while the transition is running.
+    pub(crate) fn step_transition(&mut self, window_id: WindowId, interval: u64) -> bool {
+        match &mut self.window_slot(window_id).transition {
+            Some(transition) => !transition.step(interval),
+            None => false,
+        }
+    }
+
+    /// End the running transition of the window, hiding the old screen if it was pushed over.
+    /// Returns the old screen to be released, or `None` if there is nothing to release.
+    pub(crate) fn finish_transition(&mut self, window_id: WindowId) -> Option<WindowBox<D>> {
+        let (old, hide) = self.window_slot(window_id).transition.take()?.finish();
+        match hide {
+            Some(transition) => self.hide_screen(window_id, old, transition).err().map(|(_, old)| old),
+            None => Some(old),
+        }
+    }
+
+    /// True while the window is changing screens
+    pub(crate) fn in_transition(&self, window_id: WindowId) -> bool {
+        matches!(self.windows.as_ref().get(window_id.0 as usize),
+            Some(WindowSlot { transition: Some(_), .. }))
+    }
+
+    /// Move the running transition of the window out of the arena to paint it.
+    /// Must be returned with `put_transition`.
+    pub(crate) fn take_transition(&mut self, window_id: WindowId) -> Option<ScreenTransition<D>> {
+        self.window_slot(window_id).transition.take()
+    }
+
+    /// Return a transition that was moved out by `take_transition`
+    pub(crate) fn put_transition(&mut self, window_id: WindowId, transition: ScreenTransition<D>) {
+        self.window_slot(window_id).transition = Some(transition);
+    }
+
+    /// True if the window is open
+    pub(crate) fn is_window_open(&self, window_id: WindowId) -> bool {
+        matches!(self.windows.as_ref().get(window_id.0 as usize),
+            Some(slot) if window_id.0 != 0 && slot.handler.window_id == window_id)
+    }
+
+    /// Replace the Application Data
+    pub(crate) fn set_data(&mut self, data: D) {
+        assert!(!self.data_taken, "data in use");
+        self.data = data;
+    }
+
+    /// The environment passed to the Widgets. Panics while the environment is moved out.
+    pub(crate) fn env(&self) -> &Env {
+        assert!(!self.data_taken, "env in use");
+        &self.env
+    }
+
+    /// Replace the environment
+    pub(crate) fn set_env(&mut self, env: Env) {
+        assert!(!self.data_taken, "env in use");
+        self.env = env;
+    }
+
+    /// Modify the environment, e.g. to switch themes. While a Window or the `AppDelegate`
+    /// handles a call, the change is applied when the environment is returned to the arena.
+    /// A locale switch replaces a pending locale switch. Fails if `MAX_ENV_CHANGES` changes are pending.
+    pub(crate) fn configure_env(&mut self, change: EnvChange) -> Result<(), CapacityError> {
+        if !self.data_taken {
+            change.apply(&mut self.env);
+            return Ok(());
+        }
+        if let EnvChange::SetLocale(_) = change {
+            let pending = self.env_changes.iter_mut()
+                .find(|pending| matches!(pending, EnvChange::SetLocale(_)));
+            if let Some(pending) = pending {
+                *pending = change;
+                return Ok(());
+            }
+        }
+        self.env_changes.push(change).map_err(|_| CapacityError::EnvChanges)
+    }
+
+    /// Return the environment that was moved out with the Application Data, and apply the deferred changes
+    fn put_env(&mut self, env: Env) {
+        self.env = env;
+        for change in self.env_changes.iter() {
+            change.apply(&mut self.env);
+        }
+        self.env_changes.clear();
+    }
+
+    /// Set the `AppDelegate`
+    pub(crate) fn set_delegate(&mut self, delegate: D::Delegate) {
+        self.delegate = Some(delegate);
+    }
+
+    /// True if an `AppDelegate` has been set and may be taken, i.e. the `AppDelegate`
+    /// and the Application Data are not in use by an outer call
+    pub(crate) fn delegate_ready(&self) -> bool {
+        self.delegate.is_some() && !self.data_taken
+    }
+
+    /// Move the `AppDelegate`, the Application Data, the Env and the Command queue out of the arena.
+    /// Must be returned with `put_delegate`. Returns `None` if there is no `AppDelegate` or the
+    /// Application Data is already in use.
+    pub(crate) fn take_delegate(&mut self) -> Option<(D::Delegate, D, Env, CommandQueue)> {
+        if self.data_taken { return None; }
+        let delegate = self.delegate.take()?;
+        self.data_taken = true;
+        let data = mem::take(&mut self.data);
+        let commands = mem::replace(&mut self.commands, CommandQueue::new());
+        Some((delegate, data, mem::take(&mut self.env), commands)) ////
+    }
+
+    /// Return the `AppDelegate`, Application Data, Env and Command queue that were moved out by `take_delegate`
+    pub(crate) fn put_delegate(&mut self, delegate: D::Delegate, data: D, env: Env, commands: CommandQueue) {
+        self.delegate = Some(delegate);
+        self.data = data;
+        self.put_env(env);
+        self.commands = commands;
+        self.data_taken = false;
+    }
+
+    /// Move the Window, the Application Data and the Env out of the arena. Must be returned
+    /// with `put_window`. Returns `None` if the Application Data is already in use,
+    /// e.g. when an interrupt handler calls into the UI while the UI is handling a call.
+    pub(crate) fn take_window(&mut self, window_id: WindowId) -> Option<(WindowBox<D>, D, Env)> {
+        if self.data_taken { return None; }
+        self.data_taken = true;
+        let window = mem::take(&mut self.window_slot(window_id).window);
+        let data = mem::take(&mut self.data);
+        Some((window, data, mem::take(&mut self.env))) ////
+    }
+
+    /// Return a Window, Application Data and Env that were moved out by `take_window`
+    pub(crate) fn put_window(&mut self, window_id: WindowId, window: WindowBox<D>, data: D, env: Env) {
+        self.window_slot(window_id).window = window;
+        self.data = data;
+        self.put_env(env);
+        self.data_taken = false;
+    }
+}
+
+/// Static storage for a `UiArena`, generated by `#[derive(Data)]` with `#[druid(app_state)]`.
+///
+/// Access is serialised by a critical section, so the arena may be shared with
+/// interrupt handlers without `static mut`.
+pub struct ArenaCell<D: AppData>(
+    Mutex<RefCell<UiArena<D>>>,
+);
+
+impl<D: AppData> ArenaCell<D> {
+    /// Create the static storage with the initial Application Data
+    pub const fn new(data: D) -> Self {
+        ArenaCell(Mutex::new(RefCell::new(UiArena::new(data))))
+    }
+}
+
+impl<D: AppData> ArenaStorage<D> for ArenaCell<D> {
+    /// Run `f` with exclusive access to the arena. Panics if the arena is already borrowed.
+    fn with<R, F: FnOnce(&mut UiArena<D>) -> R>(&self, f: F) -> R {
+        critical_section::with(|cs| {
+            f(&mut self.0.borrow(cs).borrow_mut())
+        })
+    }
+}
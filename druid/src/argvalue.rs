@@ -1,24 +1,56 @@
 ////  Simplified replacement for FluentValue. Derived from https://github.com/projectfluent/fluent-rs/blob/master/fluent-bundle/src/types.rs
 
-use core::fmt::Write;
+use core::fmt::{self, Write};
+
+use crate::intl::{self, LocaleData, PluralCategory, PluralOperands}; ////
 
 /// Max number of arg values
-type MaxArgValues = heapless::consts::U2;
+const MAX_ARG_VALUES: usize = 2;
 /// Hash map of arg names to arg values
-pub type ArgValues = heapless::FnvIndexMap<&'static str, ArgValue, MaxArgValues>;
+pub type ArgValues = heapless::FnvIndexMap<&'static str, ArgValue, MAX_ARG_VALUES>;
 
-type MaxLocalizedString = heapless::consts::U20; //// Max length of localized strings
-type String = heapless::String::<MaxLocalizedString>; ////
+const MAX_LOCALIZED_STRING: usize = 20; //// Max length of localized strings
+type String = heapless::String::<MAX_LOCALIZED_STRING>; ////
 
+/// The value of an argument of a localized string. Numbers, dates and times are
+/// formatted for the current locale, e.g. `1.234,5` in `de-DE`.
 #[derive(Debug, PartialEq, Clone)]
 pub enum ArgValue {
     String(String),
     U32(u32),
     I32(i32),
+    /// A float and the number of fraction digits to show, e.g. `Float(1.5, 2)` for `1.50`
+    Float(f32, u8), ////
+    /// A fixed-point number `mantissa / 10^scale`, e.g. `Fixed(12345, 1)` for `1234.5`.
+    /// Shows `scale` fraction digits. Avoids floats on microcontrollers without an FPU.
+    Fixed(i32, u8), ////
+    Date(Date), ////
+    Time(Time), ////
+    /// A duration in seconds, shown like a stopwatch: `1:02:03`
+    Duration(u32), ////
     Error(String),
     None,
 }
 
+/// A calendar date, for `ArgValue::Date`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)] ////
+pub struct Date {
+    pub year: u16,
+    /// 1 to 12
+    pub month: u8,
+    /// 1 to 31
+    pub day: u8,
+}
+
+/// A time of day, for `ArgValue::Time`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)] ////
+pub struct Time {
+    /// 0 to 23
+    pub hour: u8,
+    /// 0 to 59
+    pub minute: u8,
+}
+
 impl ArgValue {
     /*
         pub fn into_number(v: String) -> Self {
@@ -30,6 +62,54 @@ impl ArgValue {
         }
     */
 
+    /// True if the value matches the key of a select expression variant: the same string,
+    /// the same number, or a number in the plural category of the locale, e.g. `one` or `other`
+    pub fn matches(&self, key: &str, locale: &str) -> bool { ////
+        if let ArgValue::String(s) = self {
+            return s.as_str() == key;
+        }
+        let (negative, number) = match self.number() {
+            Some(number) => number,
+            None => return false,
+        };
+        match PluralOperands::parse(key) {
+            Some((key_negative, key)) => {
+                number.same_value(&key) && (negative == key_negative || number.is_zero())
+            }
+            None => PluralCategory::from_key(key) == Some(PluralCategory::for_number(locale, &number)),
+        }
+    }
+
+    /// The sign and plural operands of a number, as displayed. `None` if the value is not a number.
+    pub fn number(&self) -> Option<(bool, PluralOperands)> { ////
+        match *self {
+            ArgValue::U32(n) => Some((false, PluralOperands::from_integer(i64::from(n)))),
+            ArgValue::I32(n) => Some((n < 0, PluralOperands::from_integer(i64::from(n)))),
+            ArgValue::Float(v, precision) => Some((v < 0.0, PluralOperands::from_float(v, precision))),
+            ArgValue::Fixed(n, scale) => Some((n < 0, PluralOperands::from_fixed(i64::from(n), scale))),
+            _ => None,
+        }
+    }
+
+    /// Write the value formatted for the locale, e.g. `1.234,5` for `Fixed(12345, 1)` in `de-DE`
+    pub fn write_localized<W: Write>(&self, locale: &str, out: &mut W) -> fmt::Result { ////
+        self.write_with(intl::locale_data(locale), out)
+    }
+
+    fn write_with<W: Write>(&self, data: &LocaleData, out: &mut W) -> fmt::Result { ////
+        if let Some((negative, number)) = self.number() {
+            return data.write_number(negative, &number, out);
+        }
+        match self {
+            ArgValue::String(s) => out.write_str(s),
+            ArgValue::Date(date) => data.write_date(date, out),
+            ArgValue::Time(time) => data.write_time(time, out),
+            ArgValue::Duration(seconds) => intl::write_duration(*seconds, out),
+            ArgValue::Error(d) => write!(out, "{{{}}}", d),
+            _ => out.write_str("???"),
+        }
+    }
+
     /*
         pub fn matches<R: Borrow<FluentResource>>(
             &self,
@@ -74,22 +154,30 @@ impl ArgValue {
             }
             ArgValue::Error(_s) => "Error".into(),
             ArgValue::None => "???".into(),
+            _ => { ////
+                let mut buffer = String::new();
+                write!(&mut buffer, "{}", self)
+                    .ok(); //  Truncated if too long
+                buffer
+            }
         }
     }
 }
 
-/*
-    impl<'source> fmt::Display for ArgValue<'source> {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            match self {
-                ArgValue::String(s) => f.write_str(s),
-                ArgValue::Number(n) => f.write_str(n),
-                ArgValue::Error(d) => write!(f, "{{{}}}", d),
-                ArgValue::None => f.write_str("???"),
-            }
+impl fmt::Display for ArgValue { ////
+////impl<'source> fmt::Display for ArgValue<'source> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgValue::String(s) => f.write_str(s),
+            ////ArgValue::Number(n) => f.write_str(n),
+            ArgValue::U32(v) => write!(f, "{}", v), ////
+            ArgValue::I32(v) => write!(f, "{}", v), ////
+            ArgValue::Error(d) => write!(f, "{{{}}}", d),
+            ArgValue::None => f.write_str("???"),
+            _ => self.write_with(&intl::ROOT, f), ////  Unlocalized
         }
     }
-*/
+}
 
 impl From<String> for ArgValue {
     fn from(s: String) -> Self {
@@ -115,6 +203,18 @@ impl From<i32> for ArgValue {
     }
 }
 
+impl From<Date> for ArgValue { ////
+    fn from(v: Date) -> Self {
+        ArgValue::Date(v)
+    }
+}
+
+impl From<Time> for ArgValue { ////
+    fn from(v: Time) -> Self {
+        ArgValue::Time(v)
+    }
+}
+
 /*
     macro_rules! from_num {
         ($num:ty) => {
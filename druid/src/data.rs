@@ -64,6 +64,24 @@ pub use druid_derive::Data;
 /// This function must have a signature in the form, `fn<T>(&T, &T) -> bool`,
 /// where `T` is the type of the field.
 ///
+/// - **`#[druid(init = "expr")]`**
+///
+/// The initial value of this field in the static Application Data, e.g.
+/// `#[druid(init = "Mode::Off")]`. On the type itself, the value of the whole
+/// type. See [`ConstDefault`].
+///
+/// - **`#[druid(init)]`**
+///
+/// On a struct, derive [`ConstDefault`] from the fields, for a struct that is
+/// a field of the Application Data.
+///
+/// - **`#[druid(app_state)]`**
+///
+/// Mark the Application Data type, which owns the static Widgets and Windows.
+/// Implements [`AppData`] and `ConstDefault`, and adds `handle_touch` to the type,
+/// e.g. `State::handle_touch(x, y)`. Use `#[druid(app_state, widgets = "MyWidgets")]`
+/// to store the `Widgets` of a `widget_set!`.
+///
 /// ### Example:
 ///
 /// ```
@@ -89,6 +107,8 @@ pub use druid_derive::Data;
 /// checks for equality. Therefore, such types must also implement `PartialEq`.
 ///
 /// [`Data::same`]: trait.Data.html#tymethod.same
+/// [`ConstDefault`]: trait.ConstDefault.html
+/// [`AppData`]: trait.AppData.html
 pub trait Data: Clone {
     /// Determine whether two values are the same.
     ///
@@ -236,3 +256,94 @@ impl<T0: Data, T1: Data, T2: Data, T3: Data, T4: Data, T5: Data> Data for (T0, T
             && self.5.same(&other.5)
     }
 }
+
+impl<T: Data, const N: usize> Data for [T; N] { ////
+    fn same(&self, other: &Self) -> bool {
+        self.iter().zip(other.iter()).all(|(a, b)| a.same(b))
+    }
+}
+
+impl<T: Data, const N: usize> Data for heapless::Vec<T, N> { ////
+    fn same(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().zip(other.iter()).all(|(a, b)| a.same(b))
+    }
+}
+
+impl<const N: usize> Data for heapless::String<N> { ////
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+/// The value of a type that can be computed at compile time, so that the
+/// Application Data can be initialised in a `static` without dynamic memory.
+///
+/// `#[derive(Data)]` implements this for types marked `#[druid(app_state)]` or
+/// `#[druid(init)]`, using `#[druid(init = "expr")]` on a field (or on the type)
+/// when given, and the field type's `DEFAULT` otherwise. Enums need `init = "expr"`.
+/// For other types, implement it by hand, e.g. with a `const fn`:
+///
+/// ```
+/// # use druid::ConstDefault;
+/// struct Celsius(f32);
+/// impl Celsius {
+///     const fn new(degrees: f32) -> Self { Celsius(degrees) }
+/// }
+/// impl ConstDefault for Celsius {
+///     const DEFAULT: Self = Celsius::new(20.0);
+/// }
+/// ```
+pub trait ConstDefault: Sized {
+    /// The initial value
+    const DEFAULT: Self;
+}
+
+/// An impl of `ConstDefault` for types with a literal default value.
+macro_rules! impl_const_default {
+    ($($t:ty = $v:expr),* $(,)?) => {
+        $(
+            impl ConstDefault for $t {
+                const DEFAULT: Self = $v;
+            }
+        )*
+    };
+}
+
+impl_const_default!(
+    i8 = 0, i16 = 0, i32 = 0, i64 = 0, isize = 0,
+    u8 = 0, u16 = 0, u32 = 0, u64 = 0, usize = 0,
+    f32 = 0.0, f64 = 0.0, char = '\0', bool = false, () = (),
+    &'static str = "",
+);
+
+impl<T> ConstDefault for Option<T> {
+    const DEFAULT: Self = None;
+}
+
+impl<T: ConstDefault, const N: usize> ConstDefault for [T; N] {
+    const DEFAULT: Self = [T::DEFAULT; N];
+}
+
+impl<T, const N: usize> ConstDefault for heapless::Vec<T, N> {
+    const DEFAULT: Self = heapless::Vec::new();
+}
+
+impl<const N: usize> ConstDefault for heapless::String<N> {
+    const DEFAULT: Self = heapless::String::new();
+}
+
+impl<T0: ConstDefault> ConstDefault for (T0,) {
+    const DEFAULT: Self = (T0::DEFAULT,);
+}
+
+impl<T0: ConstDefault, T1: ConstDefault> ConstDefault for (T0, T1) {
+    const DEFAULT: Self = (T0::DEFAULT, T1::DEFAULT);
+}
+
+impl<T0: ConstDefault, T1: ConstDefault, T2: ConstDefault> ConstDefault for (T0, T1, T2) {
+    const DEFAULT: Self = (T0::DEFAULT, T1::DEFAULT, T2::DEFAULT);
+}
+
+impl<T0: ConstDefault, T1: ConstDefault, T2: ConstDefault, T3: ConstDefault> ConstDefault for (T0, T1, T2, T3) {
+    const DEFAULT: Self = (T0::DEFAULT, T1::DEFAULT, T2::DEFAULT, T3::DEFAULT);
+}
@@ -15,8 +15,11 @@
 //! An environment which is passed downward into the widget tree.
 
 ////use std::collections::HashMap;
+use core::fmt::{self, Debug, Formatter}; ////
 ////use std::fmt::{Debug, Formatter};
+use core::cell::Cell; ////
 use core::marker::PhantomData;////
+use critical_section::Mutex; ////
 ////use std::marker::PhantomData;
 ////use std::ops::Deref;
 ////use std::sync::Arc;
@@ -25,8 +28,9 @@ use crate::kurbo::{Point, Rect, Size};
 use crate::piet::{Color}; ////
 ////use crate::piet::{Color, LinearGradient};
 
-////use crate::localization::L10nManager;
-use crate::Data;
+use crate::localization::{Bundle, L10nManager}; ////
+use crate::{CapacityError, Data}; ////
+////use crate::Data;
 
 /// An environment passed down through all widget traversals.
 ///
@@ -38,18 +42,26 @@ use crate::Data;
 /// example of the latter is setting a value for enabled/disabled status
 /// so that an entire subtree can be disabled ("grayed out") with one
 /// setting.
-#[derive(Clone, Copy)] ////
-///#[derive(Clone)]
-pub struct Env {} ////
+#[derive(Clone)]
+pub struct Env(EnvImpl); //// Without an allocator there is no `Arc`, so cloning copies the map
 ////pub struct Env(Arc<EnvImpl>);
 
-/*
+/// Max number of values in an environment. Must be a power of 2 for `FnvIndexMap`.
+pub const MAX_ENV_VALUES: usize = 16; ////
+
 #[derive(Clone)]
 struct EnvImpl {
-    map: HashMap<String, Value>,
-    l10n: Arc<L10nManager>,
+    map: heapless::FnvIndexMap<&'static str, Value, MAX_ENV_VALUES>, ////
+    ////map: HashMap<String, Value>,
+    l10n: L10nManager, ////
+    ////l10n: Arc<L10nManager>,
+    /// Changes whenever a value or the locale changes. Copies of an `Env` with the same
+    /// generation have the same values, so `WidgetPod` only keeps the generation.
+    generation: u32, ////
 }
-*/
+
+/// The generation of the next change to any `Env`. Generation 0 is the empty `Env`.
+static NEXT_GENERATION: Mutex<Cell<u32>> = Mutex::new(Cell::new(1)); ////
 
 /// A typed key.
 ///
@@ -59,30 +71,26 @@ struct EnvImpl {
 ///
 /// [`ValueType`]: trait.ValueType.html
 pub struct Key<T> {
-    #[allow(dead_code)] ////
     key: &'static str,
     value_type: PhantomData<T>,
 }
 
-type MaxStringValue = heapless::consts::U20; //// Max length of string values
-type String = heapless::String::<MaxStringValue>; ////
+const MAX_STRING_VALUE: usize = 20; //// Max length of string values
+type String = heapless::String::<MAX_STRING_VALUE>; ////
 
 ////TODO: Sync with piet-embedded-graphics/src/text.rs
 ////pub type FontType<'a> = fonts::Font12x16::<'a, Rgb565>;
 //pub const FONT_WIDTH:  u16  = 12;
 pub const FONT_HEIGHT: u16  = 16;
 pub const FONT_NAME:   &str = &"Font12x16";
-pub const TEXT_SIZE_NORMAL: f64 = FONT_HEIGHT as f64;
+////pub const TEXT_SIZE_NORMAL: f64 = FONT_HEIGHT as f64;  //// Moved to `theme`
 
 pub const WINDOW_WIDTH:  u16 = 240;  //// 240x240 screen for PineTime
 pub const WINDOW_HEIGHT: u16 = 240;
 
-pub const WINDOW_BACKGROUND_COLOR: Color = Color::BLACK;
-pub const LABEL_COLOR:  Color = Color::WHITE;
-pub const BORDER:       Color = Color::WHITE;
-//pub const BORDER_LIGHT: Color = Color::WHITE;
-//pub const BUTTON_LIGHT: Color = Color::BLACK;
-//pub const BUTTON_DARK:  Color = Color::BLACK;
+////pub const WINDOW_BACKGROUND_COLOR: Color = Color::BLACK;  //// Moved to `theme`
+////pub const LABEL_COLOR:  Color = Color::WHITE;
+////pub const BORDER:       Color = Color::WHITE;
 
 // we could do some serious deriving here: the set of types that can be stored
 // could be defined per-app
@@ -119,9 +127,20 @@ pub trait ValueType<'a>: Sized {
 /// This error is expected to happen rarely, if ever, as it only
 /// happens when the string part of keys collide but the types
 /// mismatch.
-///
-/// TODO: replace with a less stringly-typed object.
-pub type EnvError = String;
+#[derive(Debug, Clone, PartialEq)] ////
+pub struct EnvError { ////
+    /// The type of value that was requested
+    pub expected: &'static str,
+    /// The type of value that was found
+    pub found: &'static str,
+}
+////pub type EnvError = String;
+
+impl fmt::Display for EnvError { ////
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "incorrect Value type. Expected {}, found {}", self.expected, self.found)
+    }
+}
 
 impl Env {
     /// Gets a value from the environment, expecting it to be present.
@@ -133,15 +152,12 @@ impl Env {
     /// # Panics
     ///
     /// Panics if the key is not found, or if it is present with the wrong type.
-    pub fn get<'a, V: ValueType<'a>>(&'a self, _key: Key<V>) -> V {
-        panic!("no env get"); ////TODO
-        /* ////
+    pub fn get<'a, V: ValueType<'a>>(&'a self, key: Key<V>) -> V {
         if let Some(value) = self.0.map.get(key.key) {
             value.to_inner_unchecked()
         } else {
             panic!("key for {} not found", key.key)
         }
-        */ ////
     }
 
     /// Gets a value from the environment.
@@ -149,24 +165,36 @@ impl Env {
     /// # Panics
     ///
     /// Panics if the value for the key is found, but has the wrong type.
-    pub fn try_get<'a, V: ValueType<'a>>(&'a self, _key: Key<V>) -> Option<V> {
-        assert!(false, "no env try_get"); ////TODO
-        None ////
-        /*
+    pub fn try_get<'a, V: ValueType<'a>>(&'a self, key: Key<V>) -> Option<V> {
         self.0
             .map
             .get(key.key)
             .map(|value| value.to_inner_unchecked())
-        */
     }
 
     /// Adds a key/value, acting like a builder.
-    #[allow(unused_mut)] ////
-    pub fn adding<'a, V: ValueType<'a>>(mut self, _key: Key<V>, _value: impl Into<V::Owned>) -> Env {
-        assert!(false, "no env add"); ////TODO
+    ///
+    /// # Panics
+    ///
+    /// Panics if the environment is full, see [`try_adding`].
+    ///
+    /// [`try_adding`]: #method.try_adding
+    pub fn adding<'a, V: ValueType<'a>>(self, key: Key<V>, value: impl Into<V::Owned>) -> Env { ////
+    ////pub fn adding<'a, V: ValueType<'a>>(mut self, key: Key<V>, value: impl Into<V::Owned>) -> Env {
+        let name = key.key; ////
+        match self.try_adding(key, value) { ////
+            Ok(env) => env,
+            Err(_) => panic!("env full, increase MAX_ENV_VALUES to add '{}'", name),
+        } ////
         ////let env = Arc::make_mut(&mut self.0);
         ////env.map.insert(key.into(), value.into().into());
-        self
+        ////self
+    }
+
+    /// Adds a key/value, acting like a builder. Fails if all `MAX_ENV_VALUES` keys already have values.
+    pub fn try_adding<'a, V: ValueType<'a>>(mut self, key: Key<V>, value: impl Into<V::Owned>) -> Result<Env, CapacityError> { ////
+        self.0.insert(key.key, value.into().into())?;
+        Ok(self)
     }
 
     /// Sets a value in an environment.
@@ -174,13 +202,29 @@ impl Env {
     /// # Panics
     ///
     /// Panics if the environment already has a value for the key, but it is
+    /// of a different type, or if the environment is full, see [`try_set`].
+    ///
+    /// [`try_set`]: #method.try_set
+    pub fn set<'a, V: ValueType<'a>>(&'a mut self, key: Key<V>, value: impl Into<V::Owned>) {
+        let name = key.key; ////
+        if self.try_set(key, value).is_err() { ////
+            panic!("env full, increase MAX_ENV_VALUES to add '{}'", name);
+        } ////
+    }
+
+    /// Sets a value in an environment. Fails if the key has no value and all `MAX_ENV_VALUES`
+    /// keys already have values. Setting the value that the key already has doesn't change the `Env`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the environment already has a value for the key, but it is
     /// of a different type.
-    pub fn set<'a, V: ValueType<'a>>(&'a mut self, _key: Key<V>, _value: impl Into<V::Owned>) {
-        assert!(false, "no env set"); ////TODO
-        /* ////
-        let env = Arc::make_mut(&mut self.0);
+    pub fn try_set<'a, V: ValueType<'a>>(&'a mut self, key: Key<V>, value: impl Into<V::Owned>) -> Result<(), CapacityError> { ////
+        let env = &mut self.0; ////
+        ////let env = Arc::make_mut(&mut self.0);
         let value = value.into().into();
-        let key = key.into();
+        let key = key.key; ////
+        ////let key = key.into();
         // TODO: use of Entry might be more efficient
         if let Some(existing) = env.map.get(&key) {
             if !existing.is_same_type(&value) {
@@ -189,12 +233,23 @@ impl Env {
                     key, existing, value
                 );
             }
+            if existing.same(&value) { ////
+                return Ok(());
+            } ////
         }
-        env.map.insert(key, value);
-        */ ////
+        env.insert(key, value) ////
+        ////env.map.insert(key, value);
+    }
+
+    /// An empty environment. `const` so that it can live in the static `UiArena`.
+    pub(crate) const fn empty() -> Env { ////
+        Env(EnvImpl {
+            map: heapless::FnvIndexMap::new(),
+            l10n: L10nManager::empty(),
+            generation: 0,
+        })
     }
 
-    /*
     /// Returns a reference to the [`L10nManager`], which handles localization
     /// resources.
     ///
@@ -202,26 +257,76 @@ impl Env {
     pub(crate) fn localization_manager(&self) -> &L10nManager {
         &self.0.l10n
     }
-    */
+
+    /// Look up localized strings in the bundles, in order of preference
+    pub(crate) fn set_localization(&mut self, bundles: &'static [Bundle]) { ////
+        self.0.l10n = L10nManager::new(bundles);
+        self.0.changed();
+    }
+
+    /// The BCP47 language tag of the locale that strings are localized into, e.g. `en-US`
+    pub fn locale(&self) -> &'static str { ////
+        self.0.l10n.current_locale()
+    }
+
+    /// Localize strings into the locale, e.g. when the user picks a language in settings.
+    /// Falls back to other locales of the same language, then to the default locale.
+    ///
+    /// Every [`LocalizedString`] is resolved again when the changed `Env` is passed to
+    /// `update()`. Use [`EventCtx::set_locale`] to switch the locale of a running app.
+    ///
+    /// [`LocalizedString`]: struct.LocalizedString.html
+    /// [`EventCtx::set_locale`]: struct.EventCtx.html#method.set_locale
+    pub fn set_locale(&mut self, locale: &'static str) { ////
+        if locale == self.locale() {
+            return;
+        }
+        self.0.l10n.set_locale(locale);
+        self.0.changed();
+    }
+
+    /// Changes whenever a value or the locale changes, so that `WidgetPod` may tell if the
+    /// `Env` changed without keeping a copy of it
+    pub(crate) fn generation(&self) -> u32 { ////
+        self.0.generation
+    }
+}
+
+impl EnvImpl { ////
+    /// Insert or replace the value for the key, then move to a new generation. Fails if the map is full.
+    fn insert(&mut self, key: &'static str, value: Value) -> Result<(), CapacityError> {
+        self.map.insert(key, value).map_err(|_| CapacityError::EnvValues)?;
+        self.changed();
+        Ok(())
+    }
+
+    /// Move to a new generation after a change
+    fn changed(&mut self) {
+        self.generation = critical_section::with(|cs| {
+            let next = NEXT_GENERATION.borrow(cs);
+            let generation = next.get();
+            next.set(generation.wrapping_add(1).max(1));
+            generation
+        });
+    }
 }
 
-/* ////
 impl Debug for Value {
-    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result { ////
+    ////fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
         match self {
             Value::Point(p) => write!(f, "Point {:?}", p),
             Value::Size(s) => write!(f, "Size {:?}", s),
             Value::Rect(r) => write!(f, "Rect {:?}", r),
             Value::Color(c) => write!(f, "Color {:?}", c),
             // TODO: make PaintBrush impl debug?
-            Value::LinearGradient(g) => write!(f, "LinearGradient {:?}", g),
+            ////Value::LinearGradient(g) => write!(f, "LinearGradient {:?}", g),
             Value::Float(x) => write!(f, "Float {}", x),
             Value::UnsignedInt(x) => write!(f, "UnsignedInt {}", x),
             Value::String(s) => write!(f, "String {:?}", s),
         }
     }
 }
-*/ ////
 
 impl<T> Key<T> {
     /// Create a new strongly typed `Key` with the given string value.
@@ -257,7 +362,19 @@ impl Value {
         }
     }
 
-    #[allow(dead_code)] ////
+    /// Name of the value variant, for `EnvError`
+    fn type_name(&self) -> &'static str { ////
+        match self {
+            Value::Point(_) => "Point",
+            Value::Size(_) => "Size",
+            Value::Rect(_) => "Rect",
+            Value::Color(_) => "Color",
+            Value::Float(_) => "Float",
+            Value::UnsignedInt(_) => "UnsignedInt",
+            Value::String(_) => "String",
+        }
+    }
+
     fn is_same_type(&self, other: &Value) -> bool {
         use Value::*;
         match (self, other) {
@@ -294,16 +411,16 @@ impl Data for Value {
 }
 
 impl Data for Env {
-    fn same(&self, _other: &Env) -> bool {
-        true ////TODO
+    fn same(&self, other: &Env) -> bool {
+        self.0.generation == other.0.generation || self.0.same(&other.0) ////  Same generation means same values
         ////Arc::ptr_eq(&self.0, &other.0) || self.0.deref().same(other.0.deref())
     }
 }
 
-/* ////
 impl Data for EnvImpl {
     fn same(&self, other: &EnvImpl) -> bool {
         self.map.len() == other.map.len()
+            && self.l10n.same(&other.l10n) ////
             && self
                 .map
                 .iter()
@@ -311,6 +428,13 @@ impl Data for EnvImpl {
     }
 }
 
+impl Default for Env {
+    fn default() -> Self {
+        Env::empty() ////
+    }
+}
+
+/* ////
 impl Default for Env {
     fn default() -> Self {
         let l10n = L10nManager::new(vec!["builtin.ftl".into()], "./resources/i18n/");
@@ -327,6 +451,7 @@ impl<T> From<Key<T>> for String {
         String::from(src.key)
     }
 }
+*/ ////
 
 /// Use this macro for types which are cheap to clone (ie all `Copy` types).
 macro_rules! impl_value_type_owned {
@@ -335,12 +460,11 @@ macro_rules! impl_value_type_owned {
             type Owned = $ty;
             fn try_from_value(value: &Value) -> Result<Self, EnvError> {
                 match value {
-                    Value::$var(f) => Ok(f.to_owned()),
-                    other => Err(format!(
-                        "incorrect Value type. Expected {}, found {:?}",
-                        stringify!($var),
-                        other
-                    )),
+                    Value::$var(f) => Ok(*f), //// All `Copy` types, no `ToOwned` without `alloc`
+                    other => Err(EnvError { ////
+                        expected: stringify!($var),
+                        found: other.type_name(),
+                    }),
                 }
             }
         }
@@ -353,8 +477,8 @@ macro_rules! impl_value_type_owned {
     };
 }
 
-/// Use this macro for types which require allocation but are not too
-/// expensive to clone.
+/// Use this macro for types which are stored in a fixed-capacity buffer and
+/// are borrowed from the environment.
 macro_rules! impl_value_type_borrowed {
     ($ty:ty, $owned:ty, $var:ident) => {
         impl<'a> ValueType<'a> for &'a $ty {
@@ -362,11 +486,10 @@ macro_rules! impl_value_type_borrowed {
             fn try_from_value(value: &'a Value) -> Result<Self, EnvError> {
                 match value {
                     Value::$var(f) => Ok(f),
-                    other => Err(format!(
-                        "incorrect Value type. Expected {}, found {:?}",
-                        stringify!($var),
-                        other
-                    )),
+                    other => Err(EnvError { ////
+                        expected: stringify!($var),
+                        found: other.type_name(),
+                    }),
                 }
             }
         }
@@ -379,6 +502,7 @@ macro_rules! impl_value_type_borrowed {
     };
 }
 
+/* ////
 /// Use this macro for types that would be expensive to clone; they
 /// are stored as an `Arc<>`.
 macro_rules! impl_value_type_arc {
@@ -410,6 +534,7 @@ macro_rules! impl_value_type_arc {
         }
     };
 }
+*/ ////
 
 impl_value_type_owned!(f64, Float);
 impl_value_type_owned!(u64, UnsignedInt);
@@ -418,5 +543,50 @@ impl_value_type_owned!(Rect, Rect);
 impl_value_type_owned!(Point, Point);
 impl_value_type_owned!(Size, Size);
 impl_value_type_borrowed!(str, String, String);
-impl_value_type_arc!(LinearGradient, LinearGradient);
-*/
\ No newline at end of file
+////impl_value_type_arc!(LinearGradient, LinearGradient);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_diff_and_type_check() {
+        const WIDTH: Key<f64> = Key::new("test.width");
+        const NAME: Key<&str> = Key::new("test.name");
+        let env = Env::default()
+            .adding(WIDTH, 2.0)
+            .adding(NAME, String::from("Font12x16"));
+        assert_eq!(env.get(WIDTH), 2.0);
+        assert_eq!(env.get(NAME), "Font12x16");
+        assert!(env.same(&env.clone()));
+
+        let mut changed = env.clone();
+        changed.set(WIDTH, 3.0);
+        assert!(!env.same(&changed));
+        assert!(changed.same(&changed.clone()));
+        let mut relocalized = changed.clone();
+        relocalized.set_locale("fr-FR");
+        assert!(!changed.same(&relocalized));
+
+        //  Setting the current value keeps the generation, setting the old value back is a value match
+        let generation = changed.generation();
+        changed.set(WIDTH, 3.0);
+        assert_eq!(changed.generation(), generation);
+        changed.set(WIDTH, 2.0);
+        assert_ne!(changed.generation(), env.generation());
+        assert!(env.same(&changed));
+
+        let mut full = env.clone();
+        for key in ["k0", "k1", "k2", "k3", "k4", "k5", "k6", "k7", "k8", "k9", "k10", "k11", "k12", "k13"].iter() {
+            full.try_set(Key::<f64>::new(key), 1.0).unwrap();
+        }
+        assert_eq!(full.try_set(Key::<f64>::new("k14"), 1.0), Err(CapacityError::EnvValues));
+        assert!(full.try_set(Key::<f64>::new("k0"), 2.0).is_ok(), "replacing a value needs no room");
+
+        let wrong: Key<u64> = Key::new("test.width");
+        assert_eq!(
+            u64::try_from_value(changed.0.map.get(wrong.key).unwrap()),
+            Err(EnvError { expected: "UnsignedInt", found: "Float" })
+        );
+    }
+}
@@ -16,10 +16,11 @@
 
 use crate::kurbo::{Rect, Shape, Size, Vec2};
 
+use druid_shell::TimerToken; ////
 ////use druid_shell::{Clipboard, FileInfo, KeyEvent, KeyModifiers, TimerToken};
 
 use crate::mouse::MouseEvent;
-////use crate::Command;
+use crate::Command; ////
 
 /// An event, propagated downwards during event flow.
 ///
@@ -106,13 +107,6 @@ pub enum Event {
     /// See [`has_focus`](struct.BaseState.html#method.has_focus) for
     /// discussion about the focus status.
     FocusChanged(bool),
-    /* ////
-    /// Called at the beginning of a new animation frame.
-    ///
-    /// On the first frame when transitioning from idle to animating, `interval`
-    /// will be 0. (This logic is presently per-window but might change to
-    /// per-widget to make it more consistent). Otherwise it is in nanoseconds.
-    AnimFrame(u64),
     /// Called on a timer event.
     ///
     /// Request a timer event through [`EventCtx::request_timer()`]. That will
@@ -123,7 +117,13 @@ pub enum Event {
     /// precisely.
     ///
     /// [`EventCtx::request_timer()`]: struct.EventCtx.html#method.request_timer
-    ////Timer(TimerToken),
+    Timer(TimerToken),
+    /// Called at the beginning of a new animation frame.
+    ///
+    /// On the first frame when transitioning from idle to animating, `interval`
+    /// will be 0. (This logic is presently per-window but might change to
+    /// per-widget to make it more consistent). Otherwise it is in nanoseconds.
+    AnimFrame(u64),
     /// Called with an arbitrary [`Command`], submitted from elsewhere in
     /// the application.
     ///
@@ -135,7 +135,21 @@ pub enum Event {
     /// [`Widget`]: trait.Widget.html
     /// [`EventCtx::submit_command`]: struct.EventCtx.html#method.submit_command
     Command(Command),
-    */ ////
+    /// Sent to every `Widget` of a screen when a [`Navigator`] shows the screen,
+    /// after it's pushed or when the screen above it is popped.
+    ///
+    /// [`Navigator`]: struct.Navigator.html
+    Shown,
+    /// Sent to every `Widget` of a screen before a [`Navigator`] covers, pops
+    /// or replaces the screen, e.g. to stop timers and animations.
+    ///
+    /// [`Navigator`]: struct.Navigator.html
+    Hidden,
+    /// Called when the user makes the back gesture. If no `Widget` handles the
+    /// event with `set_handled`, the [`Navigator`] pops the screen.
+    ///
+    /// [`Navigator`]: struct.Navigator.html
+    Back,
 }
 
 /* ////
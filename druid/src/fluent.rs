@@ -0,0 +1,316 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+////  Simplified replacement for the resolver in fluent-bundle. Derived from https://github.com/projectfluent/fluent-rs/blob/master/fluent-bundle/src/resolve.rs
+
+//! Formats Fluent message patterns without allocating.
+//!
+//! Supports the subset of [Fluent] syntax that fits a watch:
+//!
+//! - Text with placeables: `Current value is { $count }`
+//! - Variables and string or number literals: `{ $name }`, `{ "{" }`, `{ 42 }`
+//! - Message and term references: `{ app-name }`, `{ -app-name }`
+//! - Select expressions on strings, numbers and the plural categories of the locale:
+//!
+//! ```text
+//! steps = { $count ->
+//!     [0] No steps yet
+//!     [one] One step
+//!    *[other] { $count } steps
+//! }
+//! ```
+//!
+//! [Fluent]: https://projectfluent.org/fluent/guide/
+
+use core::fmt::{self, Write};
+
+use crate::{ArgValue, ArgValues}; ////
+
+/// Max depth of nested message references. Stops reference cycles.
+const MAX_DEPTH: u8 = 4;
+
+/// Errors that stop a message from being formatted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FormatError {
+    /// No message for the key
+    MissingMessage,
+    /// Unbalanced braces, or a select expression without a matching or default variant
+    Syntax,
+    /// Message references are nested too deeply, probably a cycle
+    TooDeep,
+    /// The output could not be written
+    Write,
+}
+
+impl From<fmt::Error> for FormatError {
+    fn from(_: fmt::Error) -> Self {
+        FormatError::Write
+    }
+}
+
+/// Looks up the pattern of a message or term by key
+pub(crate) type Lookup<'a> = &'a dyn Fn(&str) -> Option<&'static str>;
+
+/// Format the message for the key, interpolating the arguments formatted for the locale.
+/// Missing arguments and references are written as `{$name}`, like fluent-rs does.
+pub(crate) fn format_message<W: Write>(
+    lookup: Lookup,
+    locale: &str,
+    key: &str,
+    args: Option<&ArgValues>,
+    out: &mut W,
+) -> Result<(), FormatError> {
+    let pattern = lookup(key).ok_or(FormatError::MissingMessage)?;
+    Scope { lookup, locale, args }.write_pattern(pattern, out, 0)
+}
+
+/// The bundles, locale and arguments for formatting a message
+struct Scope<'a> {
+    lookup: Lookup<'a>,
+    locale: &'a str,
+    args: Option<&'a ArgValues>,
+}
+
+impl<'a> Scope<'a> {
+    /// Write text, resolving each `{ ... }` placeable
+    fn write_pattern<W: Write>(&self, pattern: &str, out: &mut W, depth: u8) -> Result<(), FormatError> {
+        let mut rest = pattern;
+        while let Some(start) = rest.find('{') {
+            out.write_str(&rest[..start])?;
+            let len = placeable_len(&rest[start..]).ok_or(FormatError::Syntax)?;
+            self.write_placeable(rest[start + 1..start + len - 1].trim(), out, depth)?;
+            rest = &rest[start + len..];
+        }
+        if rest.contains('}') {
+            return Err(FormatError::Syntax);
+        }
+        out.write_str(rest)?;
+        Ok(())
+    }
+
+    /// Write a select expression or an inline expression
+    fn write_placeable<W: Write>(&self, expr: &str, out: &mut W, depth: u8) -> Result<(), FormatError> {
+        match find_arrow(expr) {
+            Some(arrow) => {
+                let selector = expr[..arrow].trim();
+                let variant = select_variant(&expr[arrow + 2..], |key| self.matches(selector, key))
+                    .ok_or(FormatError::Syntax)?;
+                self.write_pattern(variant, out, depth)
+            }
+            None => self.write_expression(expr, out, depth),
+        }
+    }
+
+    /// Write a variable, a literal or a message reference
+    fn write_expression<W: Write>(&self, expr: &str, out: &mut W, depth: u8) -> Result<(), FormatError> {
+        if let Some(name) = expr.strip_prefix('$') {
+            match self.arg(name) {
+                Some(value) => value.write_localized(self.locale, out)?,
+                None => write!(out, "{{${}}}", name)?,
+            }
+        } else if let Some(literal) = string_literal(expr) {
+            write_unescaped(literal, out)?;
+        } else if is_number_literal(expr) {
+            out.write_str(expr)?;
+        } else if depth >= MAX_DEPTH {
+            return Err(FormatError::TooDeep);
+        } else {
+            match (self.lookup)(expr) {
+                Some(pattern) => self.write_pattern(pattern, out, depth + 1)?,
+                None => write!(out, "{{{}}}", expr)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// True if the value of the selector matches the variant key
+    fn matches(&self, selector: &str, key: &str) -> bool {
+        if let Some(name) = selector.strip_prefix('$') {
+            self.arg(name).map(|value| value.matches(key, self.locale)).unwrap_or(false)
+        } else if let Some(literal) = string_literal(selector) {
+            literal == key
+        } else if is_number_literal(selector) {
+            selector == key
+        } else {
+            false
+        }
+    }
+
+    fn arg(&self, name: &str) -> Option<&ArgValue> {
+        self.args.and_then(|args| args.get(name))
+    }
+}
+
+/// Length of the placeable at the start of `s`, including the braces
+fn placeable_len(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Position of the `->` of a select expression, outside nested placeables
+fn find_arrow(expr: &str) -> Option<usize> {
+    let bytes = expr.as_bytes();
+    let mut depth = 0;
+    let mut in_string = false;
+    for i in 0..bytes.len() {
+        match bytes[i] {
+            b'"' if i == 0 || bytes[i - 1] != b'\\' => in_string = !in_string,
+            _ if in_string => {}
+            b'{' => depth += 1,
+            b'}' => depth -= 1,
+            b'-' if depth == 0 && bytes.get(i + 1) == Some(&b'>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Pick the pattern of the first variant whose key matches, or the default `*[...]` variant
+fn select_variant(variants: &str, matches: impl Fn(&str) -> bool) -> Option<&str> {
+    let mut rest = variants;
+    let mut default = None;
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return default;
+        }
+        let is_default = rest.starts_with('*');
+        if is_default {
+            rest = &rest[1..];
+        }
+        if !rest.starts_with('[') {
+            return None;
+        }
+        let close = rest.find(']')?;
+        let key = rest[1..close].trim();
+        rest = &rest[close + 1..];
+        let len = variant_len(rest);
+        let pattern = rest[..len].trim();
+        if matches(key) {
+            return Some(pattern);
+        }
+        if is_default {
+            default = Some(pattern);
+        }
+        rest = &rest[len..];
+    }
+}
+
+/// Length of a variant pattern: up to the next line that starts a variant
+fn variant_len(s: &str) -> usize {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '\n' if depth == 0 => {
+                let next = s[i..].trim_start();
+                if next.starts_with('[') || next.starts_with("*[") {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+    }
+    s.len()
+}
+
+/// The contents of a `"..."` string literal
+fn string_literal(expr: &str) -> Option<&str> {
+    if expr.len() >= 2 && expr.starts_with('"') && expr.ends_with('"') {
+        Some(&expr[1..expr.len() - 1])
+    } else {
+        None
+    }
+}
+
+fn is_number_literal(expr: &str) -> bool {
+    let digits = expr.strip_prefix('-').unwrap_or(expr);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+/// Write a string literal, handling the `\"` and `\\` escapes
+fn write_unescaped<W: Write>(literal: &str, out: &mut W) -> fmt::Result {
+    let mut escaped = false;
+    for c in literal.chars() {
+        if c == '\\' && !escaped {
+            escaped = true;
+            continue;
+        }
+        escaped = false;
+        out.write_char(c)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGES: &[(&str, &str)] = &[
+        ("-app-name", "Druid"),
+        ("hello-counter", "Current value is { $count }"),
+        ("about", "About { -app-name }"),
+        ("steps", "{ $count ->\n    [0] No steps\n    [one] One step\n   *[other] { $count } steps\n}"),
+    ];
+
+    fn format(key: &str, args: &ArgValues) -> heapless::String<40> {
+        let lookup = |key: &str| MESSAGES.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+        let mut out = heapless::String::new();
+        format_message(&lookup, "en-US", key, Some(args), &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn format_placeables_and_selects() {
+        let mut args = ArgValues::new();
+        args.insert("count", 5u32.into()).unwrap();
+        assert_eq!(format("hello-counter", &args), "Current value is 5");
+        assert_eq!(format("about", &args), "About Druid");
+        assert_eq!(format("steps", &args), "5 steps");
+        args.insert("count", 1u32.into()).unwrap();
+        assert_eq!(format("steps", &args), "One step");
+        args.insert("count", 0u32.into()).unwrap();
+        assert_eq!(format("steps", &args), "No steps");
+        args.insert("count", ArgValue::Fixed(10, 1)).unwrap();
+        assert_eq!(format("steps", &args), "1.0 steps");
+        args.insert("count", ArgValue::Fixed(12345, 0)).unwrap();
+        assert_eq!(format("hello-counter", &args), "Current value is 12,345");
+        assert_eq!(format("hello-counter", &ArgValues::new()), "Current value is {$count}");
+    }
+}
@@ -0,0 +1,422 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+////  Simplified replacement for intl_pluralrules and the number formatting of fluent-bundle, which need `std`.
+
+//! Plural rules and number, date and time formats for common locales.
+//!
+//! The rules and patterns are a small subset of the [CLDR], compiled into Flash ROM.
+//! Locales are matched by BCP47 tag, e.g. `en-CA`, then by language, e.g. `en`.
+//! Other locales get unlocalized formats and the `other` plural category.
+//!
+//! [CLDR]: http://cldr.unicode.org
+
+use core::fmt::{self, Write};
+
+use crate::argvalue::{Date, Time};
+
+/// CLDR plural categories, for matching numbers in select expressions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluralCategory {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other,
+}
+
+impl PluralCategory {
+    /// Parse a variant key like `one`. Returns `None` if the key is not a plural category.
+    pub fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "zero" => Some(PluralCategory::Zero),
+            "one" => Some(PluralCategory::One),
+            "two" => Some(PluralCategory::Two),
+            "few" => Some(PluralCategory::Few),
+            "many" => Some(PluralCategory::Many),
+            "other" => Some(PluralCategory::Other),
+            _ => None,
+        }
+    }
+
+    /// The cardinal plural category of a number in the locale, e.g. `One` for `1` in `en-US`
+    pub fn for_number(locale: &str, number: &PluralOperands) -> Self {
+        (locale_data(locale).plural)(number)
+    }
+}
+
+/// The CLDR plural operands of the absolute value of a number, as displayed.
+/// `1.50` has `i = 1`, `v = 2` and `f = 50`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PluralOperands {
+    /// Integer digits
+    pub i: u64,
+    /// Number of visible fraction digits, with trailing zeros
+    pub v: u8,
+    /// Visible fraction digits, with trailing zeros
+    pub f: u64,
+}
+
+/// Max number of fraction digits, so that `10^v` fits in a `u64` with room for the integer digits
+pub const MAX_FRACTION_DIGITS: u8 = 9;
+
+impl PluralOperands {
+    /// The operands of an integer
+    pub fn from_integer(n: i64) -> Self {
+        PluralOperands { i: n.unsigned_abs(), v: 0, f: 0 }
+    }
+
+    /// The operands of a fixed-point number `mantissa / 10^scale`, e.g. `12345, 1` for `1234.5`
+    pub fn from_fixed(mantissa: i64, scale: u8) -> Self {
+        let scale = scale.min(MAX_FRACTION_DIGITS);
+        let divisor = 10u64.pow(u32::from(scale));
+        let n = mantissa.unsigned_abs();
+        PluralOperands { i: n / divisor, v: scale, f: n % divisor }
+    }
+
+    /// The operands of a float rounded to `precision` fraction digits
+    pub fn from_float(value: f32, precision: u8) -> Self {
+        let precision = precision.min(MAX_FRACTION_DIGITS);
+        let scale = 10u64.pow(u32::from(precision)) as f64;
+        let magnitude = if value < 0.0 { -f64::from(value) } else { f64::from(value) };
+        //  `as` saturates, so huge and NaN values can't overflow
+        let mantissa = (magnitude * scale + 0.5) as u64;
+        let divisor = scale as u64;
+        PluralOperands { i: mantissa / divisor, v: precision, f: mantissa % divisor }
+    }
+
+    /// Parse a number literal like `-1.5`. Returns the sign and the operands.
+    pub(crate) fn parse(s: &str) -> Option<(bool, Self)> {
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let (int, frac) = match s.find('.') {
+            Some(dot) => (&s[..dot], &s[dot + 1..]),
+            None => (s, ""),
+        };
+        if int.is_empty() || frac.len() > usize::from(MAX_FRACTION_DIGITS) {
+            return None;
+        }
+        let i = int.parse().ok()?;
+        let f = if frac.is_empty() { 0 } else { frac.parse().ok()? };
+        Some((negative, PluralOperands { i, v: frac.len() as u8, f }))
+    }
+
+    /// True if the numbers are equal, ignoring trailing zeros: `1.0` equals `1`
+    pub(crate) fn same_value(&self, other: &Self) -> bool {
+        self.i == other.i && self.trimmed() == other.trimmed()
+    }
+
+    /// Fraction digits without trailing zeros
+    fn trimmed(&self) -> (u8, u64) {
+        let (mut v, mut f) = (self.v, self.f);
+        while v > 0 && f % 10 == 0 {
+            v -= 1;
+            f /= 10;
+        }
+        (v, f)
+    }
+
+    pub(crate) fn is_zero(&self) -> bool {
+        self.i == 0 && self.f == 0
+    }
+}
+
+/// Selects the plural category of a number
+type PluralRule = fn(&PluralOperands) -> PluralCategory;
+
+/// The plural rule and formats of a locale
+pub(crate) struct LocaleData {
+    /// BCP47 tag like `en-CA`, or a language like `en` that matches every region
+    tag: &'static str,
+    decimal: char,
+    /// Group separator, `None` for no grouping
+    group: Option<char>,
+    /// Min number of digits in the highest group, e.g. `2` if 1234 is not grouped
+    min_grouping: u8,
+    plural: PluralRule,
+    /// Numeric date pattern: `y` year, `M`/`MM` month, `d`/`dd` day, `'...'` literal
+    date: &'static str,
+    /// Short time pattern: `H`/`HH`/`h` hour, `mm` minute, `a` AM/PM, `'...'` literal
+    time: &'static str,
+}
+
+/// Unlocalized formats, used by `Display` and for unknown locales
+pub(crate) const ROOT: LocaleData = LocaleData {
+    tag: "und",
+    decimal: '.',
+    group: None,
+    min_grouping: 1,
+    plural: plural_other,
+    date: "y-MM-dd",
+    time: "HH:mm",
+};
+
+//  Group separators that are (narrow) no-break spaces in the CLDR are ASCII spaces,
+//  because the builtin fonts only have ASCII glyphs
+static LOCALES: [LocaleData; 22] = [
+    LocaleData { tag: "en-US", decimal: '.', group: Some(','), min_grouping: 1, plural: plural_one_integer, date: "M/d/y", time: "h:mm a" },
+    LocaleData { tag: "en-CA", decimal: '.', group: Some(','), min_grouping: 1, plural: plural_one_integer, date: "y-MM-dd", time: "h:mm a" },
+    LocaleData { tag: "en-AU", decimal: '.', group: Some(','), min_grouping: 1, plural: plural_one_integer, date: "d/M/y", time: "h:mm a" },
+    LocaleData { tag: "en", decimal: '.', group: Some(','), min_grouping: 1, plural: plural_one_integer, date: "dd/MM/y", time: "HH:mm" },
+    LocaleData { tag: "de-CH", decimal: '.', group: Some('\''), min_grouping: 1, plural: plural_one_integer, date: "d.M.y", time: "HH:mm" },
+    LocaleData { tag: "de", decimal: ',', group: Some('.'), min_grouping: 1, plural: plural_one_integer, date: "d.M.y", time: "HH:mm" },
+    LocaleData { tag: "fr-CA", decimal: ',', group: Some(' '), min_grouping: 1, plural: plural_zero_or_one, date: "y-MM-dd", time: "HH 'h' mm" },
+    LocaleData { tag: "fr", decimal: ',', group: Some(' '), min_grouping: 1, plural: plural_zero_or_one, date: "dd/MM/y", time: "HH:mm" },
+    LocaleData { tag: "es", decimal: ',', group: Some('.'), min_grouping: 2, plural: plural_one, date: "d/M/y", time: "H:mm" },
+    LocaleData { tag: "it", decimal: ',', group: Some('.'), min_grouping: 1, plural: plural_one_integer, date: "d/M/y", time: "HH:mm" },
+    LocaleData { tag: "nl", decimal: ',', group: Some('.'), min_grouping: 1, plural: plural_one_integer, date: "d-M-y", time: "HH:mm" },
+    LocaleData { tag: "pt-PT", decimal: ',', group: Some(' '), min_grouping: 2, plural: plural_one_integer, date: "dd/MM/y", time: "HH:mm" },
+    LocaleData { tag: "pt", decimal: ',', group: Some('.'), min_grouping: 1, plural: plural_zero_or_one, date: "dd/MM/y", time: "HH:mm" },
+    LocaleData { tag: "sv", decimal: ',', group: Some(' '), min_grouping: 1, plural: plural_one_integer, date: "y-MM-dd", time: "HH:mm" },
+    LocaleData { tag: "da", decimal: ',', group: Some('.'), min_grouping: 1, plural: plural_one_integer, date: "d.M.y", time: "HH.mm" },
+    LocaleData { tag: "ru", decimal: ',', group: Some(' '), min_grouping: 1, plural: plural_east_slavic, date: "dd.MM.y", time: "HH:mm" },
+    LocaleData { tag: "uk", decimal: ',', group: Some(' '), min_grouping: 1, plural: plural_east_slavic, date: "dd.MM.y", time: "HH:mm" },
+    LocaleData { tag: "pl", decimal: ',', group: Some(' '), min_grouping: 2, plural: plural_polish, date: "d.MM.y", time: "HH:mm" },
+    LocaleData { tag: "cs", decimal: ',', group: Some(' '), min_grouping: 1, plural: plural_czech, date: "d. M. y", time: "H:mm" },
+    //  24-hour clock, because the day periods of the CLDR are not ASCII
+    LocaleData { tag: "ja", decimal: '.', group: Some(','), min_grouping: 1, plural: plural_other, date: "y/M/d", time: "H:mm" },
+    LocaleData { tag: "ko", decimal: '.', group: Some(','), min_grouping: 1, plural: plural_other, date: "y. M. d.", time: "HH:mm" },
+    LocaleData { tag: "zh", decimal: '.', group: Some(','), min_grouping: 1, plural: plural_other, date: "y/M/d", time: "HH:mm" },
+];
+
+/// The formats for the locale: an exact match like `en-CA`, then a match on the
+/// language like `en`, then the unlocalized formats
+pub(crate) fn locale_data(locale: &str) -> &'static LocaleData {
+    let language = crate::localization::language(locale);
+    LOCALES
+        .iter()
+        .find(|l| l.tag.eq_ignore_ascii_case(locale))
+        .or_else(|| LOCALES.iter().find(|l| l.tag.eq_ignore_ascii_case(language)))
+        .unwrap_or(&ROOT)
+}
+
+/// `one`: 1, but not 1.0. English, German, Italian, Dutch, Swedish, ...
+fn plural_one_integer(n: &PluralOperands) -> PluralCategory {
+    if n.i == 1 && n.v == 0 {
+        PluralCategory::One
+    } else {
+        PluralCategory::Other
+    }
+}
+
+/// `one`: 1 and 1.0. Spanish.
+fn plural_one(n: &PluralOperands) -> PluralCategory {
+    if n.i == 1 && n.f == 0 {
+        PluralCategory::One
+    } else {
+        PluralCategory::Other
+    }
+}
+
+/// `one`: 0 to 1.99. French, Brazilian Portuguese.
+fn plural_zero_or_one(n: &PluralOperands) -> PluralCategory {
+    if n.i <= 1 {
+        PluralCategory::One
+    } else {
+        PluralCategory::Other
+    }
+}
+
+/// `one`: 1, 21, 31, ...; `few`: 2-4, 22-24, ...; `many`: 0, 5-20, 25-30, ...
+/// Russian, Ukrainian.
+fn plural_east_slavic(n: &PluralOperands) -> PluralCategory {
+    let (i10, i100) = (n.i % 10, n.i % 100);
+    if n.v != 0 {
+        PluralCategory::Other
+    } else if i10 == 1 && i100 != 11 {
+        PluralCategory::One
+    } else if (2..=4).contains(&i10) && !(12..=14).contains(&i100) {
+        PluralCategory::Few
+    } else {
+        PluralCategory::Many
+    }
+}
+
+/// `one`: 1; `few`: 2-4, 22-24, ...; `many`: 0, 5-21, 25-31, ... Polish.
+fn plural_polish(n: &PluralOperands) -> PluralCategory {
+    let (i10, i100) = (n.i % 10, n.i % 100);
+    if n.v != 0 {
+        PluralCategory::Other
+    } else if n.i == 1 {
+        PluralCategory::One
+    } else if (2..=4).contains(&i10) && !(12..=14).contains(&i100) {
+        PluralCategory::Few
+    } else {
+        PluralCategory::Many
+    }
+}
+
+/// `one`: 1; `few`: 2-4; `many`: fractions. Czech, Slovak.
+fn plural_czech(n: &PluralOperands) -> PluralCategory {
+    if n.v != 0 {
+        PluralCategory::Many
+    } else if n.i == 1 {
+        PluralCategory::One
+    } else if (2..=4).contains(&n.i) {
+        PluralCategory::Few
+    } else {
+        PluralCategory::Other
+    }
+}
+
+/// No plural forms. Japanese, Korean, Chinese.
+fn plural_other(_: &PluralOperands) -> PluralCategory {
+    PluralCategory::Other
+}
+
+impl LocaleData {
+    /// Write a number with the decimal and group separators of the locale
+    pub(crate) fn write_number<W: Write>(&self, negative: bool, n: &PluralOperands, out: &mut W) -> fmt::Result {
+        if negative && !n.is_zero() {
+            out.write_char('-')?;
+        }
+        //  Digits of the integer part, least significant first
+        let mut digits = [0u8; 20];
+        let mut len = 0;
+        let mut i = n.i;
+        loop {
+            digits[len] = b'0' + (i % 10) as u8;
+            len += 1;
+            i /= 10;
+            if i == 0 {
+                break;
+            }
+        }
+        let group = self.group.filter(|_| len >= 3 + usize::from(self.min_grouping));
+        for pos in (0..len).rev() {
+            out.write_char(char::from(digits[pos]))?;
+            match group {
+                Some(separator) if pos > 0 && pos % 3 == 0 => out.write_char(separator)?,
+                _ => {}
+            }
+        }
+        if n.v > 0 {
+            out.write_char(self.decimal)?;
+            write!(out, "{:01$}", n.f, usize::from(n.v))?;
+        }
+        Ok(())
+    }
+
+    /// Write a date in the numeric format of the locale, e.g. `3/7/2024` in `en-US`
+    pub(crate) fn write_date<W: Write>(&self, date: &Date, out: &mut W) -> fmt::Result {
+        write_pattern(self.date, out, |field, width, out| match field {
+            'y' => write!(out, "{}", date.year),
+            'M' => write!(out, "{:01$}", date.month, width),
+            'd' => write!(out, "{:01$}", date.day, width),
+            _ => Err(fmt::Error),
+        })
+    }
+
+    /// Write a time in the short format of the locale, e.g. `3:05 PM` in `en-US`
+    pub(crate) fn write_time<W: Write>(&self, time: &Time, out: &mut W) -> fmt::Result {
+        write_pattern(self.time, out, |field, width, out| match field {
+            'H' => write!(out, "{:01$}", time.hour, width),
+            'h' => write!(out, "{:01$}", (time.hour + 11) % 12 + 1, width),
+            'm' => write!(out, "{:01$}", time.minute, width),
+            'a' => out.write_str(if time.hour < 12 { "AM" } else { "PM" }),
+            _ => Err(fmt::Error),
+        })
+    }
+}
+
+/// Write a duration in seconds like a stopwatch: `1:02:03`, or `2:03` under an hour
+pub(crate) fn write_duration<W: Write>(seconds: u32, out: &mut W) -> fmt::Result {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        write!(out, "{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        write!(out, "{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Write a date or time pattern. Runs of ASCII letters are fields, written by `field`
+/// with the letter and the length of the run. Text in single quotes is written as is.
+fn write_pattern<W: Write>(
+    pattern: &str,
+    out: &mut W,
+    field: impl Fn(char, usize, &mut W) -> fmt::Result,
+) -> fmt::Result {
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\'' {
+            for c in chars.by_ref().take_while(|c| *c != '\'') {
+                out.write_char(c)?;
+            }
+        } else if c.is_ascii_alphabetic() {
+            let mut width = 1;
+            while chars.peek() == Some(&c) {
+                chars.next();
+                width += 1;
+            }
+            field(c, width, out)?;
+        } else {
+            out.write_char(c)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category(locale: &str, n: i64) -> PluralCategory {
+        PluralCategory::for_number(locale, &PluralOperands::from_integer(n))
+    }
+
+    fn number(locale: &str, mantissa: i64, scale: u8) -> heapless::String<20> {
+        let mut out = heapless::String::new();
+        locale_data(locale)
+            .write_number(mantissa < 0, &PluralOperands::from_fixed(mantissa, scale), &mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn plurals_and_formats() {
+        use PluralCategory::*;
+        assert_eq!(category("en-US", 1), One);
+        assert_eq!(category("en-US", 0), Other);
+        assert_eq!(PluralCategory::for_number("en-US", &PluralOperands::from_fixed(10, 1)), Other);
+        assert_eq!(category("fr-FR", 0), One);
+        assert_eq!(category("ru", 21), One);
+        assert_eq!(category("ru", 3), Few);
+        assert_eq!(category("ru", 11), Many);
+        assert_eq!(category("pl", 22), Few);
+        assert_eq!(category("pl", 25), Many);
+        assert_eq!(category("ja", 1), Other);
+
+        assert_eq!(number("en-US", 12345, 1), "1,234.5");
+        assert_eq!(number("de-DE", 12345, 1), "1.234,5");
+        assert_eq!(number("fr-FR", -1234567, 0), "-1 234 567");
+        assert_eq!(number("es", 1234, 0), "1234");
+        assert_eq!(number("es", 12345, 0), "12.345");
+        assert_eq!(number("xx", 12345, 2), "123.45");
+
+        let date = Date { year: 2024, month: 3, day: 7 };
+        let time = Time { hour: 15, minute: 5 };
+        let mut out = heapless::String::<40>::new();
+        locale_data("en-US").write_date(&date, &mut out).unwrap();
+        out.push(' ').unwrap();
+        locale_data("en-US").write_time(&time, &mut out).unwrap();
+        out.push(' ').unwrap();
+        locale_data("fr-CA").write_time(&time, &mut out).unwrap();
+        out.push(' ').unwrap();
+        locale_data("de").write_date(&date, &mut out).unwrap();
+        assert_eq!(out, "3/7/2024 3:05 PM 15 h 05 7.3.2024");
+    }
+}
@@ -15,26 +15,31 @@
 //! Simple data-oriented GUI.
 
 #![no_std] ////
-#![feature(specialization)] ////
-#![deny(intra_doc_link_resolution_failure)] ////
+#![deny(intra_doc_link_resolution_failure, unsafe_code)]
 ////#![deny(intra_doc_link_resolution_failure, unsafe_code)]
 #![allow(clippy::new_ret_no_self)]
 
 use druid_shell as shell;
 pub use druid_shell::{kurbo, piet};
 
+pub mod animation; ////
 mod app;
+mod arena; ////
 pub mod argvalue; ////
-////mod app_delegate;
-////mod command;
+mod app_delegate; ////
+mod command; ////
 mod data;
 pub mod env; ////
 mod event;
-////mod lens;
+mod fluent; ////
+mod intl; ////
+pub mod lens; ////
 mod localization;
 ////mod menu;
 mod mouse;
-////pub mod theme;
+mod navigator; ////
+pub mod theme; ////
+mod transition; ////
 pub mod widget;
 mod win_handler;
 mod window; ////
@@ -58,26 +63,33 @@ pub use shell::{ ////
     FileSpec, FormatId, HotKey, KeyCode, KeyEvent, KeyModifiers, */
     MouseButton,
     /* RawMods, SysMods, */
-    Text, /* TimerToken, */ WinCtx, WindowHandle,
+    Text, Instant, TimerToken, WinCtx, WindowHandle, ////
 }; ////
 
-pub use app::{AppLauncher, WindowDesc};
-use argvalue::{ArgValue, ArgValues}; ////
-////pub use app_delegate::{AppDelegate, DelegateCtx};
-////pub use command::{sys as commands, Command, Selector};
-pub use data::Data;
+pub use app::{AppLauncher, RunLoop, WindowDesc}; ////
+pub use argvalue::{ArgValue, Date, Time}; ////
+use argvalue::ArgValues; ////
+pub use intl::{PluralCategory, PluralOperands}; ////
+pub use app_delegate::{AppDelegate, DelegateCtx, NoDelegate}; ////
+pub use command::{sys as commands, Command, CommandArg, Selector, MAX_COMMANDS}; ////
+pub use data::{ConstDefault, Data}; ////
 pub use env::{Env, Key, Value}; ////
 pub use event::{Event, /* WheelEvent */}; ////
 ////pub use event::{Event, WheelEvent};
-////pub use lens::{Lens, LensWrap};
-pub use localization::LocalizedString;
+pub use lens::{Lens, LensExt, LensWrap}; ////
+pub use localization::{include_l10n, l10n_keys, Bundle, L10nKey, LocalizedString, MAX_LOCALES, MAX_LOCALIZED_STRING}; ////
 ////pub use menu::{sys as platform_menus, ContextMenu, MenuDesc, MenuItem};
 pub use mouse::MouseEvent;
-pub use win_handler::{AppState, DruidHandler, GlobalWindows, MAX_WINDOWS}; ////
+pub use navigator::{NavError, Navigator, MAX_SCREENS}; ////
+pub use transition::{Edge, Transition, TransitionKind}; ////
+pub use arena::{AppData, ArenaCell, ArenaStorage, Slots, UiArena, WindowSlot, MAX_ENV_CHANGES}; ////
+use arena::EnvChange; ////
+pub use win_handler::{AppState, DruidHandler, MAX_WINDOWS}; ////
 pub use window::{Window, WindowId}; ////
-pub use windowbox::{WindowBox, WindowType}; ////
-pub use crate::widget::{GlobalWidgets, WidgetBox, WidgetId, WidgetType, MAX_WIDGETS}; ////
+pub use windowbox::WindowBox; ////
+pub use crate::widget::{AsWidget, CapacityError, ChildError, WidgetBox, WidgetId, WidgetSet, WidgetType, MAX_WIDGETS}; ////
 pub use shell::{WindowIdType, start_display, draw_to_display, set_display_pixels, show_touch}; ////
+pub use shell::{post_event, post_touch, QueuedEvent}; ////
 pub use crate::shell::platform::window::WindowHandle as PlatformWindowHandle; ////
 pub use crate::shell::platform::window::WindowState as PlatformWindowState; ////
 
@@ -93,12 +105,13 @@ pub use crate::shell::platform::window::WindowState as PlatformWindowState; ////
 /// widget can process a diff between the old value and the new.
 ///
 /// [`update`]: trait.Widget.html#tymethod.update
-#[derive(Clone, Copy, Default)] ////
-pub struct WidgetPod<T: Data + 'static + Default, W: Widget<T>> { ////
+#[derive(Clone, Default)] ////
+pub struct WidgetPod<T: AppData, W: Widget<T>> { ////
 ////pub struct WidgetPod<T: Data, W: Widget<T>> {
     state: BaseState,
     old_data: Option<T>,
-    env: Option<Env>,
+    env_generation: Option<u32>, //// Generation of the last `Env`, instead of a copy of the `Env`
+    ////env: Option<Env>,
     inner: W,
 }
 
@@ -193,7 +206,14 @@ pub struct BaseState {
 /// [`update`]: #tymethod.update
 /// [`Data`]: trait.Data.html
 /// [`WidgetPod`]: struct.WidgetPod.html
-pub trait Widget<T: Data + 'static + Default> { ////
+///
+/// `D` is the Application Data that owns the `Widget`, which is usually also the
+/// data `T` of the `Widget`. They differ when a `Widget` is given one field of the
+/// Application Data by a [`LensWrap`], e.g. a `Label<u32>` that implements
+/// `Widget<u32, State>`. The contexts always belong to the Application Data.
+///
+/// [`LensWrap`]: struct.LensWrap.html
+pub trait Widget<T: Data, D: AppData = T> { ////
 ////pub trait Widget<T> {
     /// Paint the widget appearance.
     ///
@@ -239,7 +259,7 @@ pub trait Widget<T: Data + 'static + Default> { ////
     /// [`Event`]: struct.Event.html
     /// [`EventCtx`]: struct.EventCtx.html
     /// [`Command`]: struct.Command.html
-    fn event(&mut self, ctx: &mut EventCtx<T>, event: &Event, data: &mut T, env: &Env); ////
+    fn event(&mut self, ctx: &mut EventCtx<D>, event: &Event, data: &mut T, env: &Env); ////
     ////fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env);
 
     /// Handle a change of data.
@@ -257,17 +277,17 @@ pub trait Widget<T: Data + 'static + Default> { ////
 
     // Consider a no-op default impl. One reason against is that containers might
     // inadvertently forget to propagate.
-    fn update(&mut self, ctx: &mut UpdateCtx<T>, old_data: Option<&T>, data: &T, env: &Env); ////
+    fn update(&mut self, ctx: &mut UpdateCtx<D>, old_data: Option<&T>, data: &T, env: &Env); ////
     ////fn update(&mut self, ctx: &mut UpdateCtx, old_data: Option<&T>, data: &T, env: &Env);
 
-    /// Wrap this `Widget` in a `WidgetType` enum for boxing by `WidgetBox`
-    fn to_type(self) -> WidgetType<T>; ////
-
-    /// Wrap this `Widget` in a `WindowBox` as the root of the `Window`
-    fn new_window(self) -> WindowBox<T>; ////
-
     /// Return the unique ID for this `Widget`
-    fn get_id(self) -> WidgetId; ////
+    fn get_id(&self) -> WidgetId; ////
+
+    /// Release the Widget ID so that it may be reused, when the `Widget` is removed from the UI.
+    /// Containers must also release their children. The `Widget` must not be used afterwards.
+    fn release(self) where Self: Sized { ////
+        crate::widget::release_widget_id(self.get_id());
+    }
 }
 
 /*
@@ -391,7 +411,7 @@ pub struct LayoutCtx<'a> {
 /// in the widget's appearance, to schedule a repaint.
 ///
 /// [`invalidate`]: #method.invalidate
-pub struct EventCtx<'a, D: Data + 'static + Default> { ////
+pub struct EventCtx<'a, D: AppData> { ////
 ////pub struct EventCtx<'a, 'b> {
     // Note: there's a bunch of state that's just passed down, might
     // want to group that into a single struct.
@@ -415,7 +435,7 @@ pub struct EventCtx<'a, D: Data + 'static + Default> { ////
 /// in the widget's appearance, to schedule a repaint.
 ///
 /// [`invalidate`]: #method.invalidate
-pub struct UpdateCtx<'a, D: Data + 'static + Default> { ////
+pub struct UpdateCtx<'a, D: AppData> { ////
 ////pub struct UpdateCtx<'a, 'b: 'a> {
     text_factory: &'a mut Text,
     window: &'a WindowHandle<DruidHandler<D>>, ////
@@ -426,6 +446,8 @@ pub struct UpdateCtx<'a, D: Data + 'static + Default> { ////
     // now keep it super-simple.
     needs_inval: bool,
     window_id: WindowId,
+    /// True if the `Env` changed since the last update of the enclosing `WidgetPod`
+    env_changed: bool, ////
 }
 
 /// Constraints for layout.
@@ -448,7 +470,7 @@ pub struct BoxConstraints {
     max: Size,
 }
 
-impl<T: Data + 'static + Default, W: Widget<T>> WidgetPod<T, W> { ////
+impl<T: AppData, W: Widget<T>> WidgetPod<T, W> { ////
 ////impl<T: Data, W: Widget<T>> WidgetPod<T, W> {
     /// Create a new widget pod.
     ///
@@ -459,7 +481,8 @@ impl<T: Data + 'static + Default, W: Widget<T>> WidgetPod<T, W> { ////
         WidgetPod {
             state: Default::default(),
             old_data: None,
-            env: None,
+            env_generation: None, ////
+            ////env: None,
             inner,
         }
     }
@@ -474,6 +497,11 @@ impl<T: Data + 'static + Default, W: Widget<T>> WidgetPod<T, W> { ////
         &mut self.inner
     }
 
+    /// Return the inner widget, e.g. to release it when it's removed from a container
+    pub fn into_inner(self) -> W { ////
+        self.inner
+    }
+
     /// Set layout rectangle.
     ///
     /// Intended to be called on child widget in container's `layout`
@@ -529,7 +557,6 @@ impl<T: Data + 'static + Default, W: Widget<T>> WidgetPod<T, W> { ////
         paint_if_not_visible: bool,
     ) {
         if !paint_if_not_visible && !paint_ctx.region().intersects(self.state.layout_rect) {
-            cortex_m::asm::bkpt(); ////
             return;
         }
         //cortex_m::asm::bkpt(); ////
@@ -668,7 +695,6 @@ impl<T: Data + 'static + Default, W: Widget<T>> WidgetPod<T, W> { ////
                 recurse = focus || had_focus;
                 Event::FocusChanged(focus)
             }
-            /*
             Event::AnimFrame(interval) => {
                 recurse = child_ctx.base_state.request_anim;
                 child_ctx.base_state.request_anim = false;
@@ -679,7 +705,9 @@ impl<T: Data + 'static + Default, W: Widget<T>> WidgetPod<T, W> { ////
                 Event::Timer(*id)
             }
             Event::Command(cmd) => Event::Command(cmd.clone()),
-            */
+            Event::Shown => Event::Shown, ////
+            Event::Hidden => Event::Hidden, ////
+            Event::Back => Event::Back, ////
         };
         child_ctx.base_state.needs_inval = false;
         if let Some(is_hot) = hot_changed {
@@ -714,18 +742,24 @@ impl<T: Data + 'static + Default, W: Widget<T>> WidgetPod<T, W> { ////
         } else {
             false
         };
+        let env_same = self.env_generation == Some(env.generation()); ////
+        /* ////
         let env_same = if let Some(ref old_env) = self.env {
             old_env.same(env)
         } else {
             false
         };
+        */ ////
 
         if data_same && env_same {
             return;
         }
+        let env_changed = core::mem::replace(&mut ctx.env_changed, !env_same); ////
         self.inner.update(ctx, self.old_data.as_ref(), data, env);
+        ctx.env_changed = env_changed; ////
         self.old_data = Some(data.clone());
-        self.env = Some(env.clone());
+        self.env_generation = Some(env.generation()); ////
+        ////self.env = Some(env.clone());
     }
 }
 
@@ -894,7 +928,7 @@ impl BoxConstraints {
     }
 }
 
-impl<'a, D: Data + 'static + Default> EventCtx<'a, D> { ////
+impl<'a, D: AppData> EventCtx<'a, D> { ////
 ////impl<'a, 'b> EventCtx<'a, 'b> {
     /// Invalidate.
     ///
@@ -992,7 +1026,6 @@ impl<'a, D: Data + 'static + Default> EventCtx<'a, D> { ////
         self.base_state.request_focus = true;
     }
 
-    /* ////
     /// Request an animation frame.
     pub fn request_anim_frame(&mut self) {
         self.base_state.request_anim = true;
@@ -1001,36 +1034,95 @@ impl<'a, D: Data + 'static + Default> EventCtx<'a, D> { ////
     /// Request a timer event.
     ///
     /// The return value is a token, which can be used to associate the
-    /// request with the event.
+    /// request with the event. The deadline is usually `Instant::now()`
+    /// plus a `core::time::Duration`.
     pub fn request_timer(&mut self, deadline: Instant) -> TimerToken {
         self.base_state.request_timer = true;
         self.win_ctx.request_timer(deadline)
     }
-    */ ////
 
     /// Returns the layout size of the current widget.
     pub fn size(&self) -> Size {
         self.base_state.size()
     }
 
-    /* ////
+    /// Modify the environment of all windows, e.g. to switch to a night mode theme.
+    ///
+    /// The change is seen by [`update()`] after this event is handled, and the
+    /// window is repainted. Fails with `CapacityError::EnvChanges` if [`MAX_ENV_CHANGES`]
+    /// changes are already pending.
+    ///
+    /// [`update()`]: trait.Widget.html#tymethod.update
+    /// [`MAX_ENV_CHANGES`]: constant.MAX_ENV_CHANGES.html
+    pub fn configure_env(&mut self, f: fn(&mut Env)) -> Result<(), CapacityError> { ////
+        D::with_arena(|arena| arena.configure_env(EnvChange::Configure(f)))?;
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Switch the locale of all windows, e.g. when the user picks a language in settings.
+    /// Every [`LocalizedString`] is resolved again by [`update()`] after this event is
+    /// handled, and the window is laid out and repainted. Switching again in the same event
+    /// replaces the pending switch. Fails with `CapacityError::EnvChanges` if [`MAX_ENV_CHANGES`]
+    /// other changes are already pending.
+    ///
+    /// [`LocalizedString`]: struct.LocalizedString.html
+    /// [`update()`]: trait.Widget.html#tymethod.update
+    /// [`MAX_ENV_CHANGES`]: constant.MAX_ENV_CHANGES.html
+    pub fn set_locale(&mut self, locale: &'static str) -> Result<(), CapacityError> { ////
+        D::with_arena(|arena| arena.configure_env(EnvChange::SetLocale(locale)))?;
+        self.invalidate();
+        Ok(())
+    }
+
     /// Submit a [`Command`] to be run after this event is handled.
     ///
     /// Commands are run in the order they are submitted; all commands
     /// submitted during the handling of an event are executed before
-    /// the [`update()`] method is called.
+    /// the [`update()`] method is called. Returns the command if
+    /// [`MAX_COMMANDS`] commands are already queued.
     ///
     /// [`Command`]: struct.Command.html
     /// [`update()`]: trait.Widget.html#tymethod.update
+    /// [`MAX_COMMANDS`]: constant.MAX_COMMANDS.html
     pub fn submit_command(
         &mut self,
         command: impl Into<Command>,
         window_id: impl Into<Option<WindowId>>,
-    ) {
+    ) -> Result<(), Command> { ////
         let window_id = window_id.into().unwrap_or(self.window_id);
-        self.command_queue.push_back((window_id, command.into()))
+        D::with_arena(|arena| arena.push_command(window_id, command.into())) ////
+        ////self.command_queue.push_back((window_id, command.into()))
+    }
+
+    /// Open a window after this event is handled and give it the focus, e.g. a settings
+    /// window. Returns the ID of the new window. Fails with `CapacityError::Windows` if the
+    /// application already has as many windows as its `max_windows` allows, or with
+    /// `CapacityError::Commands` if [`MAX_COMMANDS`] commands are already queued.
+    /// The ID of a window that fails to open is released.
+    ///
+    /// [`MAX_COMMANDS`]: constant.MAX_COMMANDS.html
+    pub fn new_window<W: Widget<D> + Clone>(&mut self, desc: WindowDesc<D, W>) -> Result<WindowId, CapacityError> ////
+    where D::Widgets: From<W> {
+        let id = desc.add_to_arena(&mut AppState::<D>::new())?;
+        if self.submit_command(Command::new(commands::NEW_WINDOW, id), None).is_err() {
+            //  The window will never be opened, so release it now
+            if let Some(window) = D::with_arena(|arena| arena.remove_window(id)) {
+                window.release();
+            }
+            id.release();
+            return Err(CapacityError::Commands);
+        }
+        Ok(id)
+    }
+
+    /// The [`Navigator`] for the screens of this window, e.g. to push a screen when
+    /// a button is tapped. The change is applied after the current event.
+    ///
+    /// [`Navigator`]: struct.Navigator.html
+    pub fn navigator(&self) -> Navigator<D> { ////
+        Navigator::new(self.window_id)
     }
-    */ ////
 
     /// Get the window id.
     pub fn window_id(&self) -> WindowId {
@@ -1050,7 +1142,7 @@ impl<'a, 'b> LayoutCtx<'a> {
     }
 }
 
-impl<'a, D: Data + 'static + Default> UpdateCtx<'a, D> {  ////
+impl<'a, D: AppData> UpdateCtx<'a, D> {  ////
 ////impl<'a, 'b> UpdateCtx<'a, 'b> {
     /// Invalidate.
     ///
@@ -1079,4 +1171,11 @@ impl<'a, D: Data + 'static + Default> UpdateCtx<'a, D> {  ////
     pub fn window_id(&self) -> WindowId {
         self.window_id
     }
+
+    /// True if the `Env` changed since the enclosing `WidgetPod` was last updated, e.g. a
+    /// new theme or locale. A `Widget` that skips updates for unchanged data must still
+    /// pass the update on when the `Env` changed.
+    pub fn env_changed(&self) -> bool { ////
+        self.env_changed
+    }
 }
@@ -14,25 +14,52 @@
 
 //! Localization handling.
 //!
-//! Localization is backed by [Fluent], via [fluent-rs].
+//! Localization is backed by [Fluent] message tables that are compiled into
+//! Flash ROM, formatted by a simplified version of [fluent-rs].
 //!
 //! In Druid, the main way you will deal with localization is via the
 //! [`LocalizedString`] struct.
 //!
 //! You construct a [`LocalizedString`] with a key, which identifies a 'message'
-//! in your `.flt` files. If your string requires arguments, you supply it with
+//! in your `.flt` files. The keys are constants generated by [`l10n_keys!`] from the
+//! default locale, so a key without a message fails to compile. If your string requires arguments, you supply it with
 //! closures that can extract those arguments from the current [`Env`] and
 //! [`Data`].
 //!
 //! At runtime, you resolve your [`LocalizedString`] into an actual string,
 //! passing it the current [`Env`] and [`Data`].
 //!
+//! The messages are looked up in the [`Bundle`]s passed to
+//! `AppLauncher::localization`:
+//!
+//! ```ignore
+//! static BUNDLES: [Bundle; 1] = [Bundle {
+//!     locale: "en-US",
+//!     messages: &[("hello-counter", "Current value is { $count }")],
+//! }];
+//! AppLauncher::with_window(main_window).localization(&BUNDLES)
+//! ```
+//!
+//! The bundles and the keys are usually compiled from the `.ftl` files:
+//!
+//! ```ignore
+//! mod keys {
+//!     druid::l10n_keys!("resources/i18n");
+//! }
+//! AppLauncher::with_window(main_window).localization(druid::include_l10n!("resources/i18n"))
+//! LocalizedString::new(keys::HELLO_COUNTER)
+//! ```
+//!
+//! The first bundle is the default locale. Switch locales at runtime with
+//! `EventCtx::set_locale`, e.g. `ctx.set_locale("fr-FR")`.
 //!
 //! [Fluent]: https://projectfluent.org
 //! [fluent-rs]: https://github.com/projectfluent/fluent-rs
 //! [`LocalizedString`]: struct.LocalizedString.html
 //! [`Env`]: struct.Env.html
 //! [`Data`]: trait.Data.html
+//! [`Bundle`]: struct.Bundle.html
+//! [`l10n_keys!`]: macro.l10n_keys.html
 
 /* ////
     use std::collections::HashMap;
@@ -42,10 +69,14 @@
     use log::{debug, error, warn};
 */ ////
 
+use core::fmt::{self, Write}; ////
 use crate::data::Data;
 use crate::env::Env;
+use crate::fluent::{self, FormatError}; ////
 use crate::{ArgValue, ArgValues}; ////
 
+pub use druid_derive::{include_l10n, l10n_keys}; ////
+
 /* ////
     use fluent_bundle::{
         FluentArgs, FluentBundle, FluentError, FluentMessage, FluentResource, FluentValue,
@@ -81,11 +112,213 @@ use crate::{ArgValue, ArgValues}; ////
 
 */ ////
 
-type MaxLocalizedString = heapless::consts::U20; //// Max length of localized strings
-type String = heapless::String::<MaxLocalizedString>; ////
+/// Localized messages for one locale, compiled into Flash ROM. Replaces the
+/// `.ftl` files that were loaded from disk. ////
+#[derive(Debug)]
+pub struct Bundle {
+    /// BCP47 language tag, e.g. `en-US`
+    pub locale: &'static str,
+    /// Keys and Fluent patterns of the messages. Terms are keyed with a leading `-`.
+    pub messages: &'static [(&'static str, &'static str)],
+}
+
+impl Bundle {
+    /// The pattern of the message or term for the key
+    pub fn get_message(&self, key: &str) -> Option<&'static str> {
+        self.messages.iter().find(|(k, _)| *k == key).map(|(_, v)| *v)
+    }
+}
+
+/// The key of a message, e.g. `hello-counter`. Generated by `l10n_keys!` for every message
+/// of the default locale, so that a `LocalizedString` can't refer to a missing message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct L10nKey(&'static str);
 
-type MaxLocalizedArg = heapless::consts::U2; //// Max number of localized args
-type Vec<T> = heapless::Vec::<T, MaxLocalizedArg>; ///
+impl L10nKey {
+    /// A key that isn't checked against the resources, e.g. for messages in `Bundle`s that
+    /// aren't compiled by `include_l10n!`. Prefer the constants generated by `l10n_keys!`.
+    pub const fn unchecked(key: &'static str) -> Self {
+        L10nKey(key)
+    }
+
+    /// The key as written in the `.ftl` files
+    pub const fn as_str(&self) -> &'static str {
+        self.0
+    }
+}
+
+/// Builtin strings, used when no other `Bundle` has the message. From `OLDresources/i18n/en-US/builtin.ftl`. ////
+const FALLBACK_STRINGS: Bundle = Bundle {
+    locale: "en-US",
+    messages: &[
+        ("-app-name", "Druid"),
+        ("hello-counter", "Current value is { $count }"),
+    ],
+};
+
+/// Max number of locales in a fallback stack
+pub const MAX_LOCALES: usize = 8; ////
+
+/// Locales in order of preference
+type LocaleList = heapless::Vec<&'static str, MAX_LOCALES>; ////
+
+/// Provides access to the localization strings for the current locale.
+#[derive(Clone)] ////
+pub(crate) struct L10nManager {
+    res_mgr: ResourceManager, ////
+    current_bundle: BundleStack,
+    current_locale: &'static str, ////
+}
+
+/// Manages the compiled message tables of all locales. ////
+#[derive(Clone, Copy)]
+struct ResourceManager {
+    bundles: &'static [Bundle],
+    default_locale: &'static str,
+}
+
+/// A stack of localization resources, used for fallback.
+#[derive(Clone)] ////
+struct BundleStack(heapless::Vec<&'static Bundle, MAX_LOCALES>); ////
+
+impl BundleStack {
+    /// The pattern of the message or term for the key, from the first bundle that has it,
+    /// or from the builtin strings
+    fn get_message(&self, id: &str) -> Option<&'static str> {
+        self.0
+            .iter()
+            .copied()
+            .chain(core::iter::once(&FALLBACK_STRINGS)) ////
+            .flat_map(|b| b.get_message(id))
+            .next()
+    }
+}
+
+//NOTE: much of this is adapted from https://github.com/projectfluent/fluent-rs/blob/master/fluent-resmgr/src/resource_manager.rs
+impl ResourceManager {
+    /// Return the localization bundles for the locale, in order of fallback
+    fn get_bundle(&self, locale: &str) -> BundleStack {
+        let mut stack = heapless::Vec::new();
+        for locale in self.resolve_locales(locale) {
+            if let Some(bundle) = self.bundles.iter().find(|b| b.locale == locale) {
+                stack.push(bundle).ok();
+            }
+        }
+        BundleStack(stack)
+    }
+
+    /// Given a locale, returns the best set of available locales: the exact match, then the
+    /// other locales of the same language, then the default locale. Like the `Filtering`
+    /// strategy of fluent-locale, without likely subtags.
+    pub(crate) fn resolve_locales(&self, locale: &str) -> LocaleList {
+        let mut result = LocaleList::new();
+        let available = self.bundles.iter().map(|b| b.locale);
+        for l in available.clone().filter(|l| l.eq_ignore_ascii_case(locale)) {
+            result.push(l).ok();
+        }
+        for l in available.filter(|l| language(l).eq_ignore_ascii_case(language(locale))) {
+            if !result.contains(&l) {
+                result.push(l).ok();
+            }
+        }
+        if !result.contains(&self.default_locale) {
+            result.push(self.default_locale).ok();
+        }
+        result
+    }
+}
+
+/// The language subtag of a BCP47 language tag, e.g. `en` for `en-US`
+pub(crate) fn language(locale: &str) -> &str {
+    locale.split(|c| c == '-' || c == '_').next().unwrap_or(locale)
+}
+
+impl L10nManager {
+    /// A localization manager with only the builtin strings
+    pub(crate) const fn empty() -> Self { ////
+        L10nManager {
+            res_mgr: ResourceManager {
+                bundles: &[],
+                default_locale: FALLBACK_STRINGS.locale,
+            },
+            current_bundle: BundleStack(heapless::Vec::new()),
+            current_locale: FALLBACK_STRINGS.locale,
+        }
+    }
+
+    /// Create a new localization manager. `bundles` is usually created by `include_l10n!`,
+    /// the first bundle is the default locale. The default locale is also the current locale.
+    pub(crate) fn new(bundles: &'static [Bundle]) -> Self { ////
+        let default_locale = bundles.first().map(|b| b.locale).unwrap_or(FALLBACK_STRINGS.locale);
+        let current_locale = default_locale; ////TODO: Application::get_locale()
+        let res_mgr = ResourceManager {
+            bundles,
+            default_locale,
+        };
+        let current_bundle = res_mgr.get_bundle(current_locale);
+        L10nManager {
+            res_mgr,
+            current_bundle,
+            current_locale,
+        }
+    }
+
+    /// Fetch a localized string by key, interpolating the args. Returns `None` if there
+    /// is no message for the key. The string is truncated if it's too long.
+    ///
+    /// In general, this should not be used directly; [`LocalizedString`]
+    /// should be used for localization, and you should call
+    /// [`LocalizedString::resolve`] to update the string as required.
+    ///
+    ///[`LocalizedString`]: struct.LocalizedString.html
+    ///[`LocalizedString::resolve`]: struct.LocalizedString.html#method.resolve
+    pub(crate) fn localize<const N: usize>(&self, key: &str, args: Option<&ArgValues>) -> Option<heapless::String<N>> { ////
+        let mut result = heapless::String::new();
+        let lookup = |key: &str| self.current_bundle.get_message(key);
+        match fluent::format_message(&lookup, self.current_locale, key, args, &mut Truncate(&mut result)) {
+            Err(FormatError::MissingMessage) => None,
+            //  Like fluent-rs, show what was formatted before the error
+            Ok(()) | Err(_) => Some(result),
+        }
+    }
+
+    /// The locale that strings are localized into
+    pub(crate) fn current_locale(&self) -> &'static str { ////
+        self.current_locale
+    }
+
+    /// Localize strings into the locale, falling back to other locales of the same
+    /// language and then to the default locale
+    pub(crate) fn set_locale(&mut self, locale: &'static str) { ////
+        self.current_bundle = self.res_mgr.get_bundle(locale);
+        self.current_locale = locale;
+    }
+
+    /// True if both managers look up the same bundles in the same locale
+    pub(crate) fn same(&self, other: &L10nManager) -> bool { ////
+        core::ptr::eq(self.res_mgr.bundles, other.res_mgr.bundles) && self.current_locale == other.current_locale
+    }
+}
+
+/// Writes as much as fits into the string, and drops the rest
+struct Truncate<'a, const N: usize>(&'a mut heapless::String<N>); ////
+
+impl<'a, const N: usize> Write for Truncate<'a, N> { ////
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if self.0.push(c).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Default max length of localized strings, e.g. `LocalizedString<T, 40>` for longer strings
+pub const MAX_LOCALIZED_STRING: usize = 20; ////
+
+const MAX_LOCALIZED_ARG: usize = 2; //// Max number of localized args
+type Vec<T> = heapless::Vec::<T, MAX_LOCALIZED_ARG>; ///
 
 //////NOTE: instead of a closure, at some point we can use something like a lens for this.
 //////TODO: this is an Arc so that it can be clone, which is a bound on things like `Menu`.
@@ -101,13 +334,15 @@ struct ArgSource<T>(ArgClosure<T>);
 ///
 /// At its simplest, a `LocalizedString` is a key that can be resolved
 /// against a map of localized strings for a given locale.
+/// The localized string holds up to `N` bytes and is truncated if it's longer.
 #[derive(Clone)]////
 ////#[derive(Debug, Clone)]
-pub struct LocalizedString<T> {
+pub struct LocalizedString<T, const N: usize = MAX_LOCALIZED_STRING> { ////
     pub(crate) key: &'static str,
-    placeholder: Option<String>,
+    placeholder: Option<heapless::String<N>>, ////
     args: Option<Vec<(&'static str, ArgSource<T>)>>,
-    resolved: Option<String>,
+    resolved: Option<heapless::String<N>>, ////
+    resolved_lang: Option<&'static str>, ////
     ////resolved_lang: Option<LanguageIdentifier>,
 }
 
@@ -306,15 +541,17 @@ pub struct LocalizedString<T> {
     }
 */ ////
 
-impl<T> LocalizedString<T> {
-    /// Create a new `LocalizedString` with the given key.
-    pub const fn new(key: &'static str) -> Self {
+impl<T, const N: usize> LocalizedString<T, N> { ////
+    /// Create a new `LocalizedString` with the given key, usually generated by `l10n_keys!`.
+    pub const fn new(key: L10nKey) -> Self { ////
+    ////pub const fn new(key: &'static str) -> Self {
         LocalizedString {
-            key,
+            key: key.as_str(), ////
+            ////key,
             args: None,
             placeholder: None,
             resolved: None,
-            ////resolved_lang: None,
+            resolved_lang: None,
         }
     }
 
@@ -331,22 +568,15 @@ impl<T> LocalizedString<T> {
     /// Return the localized value for this string, or the placeholder, if
     /// the localization is missing, or the key if there is no placeholder.
     pub fn localized_str(&self) -> &str {
-        //cortex_m::asm::bkpt(); ////
-        self.resolved ////
+        self.resolved
             .as_ref()
-            .expect("not resolved")
-            .as_str()
-        /* ////
-            self.resolved
-                .as_ref()
-                .map(|s| s.as_str())
-                .or_else(|| self.placeholder.as_ref().map(String::as_ref))
-                .unwrap_or(self.key)
-        */ ////
+            .map(|s| s.as_str())
+            .or_else(|| self.placeholder.as_ref().map(heapless::String::as_str)) ////
+            .unwrap_or(self.key)
     }
 }
 
-impl<T: Data> LocalizedString<T> {
+impl<T: Data, const N: usize> LocalizedString<T, N> { ////
     /// Add a named argument and a corresponding [`ArgClosure`]. This closure
     /// is a function that will return a value for the given key from the current
     /// environment and data.
@@ -374,35 +604,21 @@ impl<T: Data> LocalizedString<T> {
         //TODO: this recomputes the string if either the language has changed,
         //or *anytime* we have arguments. Ideally we would be using a lens
         //to only recompute when our actual data has changed.
+        let current_locale = env.localization_manager().current_locale(); ////
         if self.args.is_some()
+            || self.resolved_lang != Some(current_locale) ////
             ////|| self.resolved_lang.as_ref() != Some(&env.localization_manager().current_locale)
         {
-            ////  Resolve all args
-            let mut args = ArgValues::new();
-            for arg in self.args.as_ref().expect("resolve fail") {
-                let (k, v) = arg;
-                let argvalue = (v.0)(data, env);
-                args.insert(k, argvalue.clone())
-                    .expect("resolve fail");
-                ////  Convert the first arg to text and exit
-                self.resolved = Some(argvalue.to_string());
-                //cortex_m::asm::bkpt(); ////
-                return true;
-            }
-            ////  No args to resolve
-            false
-
-            /* ////
-                let args: Option<FluentArgs> = self
-                    .args
-                    .as_ref()
-                    .map(|a| a.iter().map(|(k, v)| (*k, (v.0)(data, env))).collect());
-                self.resolved_lang = Some(env.localization_manager().current_locale.clone());
-                let next = env.localization_manager().localize(self.key, args.as_ref());
-                let result = next != self.resolved;
-                self.resolved = next;
-                result
-            */ ////
+            let args: Option<ArgValues> = self ////
+                .args
+                .as_ref()
+                .map(|a| a.iter().map(|(k, v)| (*k, (v.0)(data, env))).collect());
+            self.resolved_lang = Some(current_locale); ////
+            ////self.resolved_lang = Some(env.localization_manager().current_locale.clone());
+            let next = env.localization_manager().localize(self.key, args.as_ref());
+            let result = next != self.resolved;
+            self.resolved = next;
+            result
         } else {
             false
         }
@@ -434,49 +650,53 @@ impl<T: Data> LocalizedString<T> {
         }
     }
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
-        #[test]
-        fn resolve() {
-            let en_us: LanguageIdentifier = "en-US".parse().unwrap();
-            let en_ca: LanguageIdentifier = "en-CA".parse().unwrap();
-            let en_gb: LanguageIdentifier = "en-GB".parse().unwrap();
-            let fr_fr: LanguageIdentifier = "fr-FR".parse().unwrap();
-            let pt_pt: LanguageIdentifier = "pt-PT".parse().unwrap();
-
-            let resmgr = ResourceManager {
-                resources: HashMap::new(),
-                locales: vec![en_us.clone(), en_ca.clone(), en_gb.clone(), fr_fr.clone()],
-                default_locale: en_us.clone(),
-                path_scheme: String::new(),
-            };
+*/ ////
 
-            let en_za: LanguageIdentifier = "en-GB".parse().unwrap();
-            let cn_hk: LanguageIdentifier = "cn-HK".parse().unwrap();
-            let fr_ca: LanguageIdentifier = "fr-CA".parse().unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn resolve() {
+        static LOCALES: [Bundle; 4] = [ ////
+            Bundle { locale: "en-US", messages: &[] },
+            Bundle { locale: "en-CA", messages: &[] },
+            Bundle { locale: "en-GB", messages: &[] },
+            Bundle { locale: "fr-FR", messages: &[] },
+        ];
+        let resmgr = ResourceManager { ////
+            bundles: &LOCALES,
+            default_locale: "en-US",
+        };
+
+        assert_eq!(resmgr.resolve_locales("en-CA"), ["en-CA", "en-US", "en-GB"]); ////
+        assert_eq!(resmgr.resolve_locales("en-GB"), ["en-GB", "en-US", "en-CA"]);
+        assert_eq!(resmgr.resolve_locales("fr-CA"), ["fr-FR", "en-US"]);
+        assert_eq!(resmgr.resolve_locales("fr-FR"), ["fr-FR", "en-US"]);
+        assert_eq!(resmgr.resolve_locales("cn-HK"), ["en-US"]);
+        assert_eq!(resmgr.resolve_locales("pt-PT"), ["en-US"]);
+    }
 
-            assert_eq!(
-                resmgr.resolve_locales(en_ca.clone()),
-                vec![en_ca.clone(), en_us.clone(), en_gb.clone()]
-            );
-            assert_eq!(
-                resmgr.resolve_locales(en_za.clone()),
-                vec![en_gb.clone(), en_us.clone(), en_ca.clone()]
-            );
-            assert_eq!(
-                resmgr.resolve_locales(fr_ca.clone()),
-                vec![fr_fr.clone(), en_us.clone()]
-            );
-            assert_eq!(
-                resmgr.resolve_locales(fr_fr.clone()),
-                vec![fr_fr.clone(), en_us.clone()]
-            );
-            assert_eq!(resmgr.resolve_locales(cn_hk), vec![en_us.clone()]);
-            assert_eq!(resmgr.resolve_locales(pt_pt), vec![en_us.clone()]);
-        }
+    #[test]
+    fn switch_locale() {
+        static BUNDLES: [Bundle; 2] = [
+            Bundle { locale: "en-US", messages: &[("greeting", "Hello")] },
+            Bundle { locale: "fr-FR", messages: &[("greeting", "Bonjour")] },
+        ];
+        let mut env = Env::default();
+        env.set_localization(&BUNDLES);
+        let mut greeting = LocalizedString::<u32>::new(L10nKey::unchecked("greeting"));
+        assert!(greeting.resolve(&0, &env));
+        assert_eq!(greeting.localized_str(), "Hello");
+        assert!(!greeting.resolve(&0, &env));
+
+        let old_env = env.clone();
+        env.set_locale("fr-CA");
+        assert!(!old_env.same(&env));
+        assert_eq!(env.locale(), "fr-CA");
+        assert!(greeting.resolve(&0, &env));
+        assert_eq!(greeting.localized_str(), "Bonjour");
     }
-*/ ////
+}
 
 /// Implement formatted output for ArgSource
 impl<T> core::fmt::Debug for ArgSource<T> { ////
@@ -0,0 +1,169 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A stack of screens for single-display devices. Each window shows one screen at a
+//! time; pushing a screen hides the current screen until the pushed screen is popped.
+//! Screens change immediately, or with an animated `Transition`.
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use crate::{AppData, AppState, CapacityError, Transition, Widget, Window, WindowBox, WindowId};
+
+/// Max number of screens that may be hidden under the current screens of all windows
+pub const MAX_SCREENS: usize = 4;
+
+/// A change of screen requested through a `Navigator`, applied after the current event
+pub(crate) enum Navigation<D: AppData> {
+    /// Hide the current screen and show the new screen
+    Push(WindowBox<D>, Transition),
+    /// Release the current screen and show the screen under it. Reverses the
+    /// `Transition` that pushed the current screen if `None`.
+    Pop(Option<Transition>),
+    /// Release the current screen and show the new screen
+    Replace(WindowBox<D>, Transition),
+}
+
+/// The `Navigator` couldn't change the screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NavError {
+    /// `MAX_SCREENS` screens are already hidden, or too many changes are queued
+    Full,
+    /// There is no screen under the current screen to go back to
+    Empty,
+    /// The arena is full
+    Capacity(CapacityError),
+}
+
+impl From<CapacityError> for NavError {
+    fn from(err: CapacityError) -> Self {
+        NavError::Capacity(err)
+    }
+}
+
+impl fmt::Display for NavError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NavError::Full => write!(f, "too many screens, increase `MAX_SCREENS`"),
+            NavError::Empty => write!(f, "no screen to go back to"),
+            NavError::Capacity(err) => err.fmt(f),
+        }
+    }
+}
+
+/// Pushes and pops the screens shown by a window, e.g. `ctx.navigator().push(settings())`.
+///
+/// Only the screen on top receives events and paints. Screens under it keep their
+/// Widgets and state until they are shown again. A screen is sent `Event::Hidden`
+/// before it's covered, popped or replaced, and `Event::Shown` when it's shown.
+///
+/// Changes are queued and applied after the current event, so a `Widget` may push
+/// a screen while it handles a tap. The back gesture (`State::handle_back`) sends
+/// `Event::Back` to the current screen, and pops the screen if no `Widget` handles it.
+///
+/// `push_with`, `pop_with` and `replace_with` animate the change with a `Transition`.
+/// Input to the window is blocked until the `Transition` completes.
+pub struct Navigator<D: AppData> {
+    window_id: WindowId,
+    phantom: PhantomData<D>,
+}
+
+impl<D: AppData> Navigator<D> {
+    /// The `Navigator` for the screens of the window
+    pub fn new(window_id: WindowId) -> Self {
+        Navigator {
+            window_id,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Show the screen rooted at the `Widget`, hiding the current screen.
+    /// The `Widget` is released on error.
+    pub fn push<W: Widget<D> + Clone>(&self, screen: W) -> Result<(), NavError>
+    where D::Widgets: From<W> {
+        self.push_with(screen, Transition::NONE)
+    }
+
+    /// Show the screen rooted at the `Widget` with the `Transition`, hiding the current screen.
+    /// The screen is popped with the reversed `Transition`. The `Widget` is released on error.
+    pub fn push_with<W: Widget<D> + Clone>(&self, screen: W, transition: Transition) -> Result<(), NavError>
+    where D::Widgets: From<W> {
+        if !D::with_arena(|arena| arena.can_push_screen()) {
+            screen.release();
+            return Err(NavError::Full);
+        }
+        let screen = WindowBox::new(Window::try_new(screen)?);
+        self.navigate(Navigation::Push(screen, transition))
+    }
+
+    /// Release the current screen and show the screen under it, reversing the
+    /// `Transition` that pushed the current screen
+    pub fn pop(&self) -> Result<(), NavError> {
+        self.pop_transition(None)
+    }
+
+    /// Release the current screen and show the screen under it with the `Transition`
+    pub fn pop_with(&self, transition: Transition) -> Result<(), NavError> {
+        self.pop_transition(Some(transition))
+    }
+
+    fn pop_transition(&self, transition: Option<Transition>) -> Result<(), NavError> {
+        if self.depth() == 0 {
+            return Err(NavError::Empty);
+        }
+        if !D::with_arena(|arena| arena.can_navigate()) {
+            return Err(NavError::Full);
+        }
+        self.navigate(Navigation::Pop(transition))
+    }
+
+    /// Release the current screen and show the screen rooted at the `Widget` instead,
+    /// e.g. to move from a setup screen to the main screen. The `Widget` is released on error.
+    pub fn replace<W: Widget<D> + Clone>(&self, screen: W) -> Result<(), NavError>
+    where D::Widgets: From<W> {
+        self.replace_with(screen, Transition::NONE)
+    }
+
+    /// Release the current screen and show the screen rooted at the `Widget` with the
+    /// `Transition` instead. The `Widget` is released on error.
+    pub fn replace_with<W: Widget<D> + Clone>(&self, screen: W, transition: Transition) -> Result<(), NavError>
+    where D::Widgets: From<W> {
+        if !D::with_arena(|arena| arena.can_navigate()) {
+            screen.release();
+            return Err(NavError::Full);
+        }
+        let screen = WindowBox::new(Window::try_new(screen)?);
+        self.navigate(Navigation::Replace(screen, transition))
+    }
+
+    /// Number of screens under the current screen, including the queued changes
+    pub fn depth(&self) -> usize {
+        D::with_arena(|arena| arena.screen_depth(self.window_id))
+    }
+
+    /// Queue the change, and repaint the window in case it's requested outside an event.
+    /// The checks above avoid creating a screen that can't be queued, but the queue may
+    /// have filled up since, so the arena checks again.
+    fn navigate(&self, navigation: Navigation<D>) -> Result<(), NavError> {
+        if let Err((err, navigation)) = D::with_arena(|arena| arena.push_navigation(self.window_id, navigation)) {
+            //  Released outside the arena, because releasing takes the Widgets out of the arena
+            if let Navigation::Push(screen, _) | Navigation::Replace(screen, _) = navigation {
+                screen.release();
+            }
+            return Err(err);
+        }
+        AppState::<D>::new().get_handle(self.window_id).invalidate();
+        Ok(())
+    }
+}
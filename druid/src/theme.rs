@@ -0,0 +1,161 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Theme keys and initial values.
+//!
+//! Themes are `const` tables so that they live in Flash ROM. Pick or override a theme
+//! at launch with `AppLauncher::configure_env`, or switch themes at runtime with
+//! `EventCtx::configure_env`:
+//!
+//! ```ignore
+//! AppLauncher::with_window(main_window)
+//!     .configure_env(|env| theme::LIGHT.apply(env))
+//! ```
+
+use crate::piet::Color;
+
+use crate::{Env, Key};
+
+pub const WINDOW_BACKGROUND_COLOR: Key<Color> = Key::new("window_background_color");
+
+pub const LABEL_COLOR: Key<Color> = Key::new("label_color");
+
+pub const PRIMARY_LIGHT: Key<Color> = Key::new("primary_light");
+pub const PRIMARY_DARK: Key<Color> = Key::new("primary_dark");
+/* ////
+pub const BACKGROUND_LIGHT: Key<Color> = Key::new("background_light");
+pub const BACKGROUND_DARK: Key<Color> = Key::new("background_dark");
+pub const FOREGROUND_LIGHT: Key<Color> = Key::new("foreground_light");
+pub const FOREGROUND_DARK: Key<Color> = Key::new("foreground_dark");
+*/ ////
+pub const BUTTON_DARK: Key<Color> = Key::new("button_dark");
+pub const BUTTON_LIGHT: Key<Color> = Key::new("button_light");
+pub const BORDER: Key<Color> = Key::new("border");
+pub const BORDER_LIGHT: Key<Color> = Key::new("border_light");
+////pub const SELECTION_COLOR: Key<Color> = Key::new("selection_color");
+////pub const CURSOR_COLOR: Key<Color> = Key::new("cursor_color");
+
+pub const FONT_NAME: Key<&str> = Key::new("font_name");
+pub const TEXT_SIZE_NORMAL: Key<f64> = Key::new("text_size_normal");
+pub const BASIC_WIDGET_HEIGHT: Key<f64> = Key::new("basic_widget_height");
+pub const BORDERED_WIDGET_HEIGHT: Key<f64> = Key::new("bordered_widget_height");
+
+/* //// No scroll bars yet
+pub const SCROLL_BAR_COLOR: Key<Color> = Key::new("scroll_bar_color");
+pub const SCROLL_BAR_BORDER_COLOR: Key<Color> = Key::new("scroll_bar_border_color");
+pub const SCROLL_BAR_MAX_OPACITY: Key<f64> = Key::new("scroll_bar_max_opacity");
+pub const SCROLL_BAR_FADE_DELAY: Key<u64> = Key::new("scroll_bar_fade_time");
+pub const SCROLL_BAR_WIDTH: Key<f64> = Key::new("scroll_bar_width");
+pub const SCROLL_BAR_PAD: Key<f64> = Key::new("scroll_bar_pad");
+pub const SCROLL_BAR_RADIUS: Key<f64> = Key::new("scroll_bar_radius");
+pub const SCROLL_BAR_EDGE_WIDTH: Key<f64> = Key::new("scroll_bar_edge_width");
+*/ ////
+
+/// The values of the theme keys. `Env` values can't be built at compile time,
+/// so a theme is a `const` table that is applied to an `Env`.
+#[derive(Debug, Clone, Copy)] ////
+pub struct Theme {
+    pub window_background_color: Color,
+    pub label_color: Color,
+    pub primary_light: Color,
+    pub primary_dark: Color,
+    pub button_dark: Color,
+    pub button_light: Color,
+    pub border: Color,
+    pub border_light: Color,
+    /// Must be a font known to `piet-embedded`
+    pub font_name: &'static str,
+    pub text_size_normal: f64,
+    pub basic_widget_height: f64,
+    pub bordered_widget_height: f64,
+}
+
+/// Light text on a black background. Black pixels are off, so this saves power on OLED screens.
+pub const DARK: Theme = Theme {
+    window_background_color: Color::BLACK,
+    label_color: Color::WHITE,
+    primary_light: Color::rgb8(0x5c, 0xc4, 0xff),
+    primary_dark: Color::rgb8(0x00, 0x8d, 0xdd),
+    button_dark: Color::BLACK,
+    button_light: Color::rgb8(0x21, 0x21, 0x21),
+    border: Color::WHITE,
+    border_light: Color::rgb8(0xa1, 0xa1, 0xa1),
+    font_name: crate::env::FONT_NAME,
+    text_size_normal: crate::env::FONT_HEIGHT as f64,
+    basic_widget_height: 18.0,
+    bordered_widget_height: 24.0,
+};
+
+/// Dark text on a white background, for reading in daylight.
+pub const LIGHT: Theme = Theme {
+    window_background_color: Color::WHITE,
+    label_color: Color::BLACK,
+    primary_light: Color::rgb8(0x5c, 0xc4, 0xff),
+    primary_dark: Color::rgb8(0x00, 0x8d, 0xdd),
+    button_dark: Color::rgb8(0xde, 0xde, 0xde),
+    button_light: Color::WHITE,
+    border: Color::BLACK,
+    border_light: Color::rgb8(0x5e, 0x5e, 0x5e),
+    font_name: crate::env::FONT_NAME,
+    text_size_normal: crate::env::FONT_HEIGHT as f64,
+    basic_widget_height: 18.0,
+    bordered_widget_height: 24.0,
+};
+
+impl Theme {
+    /// Set the theme keys in the environment, replacing the values of the previous theme.
+    /// Other keys in the environment are kept.
+    pub fn apply(&self, env: &mut Env) {
+        env.set(WINDOW_BACKGROUND_COLOR, self.window_background_color);
+        env.set(LABEL_COLOR, self.label_color);
+        env.set(PRIMARY_LIGHT, self.primary_light);
+        env.set(PRIMARY_DARK, self.primary_dark);
+        env.set(BUTTON_DARK, self.button_dark);
+        env.set(BUTTON_LIGHT, self.button_light);
+        env.set(BORDER, self.border);
+        env.set(BORDER_LIGHT, self.border_light);
+        env.set(FONT_NAME, self.font_name);
+        env.set(TEXT_SIZE_NORMAL, self.text_size_normal);
+        env.set(BASIC_WIDGET_HEIGHT, self.basic_widget_height);
+        env.set(BORDERED_WIDGET_HEIGHT, self.bordered_widget_height);
+    }
+
+    /// An environment with only the theme keys
+    pub fn env(&self) -> Env {
+        let mut env = Env::default();
+        self.apply(&mut env);
+        env
+    }
+}
+
+/// An initial theme.
+pub fn init() -> Env {
+    DARK.env() ////
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Data;
+
+    #[test]
+    fn switching_theme_changes_env() {
+        let dark = init();
+        let mut light = dark.clone();
+        LIGHT.apply(&mut light);
+        assert!(!dark.same(&light));
+        assert_eq!(light.get(LABEL_COLOR).as_rgba_u32(), Color::BLACK.as_rgba_u32());
+        assert_eq!(light.get(FONT_NAME), dark.get(FONT_NAME));
+    }
+}
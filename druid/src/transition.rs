@@ -0,0 +1,260 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Animated changes of screen. While a `Navigator` changes the screen with a `Transition`,
+//! the old and new screens are painted at offsets on each animation frame, and input to
+//! the window is blocked until the transition completes.
+
+use core::time::Duration;
+use crate::animation::{Easing, Tween};
+use crate::kurbo::{Size, Vec2};
+use crate::{AppData, Env, PaintCtx, WindowBox};
+
+/// The edge of the display that a screen enters from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Edge {
+    /// The edge on the other side of the display
+    pub fn opposite(self) -> Self {
+        match self {
+            Edge::Left => Edge::Right,
+            Edge::Right => Edge::Left,
+            Edge::Top => Edge::Bottom,
+            Edge::Bottom => Edge::Top,
+        }
+    }
+
+    /// Offset that moves a screen of the size just outside the display at the edge
+    fn offset(self, size: Size) -> Vec2 {
+        match self {
+            Edge::Left => Vec2::new(-size.width, 0.0),
+            Edge::Right => Vec2::new(size.width, 0.0),
+            Edge::Top => Vec2::new(0.0, -size.height),
+            Edge::Bottom => Vec2::new(0.0, size.height),
+        }
+    }
+}
+
+/// How the new screen replaces the old screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionKind {
+    /// The new screen slides in from the edge, over the old screen
+    Slide(Edge),
+    /// The new screen slides in from the edge and pushes the old screen out at the opposite edge
+    Push(Edge),
+    /// The new screen fades in as the old screen fades out. The display has no alpha
+    /// blending, so the old screen is shown for the first half and the new screen for the second half.
+    CrossFade,
+}
+
+/// An animated change of screen, e.g. `navigator.push_with(settings(), Transition::slide(Edge::Right))`.
+///
+/// A screen that is pushed with a `Transition` is popped with the reversed `Transition`,
+/// so a screen that slid in from the right slides out to the right.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transition {
+    kind: TransitionKind,
+    duration: Duration,
+    easing: Easing,
+    /// True if the old screen leaves the way a new screen enters, e.g. when popping
+    reversed: bool,
+}
+
+impl Transition {
+    /// Show the new screen immediately
+    pub const NONE: Transition = Transition::new(TransitionKind::CrossFade, Duration::from_millis(0), Easing::Linear);
+
+    /// Duration of the transitions created by `slide`, `push` and `cross_fade`
+    pub const DEFAULT_DURATION: Duration = Duration::from_millis(250);
+
+    /// A transition of the kind that runs for the duration, following the `Easing` curve
+    pub const fn new(kind: TransitionKind, duration: Duration, easing: Easing) -> Self {
+        Transition { kind, duration, easing, reversed: false }
+    }
+
+    /// Slide the new screen in from the edge, over the old screen
+    pub const fn slide(edge: Edge) -> Self {
+        Transition::new(TransitionKind::Slide(edge), Transition::DEFAULT_DURATION, Easing::EaseOut)
+    }
+
+    /// Slide the new screen in from the edge, pushing the old screen out
+    pub const fn push(edge: Edge) -> Self {
+        Transition::new(TransitionKind::Push(edge), Transition::DEFAULT_DURATION, Easing::EaseInOut)
+    }
+
+    /// Fade from the old screen to the new screen
+    pub const fn cross_fade() -> Self {
+        Transition::new(TransitionKind::CrossFade, Transition::DEFAULT_DURATION, Easing::Linear)
+    }
+
+    /// The same transition over another duration
+    pub const fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// The same transition following another `Easing` curve
+    pub const fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// The transition that undoes this transition: the screen that came in goes out the same way
+    pub const fn reversed(mut self) -> Self {
+        self.reversed = !self.reversed;
+        self
+    }
+
+    /// How the new screen replaces the old screen
+    pub fn kind(&self) -> TransitionKind {
+        self.kind
+    }
+
+    /// How long the transition runs
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// True if the new screen is shown immediately
+    pub fn is_none(&self) -> bool {
+        self.duration.as_nanos() == 0
+    }
+
+    /// The screens to paint at `progress` (0.0 to 1.0) of the transition, bottom first,
+    /// with the offset of each screen for a display of the size
+    fn layers(&self, progress: f64, size: Size) -> [Option<(Layer, Vec2)>; 2] {
+        let eased = self.easing.apply(progress);
+        //  A reversed transition runs backwards with the screens swapped
+        let (under, over, shown) = if self.reversed {
+            (Layer::New, Layer::Old, 1.0 - eased)
+        } else {
+            (Layer::Old, Layer::New, eased)
+        };
+        match self.kind {
+            TransitionKind::Slide(edge) => [
+                Some((under, Vec2::ZERO)),
+                Some((over, edge.offset(size) * (1.0 - shown))),
+            ],
+            TransitionKind::Push(edge) => [
+                Some((under, edge.opposite().offset(size) * shown)),
+                Some((over, edge.offset(size) * (1.0 - shown))),
+            ],
+            TransitionKind::CrossFade => [
+                None,
+                Some((if shown < 0.5 { under } else { over }, Vec2::ZERO)),
+            ],
+        }
+    }
+}
+
+/// The screen painted by a layer of a transition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layer {
+    Old,
+    New,
+}
+
+/// A running transition of a window. The new screen is the current screen of the window.
+pub(crate) struct ScreenTransition<D: AppData> {
+    transition: Transition,
+    /// Fraction of the transition that has elapsed
+    progress: Tween<f64>,
+    /// The old screen, painted until the transition completes
+    old: WindowBox<D>,
+    /// The transition that pushed the new screen, if the old screen is hidden under the
+    /// new screen when the transition completes. `None` if the old screen is released.
+    hide: Option<Transition>,
+}
+
+impl<D: AppData> ScreenTransition<D> {
+    /// Start the transition from the old screen. If `hide` is set, the old screen is hidden
+    /// under the new screen when the transition completes, and `hide` is used to pop the new screen.
+    pub(crate) fn new(old: WindowBox<D>, transition: Transition, hide: Option<Transition>) -> Self {
+        ScreenTransition {
+            transition,
+            progress: Tween::new(0.0, 1.0, transition.duration, Easing::Linear),
+            old,
+            hide,
+        }
+    }
+
+    /// Advance by the interval of an `Event::AnimFrame`, in nanoseconds. Returns true when done.
+    pub(crate) fn step(&mut self, interval: u64) -> bool {
+        self.progress.step(interval);
+        self.progress.is_done()
+    }
+
+    /// True if the old screen will be hidden instead of released
+    pub(crate) fn hides_old(&self) -> bool {
+        self.hide.is_some()
+    }
+
+    /// End the transition. Returns the old screen, and the transition to pop the new screen
+    /// if the old screen is to be hidden.
+    pub(crate) fn finish(self) -> (WindowBox<D>, Option<Transition>) {
+        (self.old, self.hide)
+    }
+
+    /// Paint the old screen and the new screen at their offsets
+    pub(crate) fn paint(&mut self, paint_ctx: &mut PaintCtx, new: &mut WindowBox<D>, data: &D, env: &Env) {
+        let size = new.size();
+        for (layer, offset) in self.transition.layers(self.progress.value(), size).iter().flatten() {
+            match layer {
+                Layer::Old => self.old.paint_at(paint_ctx, *offset, data, env),
+                Layer::New => new.paint_at(paint_ctx, *offset, data, env),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: Size = Size::new(240.0, 240.0);
+
+    #[test]
+    fn push_moves_both_screens() {
+        let push = Transition::push(Edge::Right).with_easing(Easing::Linear);
+        assert_eq!(push.layers(0.25, SIZE), [
+            Some((Layer::Old, Vec2::new(-60.0, 0.0))),
+            Some((Layer::New, Vec2::new(180.0, 0.0))),
+        ]);
+        //  Popping slides the new screen back out to the right
+        assert_eq!(push.reversed().layers(0.25, SIZE), [
+            Some((Layer::New, Vec2::new(-180.0, 0.0))),
+            Some((Layer::Old, Vec2::new(60.0, 0.0))),
+        ]);
+        assert_eq!(push.layers(1.0, SIZE)[1], Some((Layer::New, Vec2::ZERO)));
+    }
+
+    #[test]
+    fn slide_and_cross_fade() {
+        let slide = Transition::slide(Edge::Bottom).with_easing(Easing::Linear);
+        assert_eq!(slide.layers(0.5, SIZE), [
+            Some((Layer::Old, Vec2::ZERO)),
+            Some((Layer::New, Vec2::new(0.0, 120.0))),
+        ]);
+        let fade = Transition::cross_fade();
+        assert_eq!(fade.layers(0.4, SIZE)[1], Some((Layer::Old, Vec2::ZERO)));
+        assert_eq!(fade.layers(0.6, SIZE)[1], Some((Layer::New, Vec2::ZERO)));
+        assert!(Transition::NONE.is_none());
+    }
+}
@@ -15,16 +15,16 @@
 //! A widget that aligns its child (for example, centering it).
 
 use crate::{
-    BaseState, BoxConstraints, Data, Env, Event, EventCtx, LayoutCtx, PaintCtx, Rect, Size,
+    BaseState, BoxConstraints, AppData, Env, Event, EventCtx, LayoutCtx, PaintCtx, Rect, Size,
     UpdateCtx, Widget, WidgetPod,
-    WidgetId, WidgetType, WidgetBox, Window, WindowType, WindowBox, ////
+    WidgetId, WidgetBox, widget::CapacityError, ////
 };
 
 use crate::piet::UnitPoint;
 
 /// A widget that aligns its child.
 #[derive(Clone)] ////
-pub struct Align<T: Data + 'static + Default> { ////
+pub struct Align<T: AppData> { ////
 ////pub struct Align<T: Data> {
     id: WidgetId, //// Unique Widget ID
     align: UnitPoint,
@@ -34,14 +34,15 @@ pub struct Align<T: Data + 'static + Default> { ////
     height_factor: Option<f64>,
 }
 
-impl<T: Data + 'static + Default> Align<T> { ////
+impl<T: AppData> Align<T> { ////
 ////impl<T: Data> Align<T> {
     /// Create widget with alignment.
     ///
     /// Note that the `align` parameter is specified as a `UnitPoint` in
     /// terms of left and right. This is inadequate for bidi-aware layout
     /// and thus the API will change when druid gains bidi capability.
-    pub fn new<W: Widget<T> + Clone>(align: UnitPoint, child: W) -> Align<T> { ////
+    pub fn new<W: Widget<T> + Clone>(align: UnitPoint, child: W) -> Align<T>
+    where T::Widgets: From<W> { ////
     ////pub fn new(align: UnitPoint, child: impl Widget<T> + 'static) -> Align<T> {
         Align {
             id: super::get_widget_id(), ////
@@ -55,26 +56,52 @@ impl<T: Data + 'static + Default> Align<T> { ////
         }
     }
 
+    /// Create widget with alignment, or return an error if there is no space for the widget
+    /// or its child. The child is released on error.
+    pub fn try_new<W: Widget<T> + Clone>(align: UnitPoint, child: W) -> Result<Align<T>, CapacityError> ////
+    where T::Widgets: From<W> {
+        let id = match super::try_widget_id() {
+            Ok(id) => id,
+            Err(err) => {
+                child.release();
+                return Err(err);
+            }
+        };
+        let child = WidgetBox::<T>::try_new(child)
+            .inspect_err(|_| super::release_widget_id(id))?;
+        Ok(Align {
+            id,
+            align,
+            child: WidgetPod::new(child),
+            width_factor: None,
+            height_factor: None,
+        })
+    }
+
     /// Create centered widget.
-    pub fn centered<W: Widget<T> + Clone>(child: W) -> Align<T> { ////
+    pub fn centered<W: Widget<T> + Clone>(child: W) -> Align<T>
+    where T::Widgets: From<W> { ////
     ////pub fn centered(child: impl Widget<T> + 'static) -> Align<T> {
         Align::new(UnitPoint::CENTER, child)
     }
 
     /// Create right-aligned widget.
-    pub fn right<W: Widget<T> + Clone>(child: W) -> Align<T> { ////
+    pub fn right<W: Widget<T> + Clone>(child: W) -> Align<T>
+    where T::Widgets: From<W> { ////
     ////pub fn right(child: impl Widget<T> + 'static) -> Align<T> {
         Align::new(UnitPoint::RIGHT, child)
     }
 
     /// Create left-aligned widget.
-    pub fn left<W: Widget<T> + Clone>(child: W) -> Align<T> { ////
+    pub fn left<W: Widget<T> + Clone>(child: W) -> Align<T>
+    where T::Widgets: From<W> { ////
     ////pub fn left(child: impl Widget<T> + 'static) -> Align<T> {
         Align::new(UnitPoint::LEFT, child)
     }
 
     /// Align only in the horizontal axis, keeping the child's size in the vertical.
-    pub fn horizontal<W: Widget<T> + Clone>(align: UnitPoint, child: W) -> Align<T> { ////
+    pub fn horizontal<W: Widget<T> + Clone>(align: UnitPoint, child: W) -> Align<T>
+    where T::Widgets: From<W> { ////
     ////pub fn horizontal(align: UnitPoint, child: impl Widget<T> + 'static) -> Align<T> {
         Align {
             id: super::get_widget_id(), ////
@@ -89,7 +116,8 @@ impl<T: Data + 'static + Default> Align<T> { ////
     }
 
     /// Align only in the vertical axis, keeping the child's size in the horizontal.
-    pub fn vertical<W: Widget<T> + Clone>(align: UnitPoint, child: W) -> Align<T> { ////
+    pub fn vertical<W: Widget<T> + Clone>(align: UnitPoint, child: W) -> Align<T>
+    where T::Widgets: From<W> { ////
     ////pub fn vertical(align: UnitPoint, child: impl Widget<T> + 'static) -> Align<T> {
         Align {
             id: super::get_widget_id(), ////
@@ -104,7 +132,7 @@ impl<T: Data + 'static + Default> Align<T> { ////
     }
 }
 
-impl<T: Data + 'static + Default> Widget<T> for Align<T> {
+impl<T: AppData> Widget<T> for Align<T> {
     fn paint(&mut self, paint_ctx: &mut PaintCtx, _base_state: &BaseState, data: &T, env: &Env) {
         self.child.paint_with_offset(paint_ctx, data, env);
     }
@@ -155,19 +183,12 @@ impl<T: Data + 'static + Default> Widget<T> for Align<T> {
         self.child.update(ctx, data, env);
     }
 
-    fn to_type(self) -> WidgetType<T> { ////
-        WidgetType::Align(self)
-    }
-
-    fn new_window(self) -> WindowBox<T> { ////
-        let window = Window::new(self);
-        let window_box = WindowBox(
-            WindowType::Align(window),
-        );
-        window_box
+    fn get_id(&self) -> WidgetId { ////
+        self.id
     }
 
-    fn get_id(self) -> WidgetId { ////
-        self.id
+    fn release(self) { ////
+        self.child.into_inner().release();
+        super::release_widget_id(self.id);
     }
 }
@@ -15,7 +15,7 @@
 //! A button widget.
 
 use crate::{
-    BaseState, BoxConstraints, Data, Env, Event, EventCtx, LayoutCtx, PaintCtx, Size, UpdateCtx,
+    BaseState, BoxConstraints, AppData, Env, Event, EventCtx, LayoutCtx, PaintCtx, Size, UpdateCtx,
     WidgetId, Widget,
 };
 
@@ -23,13 +23,13 @@ use crate::kurbo::RoundedRect;
 use crate::piet::{UnitPoint}; ////
 ////use crate::piet::{LinearGradient, UnitPoint};
 
-////use crate::theme;
-use crate::widget::{/* Align, */ Label, LabelText, /* SizedBox, */ WidgetType}; ////
-use crate::{Point, RenderContext, WindowBox}; ////
+use crate::theme; ////
+use crate::widget::{/* Align, */ CapacityError, Label, LabelText, /* SizedBox, */ }; ////
+use crate::{Point, RenderContext}; ////
 
 /// A button with a text label.
 #[derive(Clone)] ////
-pub struct Button<T: Data + 'static + Default> { ////
+pub struct Button<T: AppData> { ////
 ////pub struct Button<T> {
     id: WidgetId, //// Unique Widget ID
     label: Label<T>,
@@ -38,7 +38,7 @@ pub struct Button<T: Data + 'static + Default> { ////
     ////action: Box<dyn Fn(&mut EventCtx, &mut T, &Env)>,
 }
 
-impl<T: Data + 'static + Default> Button<T> { ////
+impl<T: AppData> Button<T> { ////
 ////impl<T: Data + 'static> Button<T> {
     /// Create a new button. The closure provided will be called when the button
     /// is clicked.
@@ -55,6 +55,22 @@ impl<T: Data + 'static + Default> Button<T> { ////
         }
     }
 
+    /// Create a new button, or return an error if the text is too long for the `Label`
+    /// or all Widget IDs are in use
+    pub fn try_new( ////
+        text: &str,
+        action: fn(&mut EventCtx<T>, &mut T, &Env),
+    ) -> Result<Button<T>, CapacityError> {
+        let label = Label::try_aligned(text, UnitPoint::CENTER)?;
+        match super::try_widget_id() {
+            Ok(id) => Ok(Button { id, label, action }),
+            Err(err) => {
+                <Label<T> as Widget<T>>::release(label);
+                Err(err)
+            }
+        }
+    }
+
     /* ////
         /// Create a new button with a fixed size.
         pub fn sized(
@@ -88,15 +104,15 @@ impl<T: Data + 'static + Default> Button<T> { ////
 }
 
 
-impl<T: Data + 'static + Default> Widget<T> for Button<T> { ////
+impl<T: AppData> Widget<T> for Button<T> { ////
 ////impl<T: Data> Widget<T> for Button<T> {
     fn paint(&mut self, paint_ctx: &mut PaintCtx, base_state: &BaseState, data: &T, env: &Env) {
         ////let is_active = base_state.is_active();
-        ////let is_hot = base_state.is_hot();
+        let is_hot = base_state.is_hot();
 
         let rounded_rect =
             RoundedRect::from_origin_size(Point::ORIGIN, base_state.size().to_vec2(), 4.);
-        //let bg_color = crate::env::BUTTON_DARK; ////
+        //let bg_color = env.get(theme::BUTTON_DARK); ////
         /* ////
             let bg_gradient = if is_active {
                 LinearGradient::new(
@@ -113,14 +129,11 @@ impl<T: Data + 'static + Default> Widget<T> for Button<T> { ////
             };
         */ ////
 
-        let border_color = crate::env::BORDER; ////
-        /* ////
-            let border_color = if is_hot {
-                env.get(theme::BORDER_LIGHT)
-            } else {
-                env.get(theme::BORDER)
-            };
-        */ ////
+        let border_color = if is_hot {
+            env.get(theme::BORDER_LIGHT)
+        } else {
+            env.get(theme::BORDER)
+        };
 
         // TODO paint_ctx.render_ctx.fill(rounded_rect, &bg_color); ////
         paint_ctx.render_ctx.stroke(rounded_rect, &border_color, 2.0); ////
@@ -129,7 +142,8 @@ impl<T: Data + 'static + Default> Widget<T> for Button<T> { ////
         ////paint_ctx.render_ctx.fill(rounded_rect, &bg_gradient); ////
         ////paint_ctx.fill(rounded_rect, &bg_gradient);
 
-        self.label.paint(paint_ctx, base_state, data, env);
+        <Label<T> as Widget<T>>::paint(&mut self.label, paint_ctx, base_state, data, env); ////
+        ////self.label.paint(paint_ctx, base_state, data, env);
     }
 
     fn layout(
@@ -141,7 +155,8 @@ impl<T: Data + 'static + Default> Widget<T> for Button<T> { ////
     ) -> Size {
         bc.debug_check("Button");
 
-        self.label.layout(layout_ctx, bc, data, env)
+        <Label<T> as Widget<T>>::layout(&mut self.label, layout_ctx, bc, data, env) ////
+        ////self.label.layout(layout_ctx, bc, data, env)
     }
 
     fn event(&mut self, ctx: &mut EventCtx<T>, event: &Event, data: &mut T, env: &Env) { ////
@@ -173,15 +188,12 @@ impl<T: Data + 'static + Default> Widget<T> for Button<T> { ////
         self.label.update(ctx, old_data, data, env)
     }
 
-    fn to_type(self) -> WidgetType<T> { ////
-        WidgetType::Button(self)
-    }
-
-    fn new_window(self) -> WindowBox<T> { ////
-        WindowBox::new()
+    fn get_id(&self) -> WidgetId { ////
+        self.id
     }
 
-    fn get_id(self) -> WidgetId { ////
-        self.id
+    fn release(self) { ////
+        <Label<T> as Widget<T>>::release(self.label);
+        super::release_widget_id(self.id);
     }
 }
@@ -17,9 +17,9 @@
 use crate::kurbo::{Point, Rect, Size}; ////
 
 use crate::{
-    BaseState, BoxConstraints, Data, Env, Event, EventCtx, LayoutCtx, PaintCtx, UpdateCtx, Widget,
+    BaseState, BoxConstraints, AppData, Env, Event, EventCtx, LayoutCtx, PaintCtx, UpdateCtx, Widget,
     WidgetPod,
-    Window, WindowType, WindowBox, widget::{WidgetBox, WidgetId, WidgetType}, ////
+    widget::{CapacityError, WidgetBox, WidgetId}, ////
 };
 
 /// A builder for a row widget that can contain flex children.
@@ -37,21 +37,54 @@ pub struct Row;
 /// [`Flex`]: struct.Flex.html
 pub struct Column;
 
-type MaxWidgets = heapless::consts::U8; //// Max widgets per container
-type Vec<T> = heapless::Vec::<T, MaxWidgets>;
+/// Default max number of children per container, e.g. `Flex<T, 16>` for more children
+pub const MAX_CHILDREN: usize = 8; ////
+type Vec<T, const N: usize> = heapless::Vec::<T, N>; ////
 
-/// A container with either horizontal or vertical layout.
+/// A container with either horizontal or vertical layout, with up to `N` children.
+///
+/// The builtin `WidgetType` only stores `Flex<T, MAX_CHILDREN>`. For another capacity, name it
+/// in a `widget_set!`, e.g. `WideFlex = Flex<State, 16>`, and pick that set with
+/// `#[druid(app_state, widgets = "MyWidgets")]`.
+///
+/// The children of a boxed `Flex` are changed through `WidgetBox::with_mut`, e.g.
+/// `list.with_mut(|flex: &mut Flex<State>| flex.remove_child(0))`.
 #[derive(Clone)] ////
-pub struct Flex<T: Data + 'static + Default> { ////
+pub struct Flex<T: AppData, const N: usize = MAX_CHILDREN> { ////
 ////pub struct Flex<T: Data> {
     id: WidgetId, //// Unique Widget ID
     direction: Axis,
-    children: Vec<ChildWidget<T>>, ////
+    children: Vec<ChildWidget<T>, N>, ////
     ////children: Vec<ChildWidget<T>>,
+    children_changed: bool, //// True if children were added or removed since the last `event` or `update`
+}
+
+/// A child of a `Flex` couldn't be inserted, removed, replaced or moved
+#[derive(Clone, Copy, Debug, PartialEq, Eq)] ////
+pub enum ChildError { ////
+    /// There is no child at the index
+    OutOfRange,
+    /// The container or the arena is full
+    Capacity(CapacityError),
+}
+
+impl From<CapacityError> for ChildError { ////
+    fn from(err: CapacityError) -> Self {
+        ChildError::Capacity(err)
+    }
+}
+
+impl core::fmt::Display for ChildError { ////
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ChildError::OutOfRange => write!(f, "no child at index"),
+            ChildError::Capacity(err) => err.fmt(f),
+        }
+    }
 }
 
 #[derive(Clone)] ////
-struct ChildWidget<T: Data + 'static + Default> { ////
+struct ChildWidget<T: AppData> { ////
 ////struct ChildWidget<T: Data> {
     widget: WidgetPod<T, WidgetBox<T>>, ////
     ////widget: WidgetPod<T, Box<dyn Widget<T>>>,
@@ -96,13 +129,14 @@ impl Row {
     /// Create a new row widget.
     ///
     /// The child widgets are laid out horizontally, from left to right.
-    pub fn new<T: Data + 'static + Default>() -> Flex<T> { ////
+    pub fn new<T: AppData>() -> Flex<T> { ////
     ////pub fn new<T: Data>() -> Flex<T> {
         Flex {
             id: super::get_widget_id(), ////
             direction: Axis::Horizontal,
 
             children: Vec::new(),
+            children_changed: false, ////
         }
     }
 }
@@ -111,19 +145,62 @@ impl Column {
     /// Create a new row widget.
     ///
     /// The child widgets are laid out vertically, from top to bottom.
-    pub fn new<T: Data + 'static + Default>() -> Flex<T> { ////
+    pub fn new<T: AppData>() -> Flex<T> { ////
     ////pub fn new<T: Data>() -> Flex<T> {
         Flex {
             id: super::get_widget_id(), ////
             direction: Axis::Vertical,
 
             children: Vec::new(),
+            children_changed: false, ////
         }
     }
 }
 
-impl<T: Data + 'static + Default> Flex<T> { ////
+impl<T: AppData, const N: usize> Flex<T, N> { ////
 ////impl<T: Data> Flex<T> {
+    /// Create a new row widget, e.g. `Flex::<State, 16>::row()` for up to 16 children.
+    /// `Row::new()` creates a row with the default number of children.
+    pub fn row() -> Self { ////
+        Flex {
+            id: super::get_widget_id(),
+            direction: Axis::Horizontal,
+            children: Vec::new(),
+            children_changed: false,
+        }
+    }
+
+    /// Create a new row widget, or return an error if all Widget IDs are in use
+    pub fn try_row() -> Result<Self, CapacityError> { ////
+        Ok(Flex {
+            id: super::try_widget_id()?,
+            direction: Axis::Horizontal,
+            children: Vec::new(),
+            children_changed: false,
+        })
+    }
+
+    /// Create a new column widget, or return an error if all Widget IDs are in use
+    pub fn try_column() -> Result<Self, CapacityError> { ////
+        Ok(Flex {
+            id: super::try_widget_id()?,
+            direction: Axis::Vertical,
+            children: Vec::new(),
+            children_changed: false,
+        })
+    }
+
+    /// Create a new column widget, e.g. `Flex::<State, 16>::column()` for up to 16 children.
+    /// `Column::new()` creates a column with the default number of children.
+    pub fn column() -> Self { ////
+        Flex {
+            id: super::get_widget_id(),
+            direction: Axis::Vertical,
+            children: Vec::new(),
+            children_changed: false,
+        }
+    }
+
     /// Add a child widget.
     ///
     /// If `flex` is zero, then the child is non-flex. It is given the same
@@ -133,7 +210,8 @@ impl<T: Data + 'static + Default> Flex<T> { ////
     /// If `flex` is non-zero, then all the space left over after layout of
     /// the non-flex children is divided up, in proportion to the `flex` value,
     /// among the flex children.
-    pub fn add_child<W: Widget<T> + Clone>(&mut self, child: W, flex: f64) { ////
+    pub fn add_child<W: Widget<T> + Clone>(&mut self, child: W, flex: f64)
+    where T::Widgets: From<W> { ////
     ////pub fn add_child(&mut self, child: impl Widget<T> + 'static, flex: f64) {
         let params = Params { flex };
         let child = ChildWidget {
@@ -144,12 +222,101 @@ impl<T: Data + 'static + Default> Flex<T> { ////
             params,
         };
         self.children.push(child)
-            .expect("add child fail"); ////
+            .expect("too many children, use a larger `Flex<T, N>`"); ////
         ////self.children.push(child);
+        self.children_changed = true; ////
+    }
+
+    /// Add a child widget, or return an error if the container or the arena is full.
+    /// The child is released on error.
+    pub fn try_add_child<W: Widget<T> + Clone>(&mut self, child: W, flex: f64) -> Result<(), CapacityError> ////
+    where T::Widgets: From<W> {
+        if self.children.is_full() {
+            child.release();
+            return Err(CapacityError::Children);
+        }
+        let widget = WidgetPod::new(WidgetBox::<T>::try_new(child)?);
+        self.children.push(ChildWidget { widget, params: Params { flex } }).ok();  //  Can't fail, checked above
+        self.children_changed = true;
+        Ok(())
+    }
+
+    /// Number of child widgets
+    pub fn child_count(&self) -> usize { ////
+        self.children.len()
+    }
+
+    /// Insert a child widget at the index, shifting the later children. The child is
+    /// released on error.
+    ///
+    /// The `Flex` is laid out again at its next `event` or `update`, so change the
+    /// children before passing the `event` or `update` to the `Flex`.
+    pub fn insert_child<W: Widget<T> + Clone>(&mut self, index: usize, child: W, flex: f64) -> Result<(), ChildError> ////
+    where T::Widgets: From<W> {
+        if index > self.children.len() {
+            child.release();
+            return Err(ChildError::OutOfRange);
+        }
+        if self.children.is_full() {
+            child.release();
+            return Err(CapacityError::Children.into());
+        }
+        let widget = WidgetPod::new(WidgetBox::<T>::try_new(child)?);
+        self.children.insert(index, ChildWidget { widget, params: Params { flex } }).ok();  //  Can't fail, checked above
+        self.children_changed = true;
+        Ok(())
+    }
+
+    /// Remove the child widget at the index, releasing its Widget ID and arena slot
+    pub fn remove_child(&mut self, index: usize) -> Result<(), ChildError> { ////
+        if index >= self.children.len() {
+            return Err(ChildError::OutOfRange);
+        }
+        self.children.remove(index).widget.into_inner().release();
+        self.children_changed = true;
+        Ok(())
+    }
+
+    /// Replace the child widget at the index and release the old child. On error the
+    /// new child is released and the old child is kept.
+    pub fn replace_child<W: Widget<T> + Clone>(&mut self, index: usize, child: W, flex: f64) -> Result<(), ChildError> ////
+    where T::Widgets: From<W> {
+        if index >= self.children.len() {
+            child.release();
+            return Err(ChildError::OutOfRange);
+        }
+        let widget = WidgetPod::new(WidgetBox::<T>::try_new(child)?);
+        let old = core::mem::replace(&mut self.children[index], ChildWidget { widget, params: Params { flex } });
+        old.widget.into_inner().release();
+        self.children_changed = true;
+        Ok(())
+    }
+
+    /// Move the child widget at index `from` to index `to`, shifting the children in between
+    pub fn move_child(&mut self, from: usize, to: usize) -> Result<(), ChildError> { ////
+        let len = self.children.len();
+        if from >= len || to >= len {
+            return Err(ChildError::OutOfRange);
+        }
+        if from < to {
+            self.children[from..=to].rotate_left(1);
+        } else {
+            self.children[to..=from].rotate_right(1);
+        }
+        self.children_changed = true;
+        Ok(())
+    }
+
+    /// Remove all child widgets, releasing their Widget IDs and arena slots
+    pub fn clear(&mut self) { ////
+        while let Some(child) = self.children.pop() {
+            child.widget.into_inner().release();
+        }
+        self.children_changed = true;
     }
 }
 
-impl<T: Data + 'static + Default> Widget<T> for Flex<T> { ////
+impl<T: AppData, const N: usize> Widget<T> for Flex<T, N> { ////
 ////impl<T: Data> Widget<T> for Flex<T> {
     fn paint(&mut self, paint_ctx: &mut PaintCtx, _base_state: &BaseState, data: &T, env: &Env) {
         for child in &mut self.children {
@@ -246,6 +413,11 @@ impl<T: Data + 'static + Default> Widget<T> for Flex<T> { ////
 
     fn event(&mut self, ctx: &mut EventCtx<T>, event: &Event, data: &mut T, env: &Env) { ////
     ////fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        if self.children_changed { ////
+            //  Layout runs before every paint, so invalidating is enough to relayout
+            self.children_changed = false;
+            ctx.invalidate();
+        }
         for child in &mut self.children {
             child.widget.event(ctx, event, data, env);
         }
@@ -254,33 +426,107 @@ impl<T: Data + 'static + Default> Widget<T> for Flex<T> { ////
     fn update(&mut self, ctx: &mut UpdateCtx<T>, _old_data: Option<&T>, data: &T, env: &Env) { ////
     ////fn update(&mut self, ctx: &mut UpdateCtx, _old_data: Option<&T>, data: &T, env: &Env) {
         //cortex_m::asm::bkpt(); ////
+        if self.children_changed { ////
+            //  Layout runs before every paint, so invalidating is enough to relayout
+            self.children_changed = false;
+            ctx.invalidate();
+        }
         for child in &mut self.children {
             child.widget.update(ctx, data, env);
         }
     }
 
-    fn to_type(self) -> WidgetType<T> { ////
-        WidgetType::Flex(self)
-    }
-
-    fn new_window(self) -> WindowBox<T> { ////
-        let window = Window::new(self);
-        let window_box = WindowBox(
-            WindowType::Flex(window),
-            //PhantomData,
-        );
-        window_box
+    fn get_id(&self) -> WidgetId { ////
+        self.id
     }
 
-    fn get_id(self) -> WidgetId { ////
-        self.id
+    fn release(self) { ////
+        for child in self.children {
+            child.widget.into_inner().release();
+        }
+        super::release_widget_id(self.id);
     }
 }
 
 /// Implement formatted output for ChildWidget
-impl<T: Data + Default> core::fmt::Debug for ChildWidget<T> { ////
+impl<T: AppData> core::fmt::Debug for ChildWidget<T> { ////
     fn fmt(&self, _fmt: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
         //  TODO
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widget::{Label, WidgetType, MAX_WIDGET_IDS};
+    use crate::{ArenaCell, ConstDefault, Data, WindowSlot};
+
+    #[derive(Clone, Default)]
+    struct State;
+
+    impl Data for State {
+        fn same(&self, _other: &Self) -> bool {
+            true
+        }
+    }
+
+    impl ConstDefault for State {
+        const DEFAULT: Self = State;
+    }
+
+    impl AppData for State {
+        type Widgets = WidgetType<State>;
+        type Storage = ArenaCell<State>;
+        //  Widget IDs are shared by all tests, so the arena has a slot for every ID
+        type WidgetSlots = [WidgetType<State>; MAX_WIDGET_IDS];
+        type WindowSlots = [WindowSlot<State>; 1];
+        type Delegate = crate::NoDelegate;
+        fn storage() -> &'static Self::Storage {
+            static ARENA: ArenaCell<State> = ArenaCell::new(State);
+            &ARENA
+        }
+    }
+
+    fn label(text: &'static str) -> (Label<State>, WidgetId) {
+        let label = Label::new(text);
+        let id = Widget::<State>::get_id(&label);
+        (label, id)
+    }
+
+    #[test]
+    fn insert_and_remove_children() {
+        let mut flex = Flex::<State, 2>::column();
+        let (b, b_id) = label("b");
+        let (a, a_id) = label("a");
+        flex.insert_child(0, b, 0.0).unwrap();
+        flex.insert_child(0, a, 0.0).unwrap();
+        assert_eq!(flex.child_count(), 2);
+
+        //  Rejected children are released
+        let (far, far_id) = label("far");
+        assert_eq!(flex.insert_child(3, far, 0.0), Err(ChildError::OutOfRange));
+        assert!(!far_id.is_live());
+        let (extra, extra_id) = label("extra");
+        assert_eq!(flex.insert_child(1, extra, 0.0), Err(ChildError::Capacity(CapacityError::Children)));
+        assert!(!extra_id.is_live());
+
+        assert_eq!(flex.remove_child(2), Err(ChildError::OutOfRange));
+        flex.remove_child(0).unwrap();
+        assert!(!a_id.is_live());
+        assert!(b_id.is_live());
+        assert_eq!(flex.child_count(), 1);
+        flex.release();
+    }
+
+    #[test]
+    fn change_boxed_flex() {
+        let list = WidgetBox::<State>::new(Flex::<State>::column());
+        let (a, a_id) = label("a");
+        assert_eq!(list.with_mut(|flex: &mut Flex<State>| flex.insert_child(0, a, 1.0)), Some(Ok(())));
+        assert_eq!(list.with_mut(|flex: &mut Flex<State>| flex.child_count()), Some(1));
+        assert_eq!(list.with_mut(|_: &mut Label<State>| ()), None);
+        list.release();
+        assert!(!a_id.is_live());
+    }
+}
@@ -18,9 +18,9 @@
 
 use core::str::FromStr; ////
 use crate::{
-    BaseState, BoxConstraints, Data, Env, Event, EventCtx, LayoutCtx, PaintCtx, Size, UpdateCtx,
+    BaseState, BoxConstraints, AppData, Data, Env, Event, EventCtx, LayoutCtx, PaintCtx, Size, UpdateCtx,
     Widget, 
-    WidgetId, WidgetType, WindowBox, ////
+    WidgetId, widget::CapacityError, ////
 };
 
 use crate::kurbo::Rect;
@@ -29,24 +29,29 @@ use crate::piet::{
 };
 
 use crate::localization::LocalizedString;
-////use crate::theme;
+use crate::theme; ////
 use crate::{Point, RenderContext};
 
-type MaxLabel = heapless::consts::U20; //// Max length of label strings
-type String = heapless::String::<MaxLabel>; ////
+/// Default max length of label strings, e.g. `Label<T, 40>` for longer labels
+pub const MAX_LABEL: usize = 20; ////
+type String<const N: usize> = heapless::String::<N>; ////
 
 /// The text for the label; either a localized or a specific string.
 #[derive(Clone)] ////
-pub enum LabelText<T> {
-    Localized(LocalizedString<T>),
-    Specific(String),
+pub enum LabelText<T, const N: usize = MAX_LABEL> { ////
+    Localized(LocalizedString<T, N>), ////
+    Specific(String<N>), ////
 }
 
-/// A label that displays some text.
+/// A label that displays some text, up to `N` bytes long.
+///
+/// The builtin `WidgetType` only stores `Label<T, MAX_LABEL>`. For another length, name it
+/// in a `widget_set!`, e.g. `LongLabel = Label<State, 40>`, and pick that set with
+/// `#[druid(app_state, widgets = "MyWidgets")]`.
 #[derive(Clone)] ////
-pub struct Label<T> {
+pub struct Label<T, const N: usize = MAX_LABEL> { ////
     id: WidgetId, //// Unique Widget ID
-    text: LabelText<T>,
+    text: LabelText<T, N>, ////
     align: UnitPoint,
 }
 
@@ -61,11 +66,10 @@ pub struct DynLabel<T: Data> {
 }
 */ ////
 
-impl<T: Data + 'static + Default> Label<T> { ////
-////impl<T: Data> Label<T> {
+impl<T: Data, const N: usize> Label<T, N> { ////
     /// Discussion question: should this return Label or a wrapped
     /// widget (with WidgetPod)?
-    pub fn new(text: impl Into<LabelText<T>>) -> Self {
+    pub fn new(text: impl Into<LabelText<T, N>>) -> Self { ////
         Label {
             id: super::get_widget_id(), ////
             text: text.into(),
@@ -73,7 +77,7 @@ impl<T: Data + 'static + Default> Label<T> { ////
         }
     }
 
-    pub fn aligned(text: impl Into<LabelText<T>>, align: UnitPoint) -> Self {
+    pub fn aligned(text: impl Into<LabelText<T, N>>, align: UnitPoint) -> Self { ////
         Label {
             id: super::get_widget_id(), ////
             text: text.into(),
@@ -81,9 +85,26 @@ impl<T: Data + 'static + Default> Label<T> { ////
         }
     }
 
-    fn get_layout(&self, t: &mut PietText, _env: &Env) -> PietTextLayout {
-        let font_name = crate::env::FONT_NAME; ////env.get(theme::FONT_NAME);
-        let font_size = crate::env::TEXT_SIZE_NORMAL; ////env.get(theme::TEXT_SIZE_NORMAL);
+    /// Create a label, or return an error if the text is longer than `N` bytes
+    /// or all Widget IDs are in use
+    pub fn try_new(text: &str) -> Result<Self, CapacityError> { ////
+        Self::try_aligned(text, UnitPoint::LEFT)
+    }
+
+    /// Create an aligned label, or return an error if the text is longer than `N` bytes
+    /// or all Widget IDs are in use
+    pub fn try_aligned(text: &str, align: UnitPoint) -> Result<Self, CapacityError> { ////
+        let text = LabelText::try_from_str(text)?;
+        Ok(Label {
+            id: super::try_widget_id()?,
+            text,
+            align,
+        })
+    }
+
+    fn get_layout(&self, t: &mut PietText, env: &Env) -> PietTextLayout {
+        let font_name = env.get(theme::FONT_NAME);
+        let font_size = env.get(theme::TEXT_SIZE_NORMAL);
         let text = self.text.display_text();
         // TODO: caching of both the format and the layout
         let font = t.new_font_by_name(font_name, font_size).build().expect("get layout fail"); //// .unwrap();
@@ -91,10 +112,10 @@ impl<T: Data + 'static + Default> Label<T> { ////
     }
 }
 
-impl<T: Data + 'static + Default> Widget<T> for Label<T> { ////
+impl<T: Data, D: AppData, const N: usize> Widget<T, D> for Label<T, N> { ////  `T` may be a field of the Application Data `D`
 ////impl<T: Data> Widget<T> for Label<T> {
     fn paint(&mut self, paint_ctx: &mut PaintCtx, base_state: &BaseState, _data: &T, env: &Env) {
-        let font_size = crate::env::TEXT_SIZE_NORMAL; ////env.get(theme::TEXT_SIZE_NORMAL);
+        let font_size = env.get(theme::TEXT_SIZE_NORMAL);
 
         let text_layout = self.get_layout(paint_ctx.render_ctx.text(), env); ////
         ////let text_layout = self.get_layout(paint_ctx.text(), env);
@@ -111,7 +132,7 @@ impl<T: Data + 'static + Default> Widget<T> for Label<T> { ////
         //Make sure we don't draw the text too low
         origin.y = origin.y.min(base_state.size().height);
 
-        paint_ctx.render_ctx.draw_text(&text_layout, origin, &crate::env::LABEL_COLOR); ////
+        paint_ctx.render_ctx.draw_text(&text_layout, origin, &env.get(theme::LABEL_COLOR)); ////
         ////paint_ctx.draw_text(&text_layout, origin, &env.get(theme::LABEL_COLOR));
     }
 
@@ -125,17 +146,16 @@ impl<T: Data + 'static + Default> Widget<T> for Label<T> { ////
         bc.debug_check("Label");
         self.text.resolve(data, env);  ////  TODO: Should auto-resolve
 
-        let font_size = crate::env::TEXT_SIZE_NORMAL; ////
-        ////let font_size = env.get(theme::TEXT_SIZE_NORMAL);
+        let font_size = env.get(theme::TEXT_SIZE_NORMAL);
         let text_layout = self.get_layout(layout_ctx.text(), env);
         // This magical 1.2 constant helps center the text vertically in the rect it's given
         bc.constrain((text_layout.width(), font_size * 1.2))
     }
 
-    fn event(&mut self, _ctx: &mut EventCtx<T>, _event: &Event, _data: &mut T, _env: &Env) {} ////
+    fn event(&mut self, _ctx: &mut EventCtx<D>, _event: &Event, _data: &mut T, _env: &Env) {} ////
     ////fn event(&mut self, _ctx: &mut EventCtx, _event: &Event, _data: &mut T, _env: &Env) {}
 
-    fn update(&mut self, ctx: &mut UpdateCtx<T>, _old_data: Option<&T>, data: &T, env: &Env) { ////
+    fn update(&mut self, ctx: &mut UpdateCtx<D>, _old_data: Option<&T>, data: &T, env: &Env) { ////
     ////fn update(&mut self, ctx: &mut UpdateCtx, _old_data: Option<&T>, data: &T, env: &Env) {
         //cortex_m::asm::bkpt(); ////
         if self.text.resolve(data, env) {
@@ -143,21 +163,21 @@ impl<T: Data + 'static + Default> Widget<T> for Label<T> { ////
         }
     }
 
-    fn to_type(self) -> WidgetType<T> { ////
-        WidgetType::Label(self)
-    }
-
-    fn new_window(self) -> WindowBox<T> { ////
-        WindowBox::new()
+    fn get_id(&self) -> WidgetId { ////
+        self.id
     }
+}
 
-    fn get_id(self) -> WidgetId { ////
-        self.id
+impl<T, const N: usize> LabelText<T, N> { ////
+    /// The specific text, or `CapacityError::Text` if it is longer than `N` bytes
+    pub fn try_from_str(src: &str) -> Result<Self, CapacityError> {
+        String::from_str(src)
+            .map(LabelText::Specific)
+            .map_err(|_| CapacityError::Text)
     }
 }
 
-impl<T: Data + 'static + Default> LabelText<T> { ////
-////impl<T: Data> LabelText<T> {
+impl<T: Data, const N: usize> LabelText<T, N> { ////
     /// The text that should be displayed. This ensures that localized
     /// strings are up to date.
     pub fn display_text(&self) -> &str {
@@ -239,21 +259,39 @@ impl<T: Data + 'static + Default> LabelText<T> { ////
     }
 */ ////
 
-impl<T> From<String> for LabelText<T> {
-    fn from(src: String) -> LabelText<T> {
+impl<T, const N: usize> From<String<N>> for LabelText<T, N> { ////
+    fn from(src: String<N>) -> LabelText<T, N> { ////
         LabelText::Specific(src)
     }
 }
 
-impl<T> From<&str> for LabelText<T> {
-    fn from(src: &str) -> LabelText<T> {
-        LabelText::Specific(String::from_str(src).expect("label text fail")) ////
+/// Panics if the text is longer than `N` bytes, see `LabelText::try_from_str`
+impl<T, const N: usize> From<&str> for LabelText<T, N> { ////
+    fn from(src: &str) -> LabelText<T, N> { ////
+        match LabelText::try_from_str(src) { ////
+            Ok(text) => text,
+            Err(err) => panic!("{}", err),
+        } ////
         ////LabelText::Specific(src.to_string())
     }
 }
 
-impl<T> From<LocalizedString<T>> for LabelText<T> {
-    fn from(src: LocalizedString<T>) -> LabelText<T> {
+impl<T, const N: usize> From<LocalizedString<T, N>> for LabelText<T, N> { ////
+    fn from(src: LocalizedString<T, N>) -> LabelText<T, N> { ////
         LabelText::Localized(src)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_text_is_an_error() {
+        let long = "a label that is far too long for twenty bytes";
+        assert_eq!(Label::<(), 20>::try_new(long).err(), Some(CapacityError::Text));
+        let label = Label::<(), 20>::try_new("fits").unwrap();
+        assert_eq!(label.text.display_text(), "fits");
+        crate::widget::release_widget_id(label.id);
+    }
+}
@@ -15,7 +15,10 @@
 //! Common widgets.
 
 mod widgetbox; ////
-pub use crate::widget::widgetbox::{GlobalWidgets, WidgetBox, WidgetId, WidgetType, MAX_WIDGETS, get_widget_id}; ////
+pub use crate::widget::widgetbox::{ ////
+    AsWidget, CapacityError, WidgetBox, WidgetId, WidgetSet, WidgetType, MAX_WIDGETS, MAX_WIDGET_IDS,
+    get_widget_id, release_widget_id, try_widget_id,
+};
 
 mod align;
 pub use crate::widget::align::Align;
@@ -24,7 +27,7 @@ mod button;
 pub use crate::widget::button::Button;
 
 mod label;
-pub use crate::widget::label::{/* DynLabel, */ Label, LabelText};////
+pub use crate::widget::label::{/* DynLabel, */ Label, LabelText, MAX_LABEL};////
 
 /* ////
 mod either;
@@ -32,7 +35,7 @@ pub use crate::widget::either::Either;
 */ ////
 
 mod flex;
-pub use crate::widget::flex::{Column, Flex, Row};
+pub use crate::widget::flex::{ChildError, Column, Flex, Row, MAX_CHILDREN}; ////
 
 mod padding;
 pub use crate::widget::padding::Padding;
@@ -16,14 +16,14 @@
 
 use crate::kurbo::Insets; ////
 use crate::{
-    BaseState, BoxConstraints, Data, Env, Event, EventCtx, LayoutCtx, PaintCtx, Point, Rect, Size,
+    BaseState, BoxConstraints, AppData, Env, Event, EventCtx, LayoutCtx, PaintCtx, Point, Rect, Size,
     UpdateCtx, Widget, WidgetPod,
-    WidgetId, WidgetType, WidgetBox, Window, WindowType, WindowBox, ////
+    WidgetId, WidgetBox, widget::CapacityError, ////
 };
 
 /// A widget that just adds padding around its child.
 #[derive(Clone)] ////
-pub struct Padding<T: Data + 'static + Default> { ////
+pub struct Padding<T: AppData> { ////
 ////pub struct Padding<T: Data> {
     id: WidgetId, //// Unique Widget ID
     left: f64,
@@ -34,7 +34,7 @@ pub struct Padding<T: Data + 'static + Default> { ////
     ////child: WidgetPod<T, Box<dyn Widget<T>>>,
 }
 
-impl<T: Data + 'static + Default> Padding<T> { ////
+impl<T: AppData> Padding<T> { ////
 ////impl<T: Data> Padding<T> {
     /* //// Deprecated
         /// Create widget with uniform padding.
@@ -82,7 +82,8 @@ impl<T: Data + 'static + Default> Padding<T> { ////
     /// ```
     ///
     /// [`kurbo::Insets`]: https://docs.rs/kurbo/0.5.3/kurbo/struct.Insets.html
-    pub fn new<W: Widget<T> + Clone>(insets: impl Into<Insets>, child: W) -> Padding<T> { ////
+    pub fn new<W: Widget<T> + Clone>(insets: impl Into<Insets>, child: W) -> Padding<T>
+    where T::Widgets: From<W> { ////
     ////pub fn new(insets: impl Into<Insets>, child: impl Widget<T> + 'static) -> Padding<T> {
         let insets = insets.into();
         Padding {
@@ -97,9 +98,33 @@ impl<T: Data + 'static + Default> Padding<T> { ////
             ////child: WidgetPod::new(child).boxed(),
         }
     }
+
+    /// Create a new widget with the specified padding, or return an error if there is
+    /// no space for the widget or its child. The child is released on error.
+    pub fn try_new<W: Widget<T> + Clone>(insets: impl Into<Insets>, child: W) -> Result<Padding<T>, CapacityError> ////
+    where T::Widgets: From<W> {
+        let insets = insets.into();
+        let id = match super::try_widget_id() {
+            Ok(id) => id,
+            Err(err) => {
+                child.release();
+                return Err(err);
+            }
+        };
+        let child = WidgetBox::<T>::try_new(child)
+            .inspect_err(|_| super::release_widget_id(id))?;
+        Ok(Padding {
+            id,
+            left: insets.x0,
+            right: insets.x1,
+            top: insets.y0,
+            bottom: insets.y1,
+            child: WidgetPod::new(child),
+        })
+    }
 }
 
-impl<T: Data + 'static + Default> Widget<T> for Padding<T> { ////
+impl<T: AppData> Widget<T> for Padding<T> { ////
 ////impl<T: Data> Widget<T> for Padding<T> {
     fn paint(&mut self, paint_ctx: &mut PaintCtx, _base_state: &BaseState, data: &T, env: &Env) {
         self.child.paint_with_offset(paint_ctx, data, env);
@@ -135,19 +160,12 @@ impl<T: Data + 'static + Default> Widget<T> for Padding<T> { ////
         self.child.update(ctx, data, env);
     }
 
-    fn to_type(self) -> WidgetType<T> { ////
-        WidgetType::Padding(self)
-    }
-
-    fn new_window(self) -> WindowBox<T> { ////
-        let window = Window::new(self);
-        let window_box = WindowBox(
-            WindowType::Padding(window),
-        );
-        window_box
+    fn get_id(&self) -> WidgetId { ////
+        self.id
     }
 
-    fn get_id(self) -> WidgetId { ////
-        self.id
+    fn release(self) { ////
+        self.child.into_inner().release();
+        super::release_widget_id(self.id);
     }
 }
\ No newline at end of file
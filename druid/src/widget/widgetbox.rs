@@ -1,81 +1,393 @@
 //! `WidgetBox` contains a `Widget`. Allows for dynamic dispatch with static `Widgets` in `[no_std]`.
+use core::cell::RefCell;
+use core::fmt;
 use core::marker::PhantomData;
+use critical_section::Mutex;
 use crate::kurbo::{Size};
 use crate::{
-    BaseState, BoxConstraints, Data, Env, Event, EventCtx, LayoutCtx, PaintCtx, UpdateCtx, Widget, WindowBox,
-    widget::{Align, Button, Flex, Label, Padding},
+    BaseState, BoxConstraints, AppData, Env, Event, EventCtx, LayoutCtx, PaintCtx, UpdateCtx, Widget,
 };
 
-/// Widgets are identified by an 8-bit ID
-pub type WidgetId = u8;
+/// Widgets are identified by an 8-bit slot index and a generation. The generation is
+/// incremented when the ID is released, so a stale ID never matches the `Widget` that
+/// reuses its slot.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WidgetId {
+    index: u8,
+    generation: u16,
+}
+
+impl WidgetId {
+    /// The slot of the `Widget` in the arena of the Application Data
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+
+    /// Incremented each time the slot is reused
+    pub fn generation(self) -> u16 {
+        self.generation
+    }
 
-/// Max number of `Widgets` on embedded platforms
+    /// True if the ID has not been released
+    pub fn is_live(self) -> bool {
+        critical_section::with(|cs| WIDGET_IDS.borrow(cs).borrow().is_live(self))
+    }
+}
+
+/// Default max number of `Widgets` per application.
+/// Override with `#[druid(app_state, max_widgets = 64)]`.
+///
+/// `max_widgets` sets the number of `Widgets`, not their types: the builtin `WidgetType` stores
+/// `Flex` and `Label` with their default capacities only. `Flex<T, 16>` or `Label<T, 40>`
+/// need a custom `widget_set!`.
 pub const MAX_WIDGETS: usize = 10;
 
-/// Unique Widget ID
-static mut WIDGET_ID: WidgetId = 0;
+/// Max number of Widget IDs in use at the same time, by all applications
+pub const MAX_WIDGET_IDS: usize = u8::MAX as usize;
+
+/// A `Widget` or window couldn't be created or added because a fixed-size store is full
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CapacityError {
+    /// All `MAX_WIDGET_IDS` Widget IDs are in use
+    WidgetIds,
+    /// The arena of the Application Data is full, see `max_widgets`
+    Widgets,
+    /// The container is full, e.g. `Flex<T, N>` already has `N` children
+    Children,
+    /// The arena of the Application Data has no slot for the window, see `max_windows`
+    Windows,
+    /// `MAX_COMMANDS` commands are already queued
+    Commands,
+    /// All `MAX_ENV_VALUES` keys of the `Env` have values
+    EnvValues,
+    /// `MAX_ENV_CHANGES` changes to the `Env` are already pending
+    EnvChanges,
+    /// The text is too long for the `Label`, e.g. `Label<T, N>` holds `N` bytes
+    Text,
+}
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CapacityError::WidgetIds => write!(f, "too many widget IDs"),
+            CapacityError::Widgets => write!(f, "too many widgets, increase `max_widgets`"),
+            CapacityError::Children => write!(f, "too many children, use a larger container"),
+            CapacityError::Windows => write!(f, "too many windows, increase `max_windows`"),
+            CapacityError::Commands => write!(f, "too many commands queued"),
+            CapacityError::EnvValues => write!(f, "env full, increase `MAX_ENV_VALUES`"),
+            CapacityError::EnvChanges => write!(f, "too many env changes in one call"),
+            CapacityError::Text => write!(f, "label text too long, use a larger `Label`"),
+        }
+    }
+}
+
+/// Allocates Widget IDs. Released slots are reused before new slots.
+struct WidgetIds {
+    /// generations[i] is the generation of the live ID or the next ID in slot i
+    generations: [u16; MAX_WIDGET_IDS],
+    /// Released slots
+    free: heapless::Vec<u8, MAX_WIDGET_IDS>,
+    /// Number of slots that have been used
+    used: u8,
+}
+
+impl WidgetIds {
+    const fn new() -> Self {
+        WidgetIds { generations: [0; MAX_WIDGET_IDS], free: heapless::Vec::new(), used: 0 }
+    }
 
-/// Assign a unique Widget ID
+    fn allocate(&mut self) -> Result<WidgetId, CapacityError> {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None if (self.used as usize) < MAX_WIDGET_IDS => {
+                self.used += 1;
+                self.used - 1
+            }
+            None => return Err(CapacityError::WidgetIds),
+        };
+        Ok(WidgetId { index, generation: self.generations[index as usize] })
+    }
+
+    fn is_live(&self, id: WidgetId) -> bool {
+        id.index < self.used && self.generations[id.index()] == id.generation
+    }
+
+    fn release(&mut self, id: WidgetId) {
+        //  Releasing a stale ID twice must not free the slot of another Widget
+        if self.is_live(id) {
+            self.generations[id.index()] = id.generation.wrapping_add(1);
+            self.free.push(id.index).ok();  //  Can't fail: every slot is freed at most once
+        }
+    }
+}
+
+/// Widget IDs of all applications, because Widgets are created before they are added to an arena
+static WIDGET_IDS: Mutex<RefCell<WidgetIds>> = Mutex::new(RefCell::new(WidgetIds::new())); ////
+
+/// Assign a unique Widget ID. Panics if all IDs are in use.
 pub fn get_widget_id() -> WidgetId {
-    let id = unsafe { WIDGET_ID };
-    assert!((id as usize) < MAX_WIDGETS, "too many widgets");
-    unsafe { WIDGET_ID += 1; }
-    id
+    try_widget_id().expect("too many widgets") ////
+}
+
+/// Assign a unique Widget ID, reusing the slot of a released ID if possible
+pub fn try_widget_id() -> Result<WidgetId, CapacityError> { ////
+    critical_section::with(|cs| WIDGET_IDS.borrow(cs).borrow_mut().allocate())
+}
+
+/// Release the Widget ID, so that its slot may be reused. Releasing a stale ID does nothing.
+/// Called by `Widget::release`.
+pub fn release_widget_id(id: WidgetId) { ////
+    critical_section::with(|cs| WIDGET_IDS.borrow(cs).borrow_mut().release(id))
+}
+
+/// The set of `Widget` types that may be stored in a `WidgetBox`.
+/// Selected by `AppData::Widgets` and generated by `widget_set!`.
+pub trait WidgetSet<D: AppData>: Clone + 'static {
+    /// Empty slot without a `Widget`
+    const NONE: Self;
+    /// Paint the stored `Widget`
+    fn paint(&mut self, paint_ctx: &mut PaintCtx, base_state: &BaseState, data: &D, env: &Env);
+    /// Compute the layout of the stored `Widget`
+    fn layout(&mut self, layout_ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &D, env: &Env) -> Size;
+    /// Send an event to the stored `Widget`
+    fn event(&mut self, ctx: &mut EventCtx<D>, event: &Event, data: &mut D, env: &Env);
+    /// Send a data update to the stored `Widget`
+    fn update(&mut self, ctx: &mut UpdateCtx<D>, old_data: Option<&D>, data: &D, env: &Env);
+    /// Release the Widget IDs of the stored `Widget` and its children
+    fn release(self);
 }
 
-/// Specialised Trait for handling static `Widgets` on embedded platforms
-pub trait GlobalWidgets<D: Data + 'static + Default> {
-    /// Fetch the static `Widgets` for the Data type
-    fn get_widgets(&self) -> &'static mut [ WidgetType<D> ];
-    /// Add a `Widget` for the Data type
-    fn add_widget(&self, widget: WidgetType<D>);
+/// A `WidgetSet` that stores the `Widget` type `W`, so that a boxed `W` can be
+/// reached with `WidgetBox::with_mut`. Generated by `widget_set!`.
+pub trait AsWidget<W> {
+    /// The stored `Widget`, or `None` if another type of `Widget` is stored
+    fn as_widget_mut(&mut self) -> Option<&mut W>;
 }
 
-/// Default Trait will not have static `Widgets`
-impl<D: Data + 'static + Default> GlobalWidgets<D> for WidgetBox<D> {
-    default fn get_widgets(&self) -> &'static mut [ WidgetType<D> ] { panic!("no global widgets") }
-    default fn add_widget(&self, _widget: WidgetType<D>) { panic!("no global widgets") }
+/// Generate an enum that stores each `Widget` type of an application, for use as `AppData::Widgets`.
+/// Every `Widget` type must be generic over the Application Data, like the builtin `Widgets`.
+/// `..druid::builtin` adds the builtin `Align`, `Button`, `Flex`, `Label` and `Padding` `Widgets`.
+///
+/// ```ignore
+/// druid::widget_set! {
+///     /// Widgets used by the watch face
+///     pub MyWidgets { Gauge, Clock, ..druid::builtin }
+/// }
+///
+/// #[derive(Clone, Data, Default)]
+/// #[druid(app_state, widgets = "MyWidgets")]
+/// struct State { count: u32 }
+/// ```
+///
+/// `Widgets` that only work with one Application Data type, like a `LensWrap` that shows
+/// one field, are named with their full type after `for` and the Application Data type.
+/// The same form adds builtin `Widgets` with other capacities, like a `Flex` with 16 children:
+///
+/// ```ignore
+/// druid::widget_set! {
+///     pub StateWidgets for State {
+///         CountLabel = LensWrap<u32, lenses::state::count, Label<u32>>,
+///         WideFlex = Flex<State, 16>,
+///         ..druid::builtin
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! widget_set {
+    //  Custom Widgets for one Application Data type, plus the builtin Widgets
+    ( $(#[$meta:meta])* $vis:vis $name:ident for $data:ty { $( $variant:ident = $widget:ty, )* ..druid::builtin $(,)? } ) => {
+        $crate::widget_set! { @set $(#[$meta])* $vis $name [] [$data] {
+            $( $variant: [$widget], )*
+            Align:   [$crate::widget::Align<$data>],
+            Button:  [$crate::widget::Button<$data>],
+            Flex:    [$crate::widget::Flex<$data>],
+            Label:   [$crate::widget::Label<$data>],
+            Padding: [$crate::widget::Padding<$data>],
+        } }
+    };
+    //  Custom Widgets for one Application Data type only
+    ( $(#[$meta:meta])* $vis:vis $name:ident for $data:ty { $( $variant:ident = $widget:ty ),* $(,)? } ) => {
+        $crate::widget_set! { @set $(#[$meta])* $vis $name [] [$data] {
+            $( $variant: [$widget], )*
+        } }
+    };
+    //  Custom Widgets plus the builtin Widgets
+    ( $(#[$meta:meta])* $vis:vis $name:ident { $( $variant:ident ),* $(,)? ..druid::builtin $(,)? } ) => {
+        $crate::widget_set! { @set $(#[$meta])* $vis $name [D: $crate::AppData] [D] {
+            $( $variant: [$variant<D>], )*
+            Align:   [$crate::widget::Align<D>],
+            Button:  [$crate::widget::Button<D>],
+            Flex:    [$crate::widget::Flex<D>],
+            Label:   [$crate::widget::Label<D>],
+            Padding: [$crate::widget::Padding<D>],
+        } }
+    };
+    //  Custom Widgets only
+    ( $(#[$meta:meta])* $vis:vis $name:ident { $( $variant:ident ),* $(,)? } ) => {
+        $crate::widget_set! { @set $(#[$meta])* $vis $name [D: $crate::AppData] [D] {
+            $( $variant: [$variant<D>], )*
+        } }
+    };
+    //  Generate the enum. Each variant is named after its Widget type. `WidgetSet` is
+    //  implemented for every Application Data type, or only for `$data` if it's concrete.
+    ( @set $(#[$meta:meta])* $vis:vis $name:ident [$($generics:tt)*] [$data:ty] { $( $variant:ident: [$widget:ty], )* } ) => {
+        $(#[$meta])*
+        #[derive(Clone)]
+        $vis enum $name<D: $crate::AppData> {
+            None,
+            $( $variant($widget), )*
+            #[doc(hidden)]
+            __Data(core::marker::PhantomData<D>, core::convert::Infallible),
+        }
+
+        impl<D: $crate::AppData> Default for $name<D> {
+            fn default() -> Self { $name::None }
+        }
+
+        impl<D: $crate::AppData> $crate::ConstDefault for $name<D> {
+            const DEFAULT: Self = $name::None;
+        }
+
+        $(
+            impl<D: $crate::AppData> From<$widget> for $name<D> {
+                fn from(widget: $widget) -> Self { $name::$variant(widget) }
+            }
+
+            impl<D: $crate::AppData> $crate::AsWidget<$widget> for $name<D> {
+                fn as_widget_mut(&mut self) -> Option<&mut $widget> {
+                    match self {
+                        $name::$variant(widget) => Some(widget),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+
+        impl<$($generics)*> $crate::WidgetSet<$data> for $name<$data> {
+            const NONE: Self = $name::None;
+
+            fn paint(
+                &mut self,
+                paint_ctx: &mut $crate::PaintCtx,
+                base_state: &$crate::BaseState,
+                data: &$data,
+                env: &$crate::Env
+            ) {
+                match self {
+                    $( $name::$variant(w) => <$widget as $crate::Widget<$data>>::paint(w, paint_ctx, base_state, data, env), )*
+                    $name::None => {}
+                    $name::__Data(_, never) => match *never {},
+                }
+            }
+
+            fn layout(
+                &mut self,
+                layout_ctx: &mut $crate::LayoutCtx,
+                bc: &$crate::BoxConstraints,
+                data: &$data,
+                env: &$crate::Env,
+            ) -> $crate::Size {
+                match self {
+                    $( $name::$variant(w) => <$widget as $crate::Widget<$data>>::layout(w, layout_ctx, bc, data, env), )*
+                    $name::None => $crate::Size::ZERO,
+                    $name::__Data(_, never) => match *never {},
+                }
+            }
+
+            fn event(
+                &mut self,
+                ctx: &mut $crate::EventCtx<$data>,
+                event: &$crate::Event,
+                data: &mut $data,
+                env: &$crate::Env
+            ) {
+                match self {
+                    $( $name::$variant(w) => <$widget as $crate::Widget<$data>>::event(w, ctx, event, data, env), )*
+                    $name::None => {}
+                    $name::__Data(_, never) => match *never {},
+                }
+            }
+
+            fn update(
+                &mut self,
+                ctx: &mut $crate::UpdateCtx<$data>,
+                old_data: Option<&$data>,
+                data: &$data,
+                env: &$crate::Env
+            ) {
+                match self {
+                    $( $name::$variant(w) => <$widget as $crate::Widget<$data>>::update(w, ctx, old_data, data, env), )*
+                    $name::None => {}
+                    $name::__Data(_, never) => match *never {},
+                }
+            }
+
+            fn release(self) {
+                match self {
+                    $( $name::$variant(w) => <$widget as $crate::Widget<$data>>::release(w), )*
+                    $name::None => {}
+                    $name::__Data(_, never) => match never {},
+                }
+            }
+        }
+    };
+}
+
+widget_set! {
+    /// Enum to store each builtin `Widget`
+    pub WidgetType { ..druid::builtin }
 }
 
 /// Boxed version of a `Widget`
 #[derive(Clone, Default)]
-pub struct WidgetBox<D: Data + 'static>(
+pub struct WidgetBox<D: AppData>(
     pub WidgetId,    //  Widget ID
     PhantomData<D>,  //  Needed to do compile-time checking for `Data`
 );
 
-/// Enum to store each `Widget`
-#[derive(Clone)]
-pub enum WidgetType<D: Data + 'static + Default> {
-    None,
-    Align(Align<D>),
-    Button(Button<D>),
-    Flex(Flex<D>),
-    Label(Label<D>),
-    Padding(Padding<D>),
-}
+/// Generic implementation of `WidgetBox`
+impl<D: AppData> WidgetBox<D> {
+    /// Create a new box for the `Widget`. The `Widget` type must be in the `WidgetSet` of the Data type.
+    /// Panics if the arena is full.
+    pub fn new<W: Widget<D> + Clone>(widget: W) -> Self
+    where D::Widgets: From<W> {
+        Self::try_new(widget).expect("too many widgets, increase `max_widgets`") ////
+    }
 
-impl<D: Data + 'static + Default> Default for WidgetType<D> {
-    fn default() -> Self { WidgetType::None }
-}
+    /// Create a new box for the `Widget`, or release the `Widget` if the arena is full
+    pub fn try_new<W: Widget<D>>(widget: W) -> Result<Self, CapacityError> ////
+    where D::Widgets: From<W> {
+        let id = widget.get_id();
+        let widget: D::Widgets = widget.into();
+        match D::with_arena(|arena| arena.add_widget(id, widget)) {
+            Ok(()) => Ok(WidgetBox(id, PhantomData)),
+            Err((widget, err)) => {
+                widget.release();
+                Err(err)
+            }
+        }
+    }
 
-/// Generic implementation of `WidgetBox`
-impl<D: Data + 'static + Default> WidgetBox<D> {
-    /// Create a new box for the `Widget`
-    pub fn new<W: Widget<D> + Clone>(widget: W) -> Self {
-        let id = widget.clone().get_id();
-        let widget_type: WidgetType<D> = widget.to_type();
-        let widget_box: WidgetBox<D> = WidgetBox(
-            id,
-            PhantomData,
-        );
-        widget_box.clone().add_widget(widget_type);
-        widget_box
+    /// Run `f` on the boxed `Widget` if it's a `W`, e.g. to change the children of a boxed `Flex`
+    /// while handling an event. Returns `None` if another type of `Widget` is boxed.
+    pub fn with_mut<W, R>(&self, f: impl FnOnce(&mut W) -> R) -> Option<R>
+    where D::Widgets: AsWidget<W> {
+        self.with_widget(|widget| widget.as_widget_mut().map(f))
+    }
+
+    /// Move the boxed `Widget` out of the arena, run `f` on it and move it back.
+    /// The arena is not borrowed while `f` runs, so `f` may call into child `WidgetBoxes`.
+    fn with_widget<R>(&self, f: impl FnOnce(&mut D::Widgets) -> R) -> R {
+        assert!(self.0.is_live(), "widget was released"); ////
+        let mut widget = D::with_arena(|arena| arena.take_widget(self.0));
+        let result = f(&mut widget);
+        D::with_arena(|arena| arena.put_widget(self.0, widget));
+        result
     }
 }
 
 /// Implementation of `Widget` trait for `WidgetBox`. We just forward to the inner `Widget`.
-impl<D: Data + 'static + Default> Widget<D> for WidgetBox<D> {
+impl<D: AppData> Widget<D> for WidgetBox<D> {
     fn paint(
         &mut self, 
         paint_ctx: &mut PaintCtx, 
@@ -83,14 +395,7 @@ impl<D: Data + 'static + Default> Widget<D> for WidgetBox<D> {
         data: &D, 
         env: &Env
     ) {
-        match &mut self.get_widgets()[self.0 as usize] {
-            WidgetType::Align(w)   => w.paint(paint_ctx, base_state, data, env),
-            WidgetType::Button(w)  => w.paint(paint_ctx, base_state, data, env),
-            WidgetType::Flex(w)    => w.paint(paint_ctx, base_state, data, env),
-            WidgetType::Label(w)   => w.paint(paint_ctx, base_state, data, env),
-            WidgetType::Padding(w) => w.paint(paint_ctx, base_state, data, env),
-            WidgetType::None => {}
-        };
+        self.with_widget(|widget| widget.paint(paint_ctx, base_state, data, env));
     }
 
     fn layout(
@@ -100,14 +405,7 @@ impl<D: Data + 'static + Default> Widget<D> for WidgetBox<D> {
         data: &D,
         env: &Env,
     ) -> Size {
-        match &mut self.get_widgets()[self.0 as usize] {
-            WidgetType::Align(w)   => w.layout(layout_ctx, bc, data, env),
-            WidgetType::Button(w)  => w.layout(layout_ctx, bc, data, env),
-            WidgetType::Flex(w)    => w.layout(layout_ctx, bc, data, env),
-            WidgetType::Label(w)   => w.layout(layout_ctx, bc, data, env),
-            WidgetType::Padding(w) => w.layout(layout_ctx, bc, data, env),
-            WidgetType::None => Size::ZERO,
-        }
+        self.with_widget(|widget| widget.layout(layout_ctx, bc, data, env))
     }
 
     fn event(
@@ -117,14 +415,7 @@ impl<D: Data + 'static + Default> Widget<D> for WidgetBox<D> {
         data: &mut D, 
         env: &Env
     ) {
-        match &mut self.get_widgets()[self.0 as usize] {
-            WidgetType::Align(w)   => w.event(ctx, event, data, env),
-            WidgetType::Button(w)  => w.event(ctx, event, data, env),
-            WidgetType::Flex(w)    => w.event(ctx, event, data, env),
-            WidgetType::Label(w)   => w.event(ctx, event, data, env),
-            WidgetType::Padding(w) => w.event(ctx, event, data, env),
-            WidgetType::None => {}
-        };
+        self.with_widget(|widget| widget.event(ctx, event, data, env));
     }
 
     fn update(
@@ -134,32 +425,39 @@ impl<D: Data + 'static + Default> Widget<D> for WidgetBox<D> {
         data: &D, 
         env: &Env
     ) {
-        match &mut self.get_widgets()[self.0 as usize] {
-            WidgetType::Align(w)   => w.update(ctx, old_data, data, env),
-            WidgetType::Button(w)  => w.update(ctx, old_data, data, env),
-            WidgetType::Flex(w)    => w.update(ctx, old_data, data, env),
-            WidgetType::Label(w)   => w.update(ctx, old_data, data, env),
-            WidgetType::Padding(w) => w.update(ctx, old_data, data, env),
-            WidgetType::None => {}
-        };
+        self.with_widget(|widget| widget.update(ctx, old_data, data, env));
     }
 
-    fn to_type(self) -> WidgetType<D> {
-        WidgetType::None
+    fn get_id(&self) -> WidgetId {
+        self.0
     }
 
-    fn new_window(self) -> WindowBox<D> {
-        WindowBox::new()
+    /// Remove the boxed `Widget` from the arena and release it with its children
+    fn release(self) { ////
+        if self.0.is_live() {
+            let widget = D::with_arena(|arena| arena.take_widget(self.0));
+            widget.release();
+        }
     }
+}
 
-    fn get_id(self) -> WidgetId {
-        match &mut self.get_widgets()[self.0 as usize] {
-            WidgetType::Align(w)   => w.clone().get_id(),
-            WidgetType::Button(w)  => w.clone().get_id(),
-            WidgetType::Flex(w)    => w.clone().get_id(),
-            WidgetType::Label(w)   => w.clone().get_id(),
-            WidgetType::Padding(w) => w.clone().get_id(),
-            WidgetType::None => panic!("no id")
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuse_released_ids() {
+        let first = get_widget_id();
+        assert!(first.is_live());
+        release_widget_id(first);
+        assert!(!first.is_live());
+        //  Releasing twice doesn't free the slot again
+        release_widget_id(first);
+        let second = get_widget_id();
+        let third = get_widget_id();
+        assert!(second != first && third != first && second != third);
+        assert!(second.is_live() && third.is_live());
+        release_widget_id(second);
+        release_widget_id(third);
     }
-}
\ No newline at end of file
+}
@@ -29,107 +29,28 @@ use core::clone::Clone; ////
 use crate::kurbo::{Rect, Size}; ////
 use crate::piet::{Piet, RenderContext};
 use crate::shell::{
-    /* Application, */ Cursor, /* FileDialogOptions, */ MouseEvent, WinCtx, WinHandler, WindowHandle,
+    /* Application, */ Cursor, /* FileDialogOptions, */ MouseEvent, WinCtx, WinHandler, WindowHandle, focused_window, post_event, post_touch, QueuedEvent, ////
+    Error as PlatformError, WindowBuilder, ////
 };
 
-/* ////
-    use crate::app_delegate::{AppDelegate, DelegateCtx};
-    use crate::menu::ContextMenu;
-    use crate::theme;
-*/ ////
+use crate::app_delegate::{AppDelegate, DelegateCtx}; ////
+use crate::navigator::{Navigation, Navigator}; ////
+use crate::transition::ScreenTransition; ////
+////use crate::menu::ContextMenu;
+use crate::theme; ////
 ////use crate::window::Window;
 use crate::{
-    BaseState, /* Command, */ Data, Env, Event, EventCtx, /* KeyEvent, KeyModifiers, */ LayoutCtx, /* MenuDesc, */ ////
-    PaintCtx, /* TimerToken, */ UpdateCtx, /* WheelEvent, WindowDesc, */ WindowId,
-    WindowIdType, WindowBox, ////
+    BaseState, Command, AppData, Env, Event, EventCtx, /* KeyEvent, KeyModifiers, */ LayoutCtx, /* MenuDesc, */ ////
+    Instant, PaintCtx, TimerToken, UpdateCtx, /* WheelEvent, WindowDesc, */ WindowId,
+    WindowIdType, WindowBox, PlatformWindowHandle, PlatformWindowState, ////
 };
 
-////use crate::command::sys as sys_cmd;
+use crate::command::sys as sys_cmd;
 
-//  Static storage for Windows, Window Handlers and Application Data, used for embedded platforms only
- 
-/// Max number of Windows supported. i=0 is not used, so MAX_WINDOWS should be 1 more than max number of Windows.
+/// Default max number of Windows per application. i=0 is not used, so MAX_WINDOWS should be 1 more
+/// than max number of Windows. Override with `#[druid(app_state, max_windows = 4)]`.
 pub const MAX_WINDOWS: usize = 3; ////
 
-/// Specialised Trait for handling static Windows, Window Handlers and Application Data on embedded platforms
-pub trait GlobalWindows<D: Data + 'static + Default> { ////
-    /// Add a WindowBox for the Data type
-    fn add_window(&self, window_id: WindowId, window: WindowBox<D>);
-    /// Add a Window Handler for the Data type
-    fn add_handler(&self, window_id: WindowId, handler: DruidHandler<D>);
-    /// Return a Window Handle that wraps the Window Handler for the Data type
-    fn get_handle(&self, window_id: WindowId) -> WindowHandle<DruidHandler<D>>;
-    /// Set the application data
-    fn set_data(&self, data: D);
-    /// Handle the Window event
-    fn window_event(
-        &mut self, 
-        window_id: WindowId,
-        ctx: &mut EventCtx<D>, 
-        event: &Event, 
-    );
-    /// Handle the Window update
-    fn window_update(
-        &mut self, 
-        window_id: WindowId,
-        ctx: &mut UpdateCtx<D>, 
-    );
-    /// Handle the Window layout
-    fn window_layout(
-        &mut self,
-        window_id: WindowId,
-        layout_ctx: &mut LayoutCtx,
-    );
-    /// Handle the Window painting
-    fn window_paint(
-        &mut self, 
-        window_id: WindowId,
-        paint_ctx: &mut PaintCtx, 
-    );
-    /// Return true if Window is active
-    fn window_has_active(
-        &mut self,
-        window_id: WindowId,
-    ) -> bool;
-}
-
-/// Default Trait will not have static Windows and Window Handlers
-impl<D: Data + 'static + Default> GlobalWindows<D> for AppState<D> { ////
-    default fn add_window(&self, _window_id: WindowId, _window: WindowBox<D>)
-        { panic!("no global windows") }
-    default fn add_handler(&self, _window_id: WindowId, _handler: DruidHandler<D>)
-        { panic!("no global windows") }
-    default fn get_handle(&self, _window_id: WindowId) -> WindowHandle<DruidHandler<D>>
-        { panic!("no global windows") }
-    default fn set_data(&self, _data: D)
-        { panic!("no global windows") }
-    default fn window_event(
-        &mut self, 
-        _window_id: WindowId,
-        _ctx: &mut EventCtx<D>, 
-        _event: &Event, 
-    ) { panic!("no global windows") }
-    default fn window_update(
-        &mut self, 
-        _window_id: WindowId,
-        _ctx: &mut UpdateCtx<D>, 
-    ) { panic!("no global windows") }
-    default fn window_layout(
-        &mut self,
-        _window_id: WindowId,
-        _layout_ctx: &mut LayoutCtx,
-    ) { panic!("no global windows") }
-    default fn window_paint(
-        &mut self, 
-        _window_id: WindowId,
-        _paint_ctx: &mut PaintCtx, 
-    ) { panic!("no global windows") }
-    default fn window_has_active(
-        &mut self,
-        _window_id: WindowId,
-    ) -> bool { panic!("no global windows") }
-}
-
 /// The struct implements the druid-shell `WinHandler` trait.
 ///
 /// One `DruidHandler` exists per window.
@@ -137,7 +58,7 @@ impl<D: Data + 'static + Default> GlobalWindows<D> for AppState<D> { ////
 /// This is something of an internal detail and possibly we don't want to surface
 /// it publicly.
 #[derive(Clone, Default)] ////
-pub struct DruidHandler<T: Data + 'static + Default> { ////
+pub struct DruidHandler<T: AppData> { ////
 ////pub struct DruidHandler<T: Data> {
     /// The shared app state.
     ////app_state: Rc<RefCell<AppState<T>>>,
@@ -149,7 +70,7 @@ pub struct DruidHandler<T: Data + 'static + Default> { ////
 
 /// State shared by all windows in the UI.
 #[derive(Clone)] ////
-pub struct AppState<T: Data + 'static + Default> { ////
+pub struct AppState<T: AppData> { ////
 ////pub(crate) struct AppState<T: Data> {
     phantom: PhantomData<T>,  ////  Needed to do compile-time checking for `Data`
     ////delegate: Option<Box<dyn AppDelegate<T>>>,
@@ -161,7 +82,7 @@ pub struct AppState<T: Data + 'static + Default> { ////
 
 /// All active windows.
 #[derive(Clone)] ////
-struct Windows<T: Data + 'static + Default> { ////
+struct Windows<T: AppData> { ////
 ////struct Windows<T: Data> {
     phantom: PhantomData<T>,  ////  Needed to do compile-time checking for `Data`
     ////windows: HashMap<WindowId, Window<T>>,  //// Replaced by ALL_WINDOWS
@@ -170,7 +91,7 @@ struct Windows<T: Data + 'static + Default> { ////
 
 /// Per-window state not owned by user code.
 #[derive(Clone, Default)] ////
-pub(crate) struct WindowState<D: Data + 'static + Default> { ////  D is Data + 'static
+pub(crate) struct WindowState<D: AppData> { ////  D is Data + 'static
 ////pub(crate) struct WindowState {
     window_id: WindowId,  ////
     phantom: PhantomData<D>,  ////  Needed to do compile-time checking for `Data`
@@ -179,7 +100,7 @@ pub(crate) struct WindowState<D: Data + 'static + Default> { ////  D is Data + '
 }
 
 /// Everything required for a window to handle an event.
-struct SingleWindowState<T: Data + 'static + Default> { ////
+struct SingleWindowState<T: AppData> { ////
 ////struct SingleWindowState<'a, T: Data> {
     window_id: WindowId,
     phantom: PhantomData<T>,  ////  Needed to do compile-time checking for `Data`
@@ -190,7 +111,7 @@ struct SingleWindowState<T: Data + 'static + Default> { ////
     ////env: &'a Env, //// Replaced by Env{}
 }
 
-impl<T: Data + 'static + Default> Windows<T> { ////
+impl<T: AppData> Windows<T> { ////
 ////impl<T: Data> Windows<T> {
     #[allow(dead_code)] ////
     fn connect(&mut self, _id: WindowId, _handle: WindowHandle<DruidHandler<T>>) { ////
@@ -213,12 +134,11 @@ impl<T: Data + 'static + Default> Windows<T> { ////
     }
 
     #[allow(dead_code)] ////
-    fn remove(&mut self, _id: WindowId) -> Option<WindowHandle<DruidHandler<T>>> { ////
+    fn remove(&mut self, id: WindowId) -> Option<WindowHandle<DruidHandler<T>>> { ////
     ////fn remove(&mut self, id: WindowId) -> Option<WindowHandle> {
+        AppState::<T>::new().remove_window(id) ////
         ////self.windows.remove(&id);
         ////self.state.remove(&id).map(|state| state.handle)
-        ////Some(self.state[0].handle) ////
-        None //// TODO
     }
 
     //TODO: rename me?
@@ -262,50 +182,60 @@ impl<T: Data + 'static + Default> Windows<T> { ////
     }
 }
 
-impl<T: Data + 'static + Default> SingleWindowState<T> { ////
+impl<T: AppData> SingleWindowState<T> { ////
 ////impl<T: Data + 'static> SingleWindowState<T> {
-    fn paint(&mut self, piet: &mut Piet, _ctx: &mut dyn WinCtx) -> bool {
-        ////let request_anim = self.do_anim_frame(ctx);
+    fn paint(&mut self, piet: &mut Piet, ctx: &mut dyn WinCtx) -> bool {
+        let request_anim = self.do_anim_frame(ctx);
         self.do_layout(piet);
-        piet.clear(crate::env::WINDOW_BACKGROUND_COLOR); ////
+        let background = T::with_arena(|arena| arena.env().get(theme::WINDOW_BACKGROUND_COLOR)); ////
+        piet.clear(background); ////
         ////piet.clear(self.env.get(theme::WINDOW_BACKGROUND_COLOR));
         self.do_paint(piet);
-        false ////
-        ////request_anim
+        request_anim
     }
 
-    /* ////
-        fn do_anim_frame(&mut self, ctx: &mut dyn WinCtx) -> bool {
-            // TODO: this calculation uses wall-clock time of the paint call, which
-            // potentially has jitter.
-            //
-            // See https://github.com/xi-editor/druid/issues/85 for discussion.
-            let this_paint_time = Instant::now();
-            let prev_paint_time = self.state.prev_paint_time;
-            let interval = if let Some(last) = prev_paint_time {
-                let duration = this_paint_time.duration_since(last);
-                1_000_000_000 * duration.as_secs() + u64::from(duration.subsec_nanos())
-            } else {
-                0
-            };
-            let anim_frame_event = Event::AnimFrame(interval);
-            let (_, _, request_anim) = self.do_event_inner(anim_frame_event, ctx);
-            let prev = if request_anim {
-                Some(this_paint_time)
-            } else {
-                None
-            };
-            self.state.prev_paint_time = prev;
-            request_anim
+    fn do_anim_frame(&mut self, ctx: &mut dyn WinCtx) -> bool {
+        // TODO: this calculation uses wall-clock time of the paint call, which
+        // potentially has jitter.
+        //
+        // See https://github.com/xi-editor/druid/issues/85 for discussion.
+        let this_paint_time = Instant::now();
+        let prev_paint_time = AppState::<T>::new().prev_paint_time(self.window_id); ////
+        ////let prev_paint_time = self.state.prev_paint_time;
+        let interval = if let Some(last) = prev_paint_time {
+            let duration = this_paint_time.duration_since(last);
+            1_000_000_000 * duration.as_secs() + u64::from(duration.subsec_nanos())
+        } else {
+            0
+        };
+        let anim_frame_event = Event::AnimFrame(interval);
+        let (_, _, request_anim) = self.do_event_inner(anim_frame_event, ctx);
+        let transitioning = T::with_arena(|arena| arena.step_transition(self.window_id, interval)); ////
+        if !transitioning {
+            //  Released outside the arena, because releasing takes the Widgets out of the arena
+            if let Some(old) = T::with_arena(|arena| arena.finish_transition(self.window_id)) {
+                old.release();
+            }
         }
-    */ ////
+        let request_anim = request_anim || transitioning; ////
+        let prev = if request_anim {
+            Some(this_paint_time)
+        } else {
+            None
+        };
+        AppState::<T>::new().set_prev_paint_time(self.window_id, prev); ////
+        ////self.state.prev_paint_time = prev;
+        request_anim
+    }
 
     fn do_layout(&mut self, piet: &mut Piet) {
         let mut layout_ctx = LayoutCtx {
             text_factory: piet.text(),
             window_id: self.window_id,
         };
-        AppState::<T>::new().window_layout(self.window_id, &mut layout_ctx); ////
+        AppState::<T>::new().with_window(self.window_id, |window, data, env| { ////
+            window.layout(&mut layout_ctx, data, env)
+        });
         ////self.window.layout(&mut layout_ctx, self.data, self.env);
     }
 
@@ -315,8 +245,16 @@ impl<T: Data + 'static + Default> SingleWindowState<T> { ////
             window_id: self.window_id,
             region: Rect::ZERO.into(),
         };
-        AppState::<T>::new().window_paint(self.window_id, &mut paint_ctx); ////
+        //  While the screen changes, the old screen is painted with the current screen at their offsets
+        let mut transition = T::with_arena(|arena| arena.take_transition(self.window_id)); ////
+        AppState::<T>::new().with_window(self.window_id, |window, data, env| match &mut transition { ////
+            Some(transition) => transition.paint(&mut paint_ctx, window, data, env),
+            None => window.paint(&mut paint_ctx, data, env),
+        });
         ////self.window.paint(&mut paint_ctx, self.data, self.env);
+        if let Some(transition) = transition { ////
+            T::with_arena(|arena| arena.put_transition(self.window_id, transition));
+        }
     }
 
     /// Send an event to the widget hierarchy.
@@ -344,6 +282,7 @@ impl<T: Data + 'static + Default> SingleWindowState<T> { ////
         */ ////
 
         let mut base_state = BaseState::default();
+        let handle = AppState::<T>::new().get_handle(self.window_id); ////
         let mut ctx = EventCtx {
             win_ctx,
             ////cursor: &mut cursor,
@@ -353,20 +292,21 @@ impl<T: Data + 'static + Default> SingleWindowState<T> { ////
             is_root: true,
             had_active: false, ////TODO self.window.has_active(),
             ////had_active: self.window.root.state.has_active,
-            window: &AppState::<T>::new().get_handle(self.window_id),
+            window: &handle, ////
             ////window: &self.state.handle,
             window_id: self.window_id,
         };
-        AppState::<T>::new().window_event(self.window_id, &mut ctx, &event); ////
-        ////self.window.event(&mut ctx, &event, self.data, self.env);
-
+        AppState::<T>::new().with_window(self.window_id, |window, data, env| { ////
+            window.event(&mut ctx, &event, data, env);
+            ////self.window.event(&mut ctx, &event, self.data, self.env);
+            if ctx.base_state.request_focus {
+                let focus_event = Event::FocusChanged(true);
+                window.event(&mut ctx, &focus_event, data, env); ////
+                ////self.window
+                    ////.event(&mut ctx, &focus_event, self.data, self.env);
+            }
+        });
         let is_handled = ctx.is_handled;
-        if ctx.base_state.request_focus {
-            let focus_event = Event::FocusChanged(true);
-            AppState::<T>::new().window_event(self.window_id, &mut ctx, &focus_event); ////
-            ////self.window
-                ////.event(&mut ctx, &focus_event, self.data, self.env);
-        }
         let needs_inval = ctx.base_state.needs_inval;
         let request_anim = ctx.base_state.request_anim;
         /* ////
@@ -433,7 +373,7 @@ impl<T: Data + 'static + Default> SingleWindowState<T> { ////
     }
 }
 
-impl<T: Data + 'static + Default> AppState<T> { ////
+impl<T: AppData> AppState<T> { ////
 ////impl<T: Data + 'static> AppState<T> {
     pub(crate) fn new(
         ////data: T,
@@ -452,21 +392,17 @@ impl<T: Data + 'static + Default> AppState<T> { ////
         } ////))
     }
 
-    /* ////
-        fn get_menu_cmd(&self, window_id: WindowId, cmd_id: u32) -> Option<Command> {
-            self.windows
-                .windows
-                .get(&window_id)
-                .and_then(|w| w.get_menu_cmd(cmd_id))
-        }
-
-        /// A helper fn for setting up the `DelegateCtx`. Takes a closure with
-        /// an arbitrary return type `R`, and returns `Some(R)` if an `AppDelegate`
-        /// is configured.
-        fn with_delegate<R, F>(&mut self, id: WindowId, f: F) -> Option<R>
-        where
-            F: FnOnce(&mut Box<dyn AppDelegate<T>>, &mut T, &Env, &mut DelegateCtx) -> R,
-        {
+    /// A helper fn for setting up the `DelegateCtx`. Takes a closure with
+    /// an arbitrary return type `R`, and returns `Some(R)` if an `AppDelegate`
+    /// is configured.
+    fn with_delegate<R, F>(&mut self, id: WindowId, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut dyn AppDelegate<T>, &mut T, &Env, &mut DelegateCtx) -> R, ////
+        ////F: FnOnce(&mut Box<dyn AppDelegate<T>>, &mut T, &Env, &mut DelegateCtx) -> R,
+    {
+        //  Move the delegate out of the arena while it runs, like Widgets and Windows
+        let (mut delegate, mut data, env, mut command_queue) = T::with_arena(|arena| arena.take_delegate())?; ////
+        /* ////
             let AppState {
                 ref mut delegate,
                 ref mut command_queue,
@@ -474,24 +410,54 @@ impl<T: Data + 'static + Default> AppState<T> { ////
                 ref env,
                 ..
             } = self;
-            let mut ctx = DelegateCtx {
-                source_id: id,
-                command_queue,
-            };
+        */ ////
+        let mut ctx = DelegateCtx {
+            source_id: id,
+            command_queue: &mut command_queue, ////
+            ////command_queue,
+        };
+        let result = f(&mut delegate, &mut data, &env, &mut ctx); ////
+        T::with_arena(move |arena| arena.put_delegate(delegate, data, env, command_queue)); ////
+        Some(result) ////
+        /* ////
             if let Some(delegate) = delegate {
                 Some(f(delegate, data, env, &mut ctx))
             } else {
                 None
             }
+        */ ////
+    }
+
+    fn delegate_event(&mut self, id: WindowId, event: Event) -> Option<Event> {
+        //  Without a delegate, or while the Application Data is in use by an outer call, the Widgets get the event
+        if T::with_arena(|arena| arena.delegate_ready()) { ////
+        ////if self.delegate.is_some() {
+            self.with_delegate(id, |del, data, env, ctx| del.event(event, data, env, ctx))
+                .unwrap_or(None) ////  Can't fail, the delegate is ready
+                ////.unwrap()
+        } else {
+            Some(event)
         }
+    }
 
-        fn delegate_event(&mut self, id: WindowId, event: Event) -> Option<Event> {
-            if self.delegate.is_some() {
-                self.with_delegate(id, |del, data, env, ctx| del.event(event, data, env, ctx))
-                    .unwrap()
-            } else {
-                Some(event)
-            }
+    /// Set the `AppDelegate` that sees every event before the Widgets
+    pub(crate) fn set_delegate(&mut self, delegate: T::Delegate) { ////
+        T::with_arena(move |arena| arena.set_delegate(delegate));
+    }
+
+    /// Tell the `AppDelegate` that a window has been added
+    pub(crate) fn window_added(&mut self, id: WindowId) { ////
+        self.with_delegate(id, |del, data, env, ctx| {
+            del.window_added(id, data, env, ctx)
+        });
+    }
+
+    /* ////
+        fn get_menu_cmd(&self, window_id: WindowId, cmd_id: u32) -> Option<Command> {
+            self.windows
+                .windows
+                .get(&window_id)
+                .and_then(|w| w.get_menu_cmd(cmd_id))
         }
 
         fn connect(&mut self, id: WindowId, handle: WindowHandle) {
@@ -502,31 +468,196 @@ impl<T: Data + 'static + Default> AppState<T> { ////
         }
     */ ////
 
-    /* Already implemented in GlobalWindows
     pub(crate) fn add_window(&mut self, id: WindowId, window: WindowBox<T>) { ////
     ////pub(crate) fn add_window(&mut self, id: WindowId, window: Window<T>) {
-        self.add_window(id, window); ////
+        T::with_arena(|arena| arena.add_window(id, window)); ////
         ////self.windows.add(id, window);
     }
-    */
-    
-    #[allow(dead_code)] ////
-    fn remove_window(&mut self, _id: WindowId) -> Option<WindowHandle<DruidHandler<T>>> { ////
-    ////fn remove_window(&mut self, id: WindowId) -> Option<WindowHandle> {
-        None //// TODO
-        /* ////
-            let res = self.windows.remove(id);
-            self.with_delegate(id, |del, data, env, ctx| {
-                del.window_removed(id, data, env, ctx)
+
+    /// Store the Window Handler for the window ID
+    pub(crate) fn add_handler(&mut self, id: WindowId, handler: DruidHandler<T>) { ////
+        T::with_arena(|arena| arena.add_handler(id, handler));
+    }
+
+    /// Set the size of the platform window for the window ID
+    pub(crate) fn set_window_size(&mut self, id: WindowId, size: Option<Size>) { ////
+        T::with_arena(|arena| arena.set_window_size(id, size));
+    }
+
+    /// Attempt to create a platform window for the window ID, whose Window and Window Handler
+    /// have been stored by `WindowDesc::add_to_arena`
+    pub(crate) fn build_native(&mut self, id: WindowId) -> Result<WindowHandle<DruidHandler<T>>, PlatformError> { ////
+        let (handler, size) = T::with_arena(|arena| (arena.handler(id), arena.window_size(id)));
+        let mut builder: WindowBuilder<DruidHandler<T>> = WindowBuilder::new();
+        builder.set_handler(handler);
+        if let Some(size) = size {
+            builder.set_size(size);
+        }
+        builder.build()
+    }
+
+    /// Return a Window Handle that wraps the Window Handler for the window ID
+    pub(crate) fn get_handle(&self, window_id: WindowId) -> WindowHandle<DruidHandler<T>> { ////
+        let handler = T::with_arena(|arena| arena.handler(window_id));
+        WindowHandle(
+            PlatformWindowHandle {
+                window_id: window_id.0,
+                state: PlatformWindowState {
+                    window_id: window_id.0,
+                    handler,
+                }
+            }
+        )
+    }
+
+    /// Replace the Application Data
+    pub(crate) fn set_data(&mut self, data: T) { ////
+        T::with_arena(|arena| arena.set_data(data));
+    }
+
+    /// Replace the environment
+    pub(crate) fn set_env(&mut self, env: Env) { ////
+        T::with_arena(move |arena| arena.set_env(env));
+    }
+
+    /// Pop the next Command submitted by a Widget
+    fn pop_command(&mut self) -> Option<(WindowId, Command)> { ////
+        T::with_arena(|arena| arena.pop_command())
+    }
+
+    /// Return the time of the last animation frame painted for the window ID
+    fn prev_paint_time(&self, window_id: WindowId) -> Option<Instant> { ////
+        T::with_arena(|arena| arena.prev_paint_time(window_id))
+    }
+
+    /// Record the time of the last animation frame painted for the window ID
+    fn set_prev_paint_time(&mut self, window_id: WindowId, time: Option<Instant>) { ////
+        T::with_arena(|arena| arena.set_prev_paint_time(window_id, time));
+    }
+
+    /// Move the Window and Application Data out of the arena, run `f` on them and move them back.
+    /// Returns `None` if the Application Data is in use by an outer call.
+    fn with_window<R>( ////
+        &mut self,
+        window_id: WindowId,
+        f: impl FnOnce(&mut WindowBox<T>, &mut T, &Env) -> R,
+    ) -> Option<R> {
+        let (mut window, mut data, env) = T::with_arena(|arena| arena.take_window(window_id))?;
+        let result = f(&mut window, &mut data, &env);
+        T::with_arena(|arena| arena.put_window(window_id, window, data, env));
+        Some(result)
+    }
+
+    /// Handle a touch event at the (x,y) coordinates of the focused window, which is the window
+    /// shown on the display. Safe to call from interrupt handlers: the touch is queued and
+    /// handled by the `RunLoop`. The touch is dropped if the queue is full or no window is open.
+    pub fn handle_touch(x: u16, y: u16) { ////
+        if let Some(window_id) = focused_window() {
+            post_touch(window_id, x, y).ok();
+        }
+    }
+
+    /// Handle the back gesture or button on the focused window. Safe to call from interrupt handlers.
+    /// The current screen is sent `Event::Back`, and the `Navigator` pops the screen if no `Widget` handles it.
+    pub fn handle_back() { ////
+        if let Some(window_id) = focused_window() {
+            post_event(window_id, QueuedEvent::Back).ok();
+        }
+    }
+
+    /// Apply the screen changes requested through `Navigator`s. The old screen is sent
+    /// `Event::Hidden` and the new screen is sent `Event::Shown` and an update. With a
+    /// `Transition`, the old screen is hidden or released when the transition completes.
+    fn navigate(&mut self, win_ctx: &mut dyn WinCtx) { ////
+        while let Some((id, navigation)) = T::with_arena(|arena| arena.pop_navigation()) {
+            let open = T::with_arena(|arena| arena.is_window_open(id));
+            //  Complete the running transition first, so that a popped screen is under the current screen
+            if let Some(old) = T::with_arena(|arena| if open { arena.finish_transition(id) } else { None }) {
+                old.release();
+            }
+            if !open || (matches!(navigation, Navigation::Pop(_)) && T::with_arena(|arena| arena.screen_depth(id)) == 0) {
+                continue;  //  Window has been closed, or there is no screen to go back to
+            }
+            self.send_event(id, Event::Hidden, win_ctx);
+            let old = T::with_arena(|arena| match navigation {
+                Navigation::Push(screen, transition) => Some((arena.replace_screen(id, screen), transition, Some(transition))),
+                Navigation::Replace(screen, transition) => Some((arena.replace_screen(id, screen), transition, None)),
+                Navigation::Pop(transition) => arena.restore_screen(id)
+                    .map(|(old, pushed)| (old, transition.unwrap_or_else(|| pushed.reversed()), None)),
             });
-            res
-        */ ////
+            match old {
+                Some((old, transition, hide)) if !transition.is_none() => T::with_arena(|arena|
+                    arena.start_transition(id, ScreenTransition::new(old, transition, hide))),
+                //  Released outside the arena, because releasing takes the Widgets out of the arena
+                Some((old, transition, Some(_))) => {
+                    if let Err((_, old)) = T::with_arena(|arena| arena.hide_screen(id, old, transition)) {
+                        old.release();
+                    }
+                }
+                Some((old, _, None)) => old.release(),
+                None => {}
+            }
+            self.send_event(id, Event::Shown, win_ctx);
+            self.update_window(id, true, win_ctx);
+        }
     }
 
-    #[allow(dead_code)] ////
-    fn show_window(&mut self, _id: WindowId) {
+    /// Send an event to the current screen of the window, without the `AppDelegate` or updates
+    fn send_event(&mut self, window_id: WindowId, event: Event, win_ctx: &mut dyn WinCtx) { ////
+        if let Some(mut win) = self.assemble_window_state(window_id) {
+            win.do_event_inner(event, win_ctx);
+        }
+    }
+
+    /// Send `update` to the window. Repaint the window if it requested invalidation or if `invalidate` is set.
+    fn update_window(&mut self, window_id: WindowId, invalidate: bool, win_ctx: &mut dyn WinCtx) { ////
+        let handle = self.get_handle(window_id);
+        let mut update_ctx = UpdateCtx {
+            text_factory: win_ctx.text_factory(),
+            window: &handle,
+            needs_inval: false,
+            window_id,
+            env_changed: true,
+        };
+        self.with_window(window_id, |window, data, env| window.update(&mut update_ctx, data, env));
+        if update_ctx.needs_inval || invalidate { ////  Repainted once by the `RunLoop`
+            update_ctx.window.invalidate();
+        }
+    }
+
+    /// Remove the Window and release its Widgets and hidden screens, then tell the `AppDelegate`
+    /// and release the window ID. Called when the platform destroys the window after `WindowHandle::close`.
+    fn remove_window(&mut self, id: WindowId) -> Option<WindowHandle<DruidHandler<T>>> { ////
+    ////fn remove_window(&mut self, id: WindowId) -> Option<WindowHandle> {
+        let handle = self.get_handle(id); ////
+        if !T::with_arena(|arena| arena.is_window_open(id)) { ////
+            return None;
+        }
+        if let Some(old) = T::with_arena(|arena| arena.finish_transition(id)) { ////
+            old.release();
+        }
+        let window = T::with_arena(|arena| arena.remove_window(id))?; ////
+        //  Released outside the arena, because releasing takes the Widgets out of the arena
+        window.release();
+        while let Some(screen) = T::with_arena(|arena| arena.remove_hidden_screen(id)) {
+            screen.release();
+        }
+        for screen in T::with_arena(|arena| arena.cancel_navigation(id)) {
+            screen.release();
+        }
+        let res = Some(handle); ////
+        ////let res = self.windows.remove(id);
+        self.with_delegate(id, |del, data, env, ctx| {
+            del.window_removed(id, data, env, ctx)
+        });
+        id.release(); ////
+        res
+    }
+
+    fn show_window(&mut self, id: WindowId) {
+        self.get_handle(id).bring_to_front_and_focus(); ////
         ////if let Some(state) = self.windows.state.get(&id) {
-            ////TODO1 state.handle.bring_to_front_and_focus();
+            ////state.handle.bring_to_front_and_focus();
         ////}
     }
 
@@ -549,16 +680,20 @@ impl<T: Data + 'static + Default> AppState<T> { ////
     }
 
     fn paint(&mut self, window_id: WindowId, piet: &mut Piet, ctx: &mut dyn WinCtx) -> bool {
+        self.navigate(ctx); ////  Screens pushed outside an event, e.g. before the `RunLoop` starts
         self.assemble_window_state(window_id)
             .map(|mut win| win.paint(piet, ctx))
             .unwrap_or(false)
     }
 
     fn do_event(&mut self, source_id: WindowId, event: Event, win_ctx: &mut dyn WinCtx) -> bool {
-        ////let event = self.delegate_event(source_id, event);
+        if matches!(event, Event::MouseDown(_) | Event::MouseUp(_) | Event::MouseMoved(_) | Event::Back)
+            && T::with_arena(|arena| arena.in_transition(source_id)) { ////
+            return true;  //  Input is blocked while the screen changes. Handled, so that `Back` doesn't pop the screen.
+        }
+        let event = self.delegate_event(source_id, event);
 
-        let (_is_handled, _dirty, _anim) = { ////
-        ////let (is_handled, dirty, anim) = if let Some(event) = event {
+        let (is_handled, dirty, anim) = if let Some(event) = event {
             /* ////
             // handle system window-level commands
             if let Event::Command(ref cmd) = event {
@@ -583,27 +718,20 @@ impl<T: Data + 'static + Default> AppState<T> { ////
             self.assemble_window_state(source_id)
                 .map(|mut win| win.do_event_inner(event, win_ctx))
                 .unwrap_or((false, false, false))
-        };
-        /* ////
-        else {
+        } else {
             // if the event was swallowed by the delegate we consider it handled?
             (true, false, false)
         };
-        */ ////
 
-        let window_id = WindowId(1);  ////  Assume Window ID 1 is the first and only window
-        let mut update_ctx = UpdateCtx { ////
-            text_factory: win_ctx.text_factory(),
-            window: &self.get_handle(window_id),
-            needs_inval: false,
-            window_id,
-        };
-        self.window_update(window_id, &mut update_ctx); ////
-        //cortex_m::asm::bkpt(); ////
-        if update_ctx.needs_inval { ////
-            update_ctx.window.invalidate();
+        //  We send `update` to all windows, not just the active one. Hidden windows are
+        //  repainted by the `RunLoop` when they are shown.
+        let mut window_id = WindowId(0); ////
+        while let Some(id) = T::with_arena(|arena| arena.next_window(window_id)) { ////
+            window_id = id;
+            self.update_window(window_id, window_id == source_id && (anim || dirty), win_ctx);
         }
-        true
+        //cortex_m::asm::bkpt(); ////
+        is_handled
 
         /* ////
         let AppState {
@@ -621,6 +749,7 @@ impl<T: Data + 'static + Default> AppState<T> { ////
                     window: &state.handle,
                     needs_inval: false,
                     window_id: *id,
+                    env_changed: true,
                 };
                 window.update(&mut update_ctx, data, env);
                 if update_ctx.needs_inval || (*id == source_id && (anim || dirty)) {
@@ -640,7 +769,7 @@ impl<T: Data + 'static + Default> AppState<T> { ////
 
 }
 
-impl<T: Data + 'static + Default> DruidHandler<T> { ////
+impl<T: AppData> DruidHandler<T> { ////
 ////impl<T: Data + 'static> DruidHandler<T> {
     /// Note: the root widget doesn't go in here, because it gets added to the
     /// app state.
@@ -662,28 +791,83 @@ impl<T: Data + 'static + Default> DruidHandler<T> { ////
     /// This is principally because in certain cases (such as keydown on Windows)
     /// the OS needs to know if an event was handled.
     fn do_event(&mut self, event: Event, win_ctx: &mut dyn WinCtx) -> bool {
-        AppState::<T>::new().do_event(self.window_id, event, win_ctx) ////
-        /* ////
-            let result = self
-                .app_state
-                .borrow_mut()
-                .do_event(self.window_id, event, win_ctx);
-            self.process_commands(win_ctx);
-            result
-        */ ////
+        let result = AppState::<T>::new().do_event(self.window_id, event, win_ctx); ////
+        ////let result = self
+            ////.app_state
+            ////.borrow_mut()
+            ////.do_event(self.window_id, event, win_ctx);
+        self.process_commands(win_ctx);
+        AppState::<T>::new().navigate(win_ctx); ////
+        result
     }
 
-    /* ////
-        fn process_commands(&mut self, win_ctx: &mut dyn WinCtx) {
-            loop {
-                let next_cmd = self.app_state_U32.borrow_mut().command_queue.pop_front();
-                match next_cmd {
-                    Some((id, cmd)) => self.handle_cmd(id, cmd, win_ctx),
-                    None => break,
-                }
+    fn process_commands(&mut self, win_ctx: &mut dyn WinCtx) {
+        loop {
+            let next_cmd = AppState::<T>::new().pop_command(); ////
+            ////let next_cmd = self.app_state.borrow_mut().command_queue.pop_front();
+            match next_cmd {
+                Some((id, cmd)) => self.handle_cmd(id, cmd, win_ctx),
+                None => break,
+            }
+        }
+    }
+
+    /// Handle a command. Top level commands (e.g. for creating and destroying windows)
+    /// have their logic here; other commands are passed to the window.
+    fn handle_cmd(&mut self, window_id: WindowId, cmd: Command, win_ctx: &mut dyn WinCtx) {
+        //FIXME: we need some way of getting the correct `WinCtx` for this window.
+        match cmd.selector { ////
+            sys_cmd::CLOSE_WINDOW => self.close_window(cmd, window_id),
+            sys_cmd::SHOW_WINDOW => self.show_window(cmd),
+            sys_cmd::NEW_WINDOW => self.new_window(cmd),
+            _ => {
+                let event = Event::Command(cmd);
+                AppState::<T>::new().do_event(window_id, event, win_ctx); ////
+                ////self.app_state.borrow_mut().do_event(window_id, event, win_ctx);
             }
         }
+    }
+
+    /// Open the window given by the command's argument, which was stored in the arena by
+    /// `EventCtx::new_window`, and give it focus
+    fn new_window(&mut self, cmd: Command) { ////
+        let id: WindowId = match cmd.get_object() {
+            Some(id) => id,
+            None => return,  //  New window command is missing the window ID
+        };
+        if !T::with_arena(|arena| arena.is_window_open(id)) {
+            return;
+        }
+        let mut state = AppState::<T>::new();
+        let window = match state.build_native(id) {
+            Ok(window) => window,
+            Err(_) => return,
+        };
+        state.window_added(id);
+        window.open();
+        window.bring_to_front_and_focus();
+    }
 
+    /// Close the window given by the command's argument, or the window that submitted the command.
+    /// The window is torn down when the `RunLoop` destroys it.
+    fn close_window(&mut self, cmd: Command, window_id: WindowId) { ////
+        let id = cmd.get_object().unwrap_or(window_id);
+        AppState::<T>::new().get_handle(id).close();
+    }
+
+    /// Show the window given by the command's argument and give it focus
+    fn show_window(&mut self, cmd: Command) { ////
+        let id: WindowId = match cmd.get_object() {
+            Some(id) => id,
+            None => return,  //  Show window command is missing the window ID
+        };
+        if !T::with_arena(|arena| arena.is_window_open(id)) {
+            return;
+        }
+        AppState::<T>::new().show_window(id);
+    }
+
+    /* ////
         fn handle_system_cmd(&mut self, cmd_id: u32, win_ctx: &mut dyn WinCtx) {
             let cmd = self.app_state_U32.borrow().get_menu_cmd(self.window_id, cmd_id);
             match cmd {
@@ -697,8 +881,6 @@ impl<T: Data + 'static + Default> DruidHandler<T> { ////
             self.process_commands(win_ctx)
         }
 
-        /// Handle a command. Top level commands (e.g. for creating and destroying windows)
-        /// have their logic here; other commands are passed to the window.
         fn handle_cmd(&mut self, window_id: WindowId, cmd: Command, win_ctx: &mut dyn WinCtx) {
             //FIXME: we need some way of getting the correct `WinCtx` for this window.
             match &cmd.selector {
@@ -790,7 +972,7 @@ impl<T: Data + 'static + Default> DruidHandler<T> { ////
     */ ////
 }
 
-impl<T: Data + 'static + Default> WinHandler<DruidHandler<T>> for DruidHandler<T> { ////
+impl<T: AppData> WinHandler<DruidHandler<T>> for DruidHandler<T> { ////
 ////impl<T: Data + 'static> WinHandler for DruidHandler<T> {
     /* //// TODO1
     fn connect(&mut self, handle: &WindowHandle<DruidHandler<T>>) { ////
@@ -851,6 +1033,15 @@ impl<T: Data + 'static + Default> WinHandler<DruidHandler<T>> for DruidHandler<T
         }
     */ ////
 
+    /// Send `Event::Back` to the current screen, and pop the screen if no `Widget` handles it
+    fn back(&mut self, ctx: &mut dyn WinCtx) { ////
+        if !AppState::<T>::new().do_event(self.window_id, Event::Back, ctx) {
+            Navigator::<T>::new(self.window_id).pop().ok();  //  Nothing to go back to on the first screen
+        }
+        self.process_commands(ctx);
+        AppState::<T>::new().navigate(ctx);
+    }
+
     fn got_focus(&mut self, ctx: &mut dyn WinCtx) {
         AppState::<T>::new().window_got_focus(self.window_id, ctx); ////
         /* ////
@@ -860,6 +1051,11 @@ impl<T: Data + 'static + Default> WinHandler<DruidHandler<T>> for DruidHandler<T
         */ ////
     }
 
+    /// Tear down the window after `WindowHandle::close`
+    fn destroy(&mut self, _ctx: &mut dyn WinCtx) { ////
+        AppState::<T>::new().remove_window(self.window_id);
+    }
+
     fn get_window_id(&self) -> WindowIdType { ////
         self.window_id.0
     }
@@ -868,18 +1064,22 @@ impl<T: Data + 'static + Default> WinHandler<DruidHandler<T>> for DruidHandler<T
         AppState::<T>::new().add_handler(WindowId(window_id), handler);
     }
 
-    /* ////
-        fn timer(&mut self, token: TimerToken, ctx: &mut dyn WinCtx) {
-            self.do_event(Event::Timer(token), ctx);
-        }
+    fn get_handler(&self, window_id: WindowIdType) -> DruidHandler<T> { ////
+        T::with_arena(|arena| arena.handler(WindowId(window_id)))
+    }
 
+    fn timer(&mut self, token: TimerToken, ctx: &mut dyn WinCtx) {
+        self.do_event(Event::Timer(token), ctx);
+    }
+
+    /* ////
         fn as_any(&mut self) -> &mut dyn Any {
             self
         }
     */ ////
 }
 
-impl<T: Data + 'static + Default> Default for Windows<T> { ////
+impl<T: AppData> Default for Windows<T> { ////
 ////impl<T: Data> Default for Windows<T> {
     fn default() -> Self {
         Windows {
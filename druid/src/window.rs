@@ -16,13 +16,16 @@
 
 ////use std::sync::atomic::{AtomicU32, Ordering};
 
+use core::cell::RefCell; ////
 use core::marker::PhantomData; ////
-use crate::kurbo::{Point, Rect, Size};
+use critical_section::Mutex; ////
+use crate::kurbo::{Point, Rect, Size, Vec2}; ////
+////use crate::kurbo::{Point, Rect, Size};
 
 ////use crate::shell::WindowHandle;
 use crate::{
-    BoxConstraints, /* Command, */ Data, Env, Event, EventCtx, LayoutCtx, /* LocalizedString, MenuDesc, */ ////
-    PaintCtx, UpdateCtx, Widget, WidgetPod, WindowIdType, MAX_WINDOWS
+    BoxConstraints, CapacityError, /* Command, */ AppData, Env, Event, EventCtx, LayoutCtx, /* LocalizedString, MenuDesc, */ ////
+    PaintCtx, UpdateCtx, Widget, WidgetBox, WidgetPod, WindowIdType, ////
 };
 
 /// A unique identifier for a window.
@@ -31,14 +34,46 @@ use crate::{
 pub struct WindowId(pub WindowIdType); ////
 ////pub struct WindowId(u32);
 
-static mut WINDOW_ID_COUNTER: WindowIdType = 1; ////
+/// Window IDs that have been assigned, and the IDs of closed windows that may be reused
+struct WindowIds {
+    /// IDs released by closed windows
+    free: heapless::Vec<WindowIdType, { WindowIdType::MAX as usize }>,
+    /// The lowest ID that has never been assigned. ID 0 is not used.
+    next: WindowIdType,
+}
+
+impl WindowIds {
+    const fn new() -> Self {
+        WindowIds { free: heapless::Vec::new(), next: 1 }
+    }
+
+    /// Assign an ID, reusing a released ID if possible
+    fn next(&mut self) -> WindowIdType {
+        if let Some(id) = self.free.pop() {
+            return id;
+        }
+        let id = self.next;
+        assert!(id < WindowIdType::MAX, "too many windows"); //// Capacity is checked by the arena
+        self.next = id + 1;
+        id
+    }
+
+    /// Release an assigned ID. Releasing an ID twice does nothing.
+    fn release(&mut self, id: WindowIdType) {
+        if id != 0 && id < self.next && !self.free.contains(&id) {
+            self.free.push(id).ok();  //  Can't fail, there are fewer ids than slots
+        }
+    }
+}
+
+static WINDOW_IDS: Mutex<RefCell<WindowIds>> = Mutex::new(RefCell::new(WindowIds::new())); ////
 ////static WINDOW_ID_COUNTER: AtomicU32 = AtomicU32::new(1);
 
 /// Per-window state not owned by user code.
 #[derive(Clone)] ////
-pub struct Window<T: Data + 'static + Default, W: Widget<T> + 'static> { ////
+pub struct Window<T: AppData> { ////
 ////pub struct Window<T: Data> {
-    pub(crate) root: WidgetPod<T, W>, ////
+    pub(crate) root: WidgetPod<T, WidgetBox<T>>, ////
     ////pub(crate) root: WidgetPod<T, Box<dyn Widget<T>>>,
     ////pub(crate) title: LocalizedString<T>,
     size: Size,
@@ -48,17 +83,18 @@ pub struct Window<T: Data + 'static + Default, W: Widget<T> + 'static> { ////
     // delegate?
 }
 
-impl<T: Data + 'static + Default, W: Widget<T> + 'static> Window<T, W> { ////
+impl<T: AppData> Window<T> { ////
 ////impl<T: Data> Window<T> {
-    pub fn new(
+    /// Create a `Window` rooted at the `Widget`. The `Widget` type must be in the `WidgetSet` of the Data type.
+    pub fn new<W: Widget<T> + Clone>( ////
         root: W,
         ////root: &dyn Widget<T>,
         ////title: LocalizedString<T>,
         ////menu: Option<MenuDesc<T>>,
-    ) -> Self {
+    ) -> Self
+    where T::Widgets: From<W> { ////
         Window {
-            root: WidgetPod::new(root), ////
-            ////root: WidgetPod::new(WidgetBox::new(root)), ////
+            root: WidgetPod::new(WidgetBox::new(root)), ////
             ////root: WidgetPod::new(Box::new(root)),
             size: Size { //// TODO: Allow auto size
                 width: super::env::WINDOW_WIDTH as f64,
@@ -72,6 +108,20 @@ impl<T: Data + 'static + Default, W: Widget<T> + 'static> Window<T, W> { ////
         }
     }
 
+    /// Create a `Window` rooted at the `Widget`, or return an error if the arena is full.
+    /// The `Widget` is released on error.
+    pub fn try_new<W: Widget<T> + Clone>(root: W) -> Result<Self, CapacityError> ////
+    where T::Widgets: From<W> {
+        Ok(Window {
+            root: WidgetPod::new(WidgetBox::try_new(root)?),
+            size: Size {
+                width: super::env::WINDOW_WIDTH as f64,
+                height: super::env::WINDOW_HEIGHT as f64,
+            },
+            phantom_data: PhantomData,
+        })
+    }
+
     pub fn event(&mut self, ctx: &mut EventCtx<T>, event: &Event, data: &mut T, env: &Env) { ////
     ////pub fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
         if let Event::Size(size) = event {
@@ -105,6 +155,21 @@ impl<T: Data + 'static + Default, W: Widget<T> + 'static> Window<T, W> { ////
         paint_ctx.with_child_ctx(visible, |ctx| self.root.paint(ctx, data, env));
     }
 
+    /// Paint the root `Widget` moved by the offset, e.g. while the screen slides in.
+    /// The layout of the root `Widget` is restored afterwards, so events are not affected.
+    pub fn paint_at(&mut self, paint_ctx: &mut PaintCtx, offset: Vec2, data: &T, env: &Env) { ////
+        let visible = Rect::from_origin_size(Point::ZERO, self.size);
+        let layout_rect = self.root.get_layout_rect();
+        self.root.set_layout_rect(layout_rect + offset);
+        paint_ctx.with_child_ctx(visible, |ctx| self.root.paint_with_offset(ctx, data, env));
+        self.root.set_layout_rect(layout_rect);
+    }
+
+    /// The size of the window
+    pub fn size(&self) -> Size { ////
+        self.size
+    }
+
     /* ////
     pub(crate) fn update_title(&mut self, win_handle: &WindowHandle, data: &T, env: &Env) {
         if self.title.resolve(data, env) {
@@ -120,22 +185,50 @@ impl<T: Data + 'static + Default, W: Widget<T> + 'static> Window<T, W> { ////
     }
     */ ////
 
-    /*
-    pub fn to_type(&mut self) -> WindowType<T> { ////
-        W::to_window_type(self.clone)
+    /// Release the Widget IDs and arena slots of the root `Widget` and its children
+    pub fn release(self) { ////
+        <WidgetBox<T> as Widget<T>>::release(self.root.into_inner());
+    }
+
+    /// Return true if a `Widget` in the `Window` is active
+    pub fn has_active(&self) -> bool { ////
+        self.root.state.has_active
     }
-    */
 }
 
 impl WindowId {
-    /// Allocate a new, unique window id.
-    ///
-    /// Do note that if we create 4 billion windows there may be a collision.
+    /// Allocate a window id that no open window has, reusing the id of a closed window if possible.
     pub fn next() -> WindowId {
-        let id = unsafe { WINDOW_ID_COUNTER }; ////
-        assert!((id as usize) < MAX_WINDOWS, "too many windows");
-        unsafe { WINDOW_ID_COUNTER += 1 }; ////    
+        let id = critical_section::with(|cs| WINDOW_IDS.borrow(cs).borrow_mut().next()); ////
         ////let id = WINDOW_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
         WindowId(id)
     }
+
+    /// Release the id of a closed window, so that it may be reused by `next`.
+    /// Releasing an id twice does nothing.
+    pub(crate) fn release(self) {
+        critical_section::with(|cs| WINDOW_IDS.borrow(cs).borrow_mut().release(self.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuse_closed_window_ids() {
+        //  A local allocator, so that windows opened by other tests don't take the released id
+        let mut ids = WindowIds::new();
+        let first = ids.next();
+        let second = ids.next();
+        assert!(first != second && first != 0);
+        ids.release(first);
+        //  Releasing twice doesn't free the id again
+        ids.release(first);
+        //  Ids that were never assigned are not released
+        ids.release(0);
+        ids.release(200);
+        assert_eq!(ids.next(), first);
+        assert_eq!(ids.next(), 3);
+    }
 }
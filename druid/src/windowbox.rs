@@ -1,41 +1,36 @@
 //! `WindowBox` contains a `Window`. Allows for dynamic dispatch with static `Windows` in `[no_std]`.
 
+use crate::kurbo::{Size, Vec2};
 use crate::{
-    Data, Env, Event, EventCtx, LayoutCtx, PaintCtx, UpdateCtx, Window,
-    widget::{Align, Flex, Padding},
+    AppData, Env, Event, EventCtx, LayoutCtx, PaintCtx, UpdateCtx, Window,
 };
 
-/// Boxed version of a `Window`
+/// Boxed version of a `Window`. Every `Window` is rooted at a `WidgetBox`, so the root may be
+/// any `Widget` in the `WidgetSet` of the Data type.
 #[derive(Clone, Default)]
-pub struct WindowBox<D: Data + 'static + Default>(
-    pub WindowType<D>,
+pub struct WindowBox<D: AppData>(
+    Option<Window<D>>,
 );
 
-/// Enum to store each `Window`
-#[derive(Clone)]
-pub enum WindowType<D: Data + 'static + Default> {
-    None,
-    Align(Window<D, Align<D>>),
-    Flex(Window<D, Flex<D>>),
-    Padding(Window<D, Padding<D>>),
-}
-
-impl<D: Data + 'static + Default> Default for WindowType<D> {
-    fn default() -> Self { WindowType::None }
-}
-
 /// Generic implementation of `WindowBox`
-impl<D: Data + 'static + Default> WindowBox<D> {
+impl<D: AppData> WindowBox<D> {
     /// Create a new empty box without a `Window`
-    pub fn new() -> Self {
+    pub const fn empty() -> Self {
+        WindowBox(
+            None,
+        )
+    }
+
+    /// Create a new box for the `Window`
+    pub fn new(window: Window<D>) -> Self {
         WindowBox(
-            WindowType::None,
+            Some(window),
         )
     }
 }
 
 /// Implementation of `WindowBox`. We just forward to the inner `Window`.
-impl<D: Data + 'static + Default> WindowBox<D> {
+impl<D: AppData> WindowBox<D> {
     pub fn event(
         &mut self, 
         ctx: &mut EventCtx<D>, 
@@ -43,12 +38,9 @@ impl<D: Data + 'static + Default> WindowBox<D> {
         data: &mut D, 
         env: &Env
     ) {
-        match &mut self.0 {
-            WindowType::Align(w)   => w.event(ctx, event, data, env),
-            WindowType::Flex(w)    => w.event(ctx, event, data, env),
-            WindowType::Padding(w) => w.event(ctx, event, data, env),
-            WindowType::None => {}
-        };
+        if let Some(w) = &mut self.0 {
+            w.event(ctx, event, data, env);
+        }
     }
 
     pub fn update(
@@ -57,12 +49,9 @@ impl<D: Data + 'static + Default> WindowBox<D> {
         data: &D, 
         env: &Env
     ) {
-        match &mut self.0 {
-            WindowType::Align(w)   => w.update(ctx, data, env),
-            WindowType::Flex(w)    => w.update(ctx, data, env),
-            WindowType::Padding(w) => w.update(ctx, data, env),
-            WindowType::None => {}
-        };
+        if let Some(w) = &mut self.0 {
+            w.update(ctx, data, env);
+        }
     }
 
     pub fn layout(
@@ -71,12 +60,9 @@ impl<D: Data + 'static + Default> WindowBox<D> {
         data: &D,
         env: &Env,
     ) {
-        match &mut self.0 {
-            WindowType::Align(w)   => w.layout(layout_ctx, data, env),
-            WindowType::Flex(w)    => w.layout(layout_ctx, data, env),
-            WindowType::Padding(w) => w.layout(layout_ctx, data, env),
-            WindowType::None => {}
-        };
+        if let Some(w) = &mut self.0 {
+            w.layout(layout_ctx, data, env);
+        }
     }
 
     pub fn paint(
@@ -85,22 +71,45 @@ impl<D: Data + 'static + Default> WindowBox<D> {
         data: &D, 
         env: &Env
     ) {
-        match &mut self.0 {
-            WindowType::Align(w)   => w.paint(paint_ctx, data, env),
-            WindowType::Flex(w)    => w.paint(paint_ctx, data, env),
-            WindowType::Padding(w) => w.paint(paint_ctx, data, env),
-            WindowType::None => {}
-        };
+        if let Some(w) = &mut self.0 {
+            w.paint(paint_ctx, data, env);
+        }
     }
 
-    pub fn has_active(
+    /// Paint the `Window` moved by the offset, e.g. while the screen slides in
+    pub fn paint_at(
         &mut self,
+        paint_ctx: &mut PaintCtx,
+        offset: Vec2,
+        data: &D,
+        env: &Env
+    ) {
+        if let Some(w) = &mut self.0 {
+            w.paint_at(paint_ctx, offset, data, env);
+        }
+    }
+
+    /// The size of the `Window`, or zero if the box is empty
+    pub fn size(&self) -> Size {
+        match &self.0 {
+            Some(w) => w.size(),
+            None => Size::ZERO,
+        }
+    }
+
+    /// Release the Widget IDs of the `Window`'s Widgets, when the window is closed
+    pub fn release(self) {
+        if let Some(w) = self.0 {
+            w.release();
+        }
+    }
+
+    pub fn has_active(
+        &self,
     ) -> bool {
-        match &mut self.0 {
-            WindowType::Align(w)   => w.root.state.has_active,
-            WindowType::Flex(w)    => w.root.state.has_active,
-            WindowType::Padding(w) => w.root.state.has_active,
-            WindowType::None => false
-        }        
+        match &self.0 {
+            Some(w) => w.has_active(),
+            None => false
+        }
     }
-}
\ No newline at end of file
+}
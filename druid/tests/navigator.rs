@@ -0,0 +1,151 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test the `Navigator` screen stack against the headless platform
+
+use core::marker::PhantomData;
+use std::sync::Mutex;
+
+use druid::widget::get_widget_id;
+use druid::{
+    AppData, AppLauncher, BaseState, BoxConstraints, Data, Env, Event, EventCtx, LayoutCtx,
+    NavError, Navigator, PaintCtx, Size, UpdateCtx, Widget, WidgetId, WindowDesc, MAX_SCREENS,
+};
+
+/// The screen events received by the `Screen` widgets, in order
+static LOG: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// A screen that logs `Shown`, `Hidden` and `Back`. Screen 99 handles `Back`.
+#[derive(Clone)]
+pub struct Screen<T: AppData> {
+    id: WidgetId,
+    name: u32,
+    phantom: PhantomData<T>,
+}
+
+impl<T: AppData> Screen<T> {
+    pub fn new(name: u32) -> Self {
+        Screen {
+            id: get_widget_id(),
+            name,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: AppData> Widget<T> for Screen<T> {
+    fn paint(&mut self, _ctx: &mut PaintCtx, _base_state: &BaseState, _data: &T, _env: &Env) {}
+
+    fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &T, _env: &Env) -> Size {
+        bc.max()
+    }
+
+    fn event(&mut self, ctx: &mut EventCtx<T>, event: &Event, _data: &mut T, _env: &Env) {
+        let name = match event {
+            Event::Shown => "shown",
+            Event::Hidden => "hidden",
+            Event::Back => "back",
+            _ => return,
+        };
+        LOG.lock().unwrap().push(format!("{} {}", name, self.name));
+        if let (Event::Back, 99) = (event, self.name) {
+            ctx.set_handled();
+        }
+    }
+
+    fn update(&mut self, _ctx: &mut UpdateCtx<T>, _old_data: Option<&T>, _data: &T, _env: &Env) {}
+
+    fn get_id(&self) -> WidgetId {
+        self.id
+    }
+}
+
+druid::widget_set! { pub Screens for State { Screen = Screen<State>, ..druid::builtin } }
+
+#[derive(Clone, Data, Default)]
+#[druid(app_state, widgets = "Screens")]
+struct State {}
+
+fn root() -> Screen<State> {
+    Screen::new(0)
+}
+
+/// Take the logged screen events
+fn events() -> Vec<String> {
+    std::mem::take(&mut *LOG.lock().unwrap())
+}
+
+//  The display shows one focused window per process, so the steps share one application
+#[test]
+fn navigate_screens() {
+    let desc = WindowDesc::new(root);
+    let nav = Navigator::<State>::new(desc.id);
+    let mut main_loop = AppLauncher::with_window(desc).build(State::default()).unwrap();
+    main_loop.run_once();
+    events();
+    assert_eq!(nav.pop(), Err(NavError::Empty));
+
+    //  Pushes are queued until the current event is done, but count towards the depth
+    nav.push(Screen::new(1)).unwrap();
+    nav.push(Screen::new(2)).unwrap();
+    assert_eq!(nav.depth(), 2);
+    assert!(events().is_empty());
+    main_loop.run_once();
+    assert_eq!(events(), ["hidden 0", "shown 1", "hidden 1", "shown 2"]);
+    assert_eq!(nav.depth(), 2);
+
+    //  Replacing releases the current screen without changing the depth
+    let replaced = Screen::<State>::new(3);
+    let replaced_id = replaced.get_id();
+    nav.replace(replaced).unwrap();
+    main_loop.run_once();
+    assert_eq!(events(), ["hidden 2", "shown 3"]);
+    assert_eq!(nav.depth(), 2);
+
+    //  An unhandled `Back` pops the current screen and releases it
+    State::handle_back();
+    main_loop.run_once();
+    assert_eq!(events(), ["back 3", "hidden 3", "shown 1"]);
+    assert_eq!(nav.depth(), 1);
+    assert!(!replaced_id.is_live());
+
+    //  A handled `Back` keeps the screen
+    nav.push(Screen::new(99)).unwrap();
+    main_loop.run_once();
+    events();
+    State::handle_back();
+    main_loop.run_once();
+    assert_eq!(events(), ["back 99"]);
+    assert_eq!(nav.depth(), 2);
+
+    //  Screens that can't be hidden are refused and released
+    while nav.depth() < MAX_SCREENS {
+        nav.push(Screen::new(50)).unwrap();
+    }
+    let refused = Screen::<State>::new(60);
+    let refused_id = refused.get_id();
+    assert_eq!(nav.push(refused), Err(NavError::Full));
+    assert!(!refused_id.is_live());
+    main_loop.run_once();
+    assert_eq!(nav.depth(), MAX_SCREENS);
+
+    //  Popping reverses the pushes, down to the root screen
+    for _ in 0..MAX_SCREENS {
+        nav.pop().unwrap();
+    }
+    assert_eq!(nav.depth(), 0);
+    main_loop.run_once();
+    assert_eq!(events().last().map(String::as_str), Some("shown 0"));
+    assert_eq!(nav.pop(), Err(NavError::Empty));
+}
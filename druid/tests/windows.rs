@@ -0,0 +1,186 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test multiple windows against the headless platform
+
+use std::sync::Mutex;
+
+use druid::widget::get_widget_id;
+use druid::{
+    commands, AppDelegate, AppLauncher, BaseState, BoxConstraints, CapacityError, Command, Data,
+    DelegateCtx, Env, Event, EventCtx, LayoutCtx, PaintCtx, Size, UpdateCtx, Widget, WidgetId,
+    WindowDesc, WindowId,
+};
+
+/// The taps, updates and window changes seen by the `Tapper` widgets and the delegate, in order
+static LOG: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// The IDs of the `Tapper` widgets, in order of creation
+static IDS: Mutex<Vec<WidgetId>> = Mutex::new(Vec::new());
+
+/// Run by the next `Tapper` that is tapped
+static ACTION: Mutex<Option<Action>> = Mutex::new(None);
+
+type Action = fn(&mut EventCtx<State>);
+
+/// A widget that logs taps and updates with its window ID
+#[derive(Clone)]
+pub struct Tapper {
+    id: WidgetId,
+}
+
+impl Tapper {
+    fn new() -> Self {
+        let id = get_widget_id();
+        IDS.lock().unwrap().push(id);
+        Tapper { id }
+    }
+}
+
+impl Widget<State> for Tapper {
+    fn paint(&mut self, _ctx: &mut PaintCtx, _base_state: &BaseState, _data: &State, _env: &Env) {}
+
+    fn layout(&mut self, _ctx: &mut LayoutCtx, bc: &BoxConstraints, _data: &State, _env: &Env) -> Size {
+        bc.max()
+    }
+
+    fn event(&mut self, ctx: &mut EventCtx<State>, event: &Event, data: &mut State, _env: &Env) {
+        if let Event::MouseDown(_) = event {
+            log(format!("tap {}", ctx.window_id().0));
+            data.taps += 1;
+            let action = ACTION.lock().unwrap().take();
+            if let Some(action) = action {
+                action(ctx);
+            }
+        }
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx<State>, _old_data: Option<&State>, _data: &State, _env: &Env) {
+        log(format!("update {}", ctx.window_id().0));
+    }
+
+    fn get_id(&self) -> WidgetId {
+        self.id
+    }
+}
+
+druid::widget_set! { pub Tappers for State { Tapper = Tapper, ..druid::builtin } }
+
+#[derive(Clone, Data, Default)]
+#[druid(app_state, widgets = "Tappers", delegate = "Windows")]
+struct State {
+    taps: u32,
+}
+
+/// Logs the windows that are added and removed
+struct Windows;
+
+impl AppDelegate<State> for Windows {
+    fn window_added(&mut self, id: WindowId, _data: &mut State, _env: &Env, _ctx: &mut DelegateCtx) {
+        log(format!("added {}", id.0));
+    }
+
+    fn window_removed(&mut self, id: WindowId, _data: &mut State, _env: &Env, _ctx: &mut DelegateCtx) {
+        log(format!("removed {}", id.0));
+    }
+}
+
+fn log(entry: String) {
+    LOG.lock().unwrap().push(entry);
+}
+
+/// Take the log
+fn events() -> Vec<String> {
+    std::mem::take(&mut *LOG.lock().unwrap())
+}
+
+/// Run `action` in the `Tapper` that handles the next tap
+fn on_tap(action: Action) {
+    *ACTION.lock().unwrap() = Some(action);
+}
+
+/// The window ID of the second window, which is closed and opened again
+static SECOND: Mutex<Option<WindowId>> = Mutex::new(None);
+
+//  The display shows one focused window per process, so the steps share one application
+#[test]
+fn open_focus_and_close_windows() {
+    let first = WindowDesc::new(Tapper::new);
+    let second = WindowDesc::new(Tapper::new);
+    let (a, b) = (first.id.0, second.id.0);
+    *SECOND.lock().unwrap() = Some(second.id);
+    let mut main_loop = AppLauncher::with_window(first)
+        .add_window(second)
+        .delegate(Windows)
+        .build(State::default())
+        .unwrap();
+    assert_eq!(events(), [format!("added {}", a), format!("added {}", b)]);
+    main_loop.run_once();
+    events();
+
+    //  Touches go to the first window, and data changes update every window
+    State::handle_touch(120, 120);
+    main_loop.run_once();
+    let log = events();
+    assert_eq!(log[0], format!("tap {}", a));
+    assert!(log.contains(&format!("update {}", a)));
+    assert!(log.contains(&format!("update {}", b)));
+
+    //  `SHOW_WINDOW` moves the focus, and the touches, to the second window
+    on_tap(|ctx| {
+        let second = SECOND.lock().unwrap().unwrap();
+        ctx.submit_command(Command::new(commands::SHOW_WINDOW, second), None).unwrap();
+    });
+    State::handle_touch(120, 120);
+    main_loop.run_once();
+    events();
+    State::handle_touch(120, 120);
+    main_loop.run_once();
+    assert_eq!(events()[0], format!("tap {}", b));
+
+    //  Closing the second window releases its current and hidden screens, and
+    //  moves the focus back to the first window
+    on_tap(|ctx| ctx.navigator().push(Tapper::new()).unwrap());
+    State::handle_touch(120, 120);
+    main_loop.run_once();
+    let (hidden, current) = {
+        let ids = IDS.lock().unwrap();
+        (ids[1], ids[2])
+    };
+    assert!(hidden.is_live() && current.is_live());
+    on_tap(|ctx| {
+        let id = ctx.window_id();
+        ctx.submit_command(Command::new(commands::CLOSE_WINDOW, id), None).unwrap();
+    });
+    State::handle_touch(120, 120);
+    main_loop.run_once();
+    main_loop.run_once();
+    assert!(events().contains(&format!("removed {}", b)));
+    assert!(!hidden.is_live() && !current.is_live());
+    State::handle_touch(120, 120);
+    main_loop.run_once();
+    assert_eq!(events()[0], format!("tap {}", a));
+
+    //  A new window reuses the closed window's ID, until `max_windows` windows are open
+    on_tap(|ctx| {
+        let second = SECOND.lock().unwrap().unwrap();
+        assert_eq!(ctx.new_window(WindowDesc::new(Tapper::new)), Ok(second));
+        assert_eq!(ctx.new_window(WindowDesc::new(Tapper::new)), Err(CapacityError::Windows));
+    });
+    State::handle_touch(120, 120);
+    main_loop.run_once();
+    main_loop.run_once();
+    assert!(events().contains(&format!("added {}", b)));
+    assert!(ACTION.lock().unwrap().is_none());
+}